@@ -0,0 +1,110 @@
+// bbcode.rs
+// A document's wiki part as BBCode for RichTextLabel, so in-game descriptions
+// are rendered straight from the doc instead of being copied into a field.
+
+use markdown::ParseOptions;
+use markdown::mdast::Node;
+
+use crate::index::{self, WikiPiece};
+
+/// Font sizes of the heading levels, RichTextLabel's default size being 16.
+const HEADING_SIZES: [u32; 6] = [28, 24, 20, 18, 16, 16];
+
+/// The BBCode of a markdown text : emphasis, lists, links, headings, quotes and code.
+/// `[[wiki links]]` become `[url=<link key>]` so `meta_clicked` gets the document name.
+/// Raw HTML, tables and footnotes are left out.
+pub fn to_bbcode(markdown: &str) -> String {
+    let Ok(root) = markdown::to_mdast(markdown, &ParseOptions::gfm()) else {
+        return escape(markdown);
+    };
+    blocks(root.children().map(Vec::as_slice).unwrap_or_default())
+}
+
+/// `[` opens a tag in BBCode.
+fn escape(text: &str) -> String {
+    text.replace('[', "[lb]")
+}
+
+/// Blocks separated by an empty line.
+fn blocks(nodes: &[Node]) -> String {
+    nodes
+        .iter()
+        .filter_map(block)
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn block(node: &Node) -> Option<String> {
+    Some(match node {
+        Node::Paragraph(p) => inline(&p.children),
+        Node::Heading(h) => format!(
+            "[font_size={}][b]{}[/b][/font_size]",
+            HEADING_SIZES[(h.depth as usize).clamp(1, 6) - 1],
+            inline(&h.children)
+        ),
+        Node::List(list) => {
+            let (open, close) = match list.ordered {
+                true => ("[ol type=1]", "[/ol]"),
+                false => ("[ul]", "[/ul]"),
+            };
+            let items: Vec<_> = list
+                .children
+                .iter()
+                .map(|item| {
+                    let children = item.children().map(Vec::as_slice).unwrap_or_default();
+                    children
+                        .iter()
+                        .filter_map(block)
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .collect();
+            format!("{}{}{}", open, items.join("\n"), close)
+        }
+        Node::Blockquote(q) => format!("[indent]{}[/indent]", blocks(&q.children)),
+        Node::Code(code) => format!("[code]{}[/code]", escape(&code.value)),
+        Node::ThematicBreak(_) => String::new(),
+        _ => return None,
+    })
+}
+
+/// Inline nodes, consecutive texts joined first so wiki links split by the parser are found.
+fn inline(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    let mut text = String::new();
+    for node in nodes {
+        if let Node::Text(t) = node {
+            text.push_str(&t.value);
+            continue;
+        }
+        out.push_str(&wiki_text(&std::mem::take(&mut text)));
+        match node {
+            Node::Strong(n) => out.push_str(&format!("[b]{}[/b]", inline(&n.children))),
+            Node::Emphasis(n) => out.push_str(&format!("[i]{}[/i]", inline(&n.children))),
+            Node::Delete(n) => out.push_str(&format!("[s]{}[/s]", inline(&n.children))),
+            Node::InlineCode(code) => {
+                out.push_str(&format!("[code]{}[/code]", escape(&code.value)))
+            }
+            Node::Link(link) => out.push_str(&format!(
+                "[url={}]{}[/url]",
+                link.url,
+                inline(&link.children)
+            )),
+            Node::Image(image) => out.push_str(&format!("[img]{}[/img]", image.url)),
+            Node::Break(_) => out.push('\n'),
+            _ => {}
+        }
+    }
+    out.push_str(&wiki_text(&text));
+    out
+}
+
+fn wiki_text(text: &str) -> String {
+    index::wiki_pieces(text)
+        .into_iter()
+        .map(|piece| match piece {
+            WikiPiece::Text(t) => escape(t),
+            WikiPiece::Link { key, label } => format!("[url={}]{}[/url]", key, escape(label)),
+        })
+        .collect()
+}
@@ -0,0 +1,123 @@
+// budgets.rs
+// Content budgets of a file type (`budgets:` in its config), checked over
+// every document so content regressions fail CI like code regressions.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use doke::GodotValue;
+use yaml_rust2::Yaml;
+
+use crate::config::ConfigError;
+use crate::spreadsheet;
+
+#[derive(Debug, Clone, Default)]
+pub struct Budgets {
+    /// Most documents the type may have
+    pub max_documents: Option<usize>,
+    /// field -> most characters its text may have
+    pub max_length: Vec<(String, usize)>,
+    /// field -> lowest percentage of documents that must set it
+    pub coverage: Vec<(String, f64)>,
+}
+
+/// A parsed document : its path, built value and frontmatter.
+pub type Document = (PathBuf, GodotValue, HashMap<String, GodotValue>);
+
+impl Budgets {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let invalid = |key: &str, v: &Yaml| {
+            ConfigError::Invalid(format!("budgets.{} has an invalid value : {:?}", key, v))
+        };
+        let mut budgets = Self::default();
+        match &y["max_documents"] {
+            Yaml::BadValue => {}
+            Yaml::Integer(i) if *i >= 0 => budgets.max_documents = Some(*i as usize),
+            other => return Err(invalid("max_documents", other)),
+        }
+        if let Some(lengths) = y["max_length"].as_hash() {
+            for (k, v) in lengths {
+                match (k.as_str(), v) {
+                    (Some(field), Yaml::Integer(i)) if *i >= 0 => {
+                        budgets.max_length.push((field.to_string(), *i as usize))
+                    }
+                    _ => return Err(invalid("max_length", v)),
+                }
+            }
+        }
+        if let Some(coverage) = y["coverage"].as_hash() {
+            for (k, v) in coverage {
+                let percent = match v {
+                    Yaml::Integer(i) => Some(*i as f64),
+                    Yaml::Real(_) => v.as_f64(),
+                    _ => None,
+                };
+                match (k.as_str(), percent) {
+                    (Some(field), Some(p)) if (0.0..=100.0).contains(&p) => {
+                        budgets.coverage.push((field.to_string(), p))
+                    }
+                    _ => return Err(invalid("coverage", v)),
+                }
+            }
+        }
+        Ok(budgets)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max_documents.is_none() && self.max_length.is_empty() && self.coverage.is_empty()
+    }
+
+    /// Checks the parsed documents of the type, one message per exceeded budget.
+    /// Fields are looked up like `export_csv` does.
+    pub fn check(&self, documents: &[Document]) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if let Some(max) = self.max_documents
+            && documents.len() > max
+        {
+            diagnostics.push(format!(
+                "{} documents exceed the maximum of {}",
+                documents.len(),
+                max
+            ));
+        }
+        for (field, max) in &self.max_length {
+            for (path, value, fm) in documents {
+                let len = spreadsheet::cell(spreadsheet::lookup(value, fm, field))
+                    .chars()
+                    .count();
+                if len > *max {
+                    diagnostics.push(format!(
+                        "{} : {} is {} characters long, over the maximum of {}",
+                        path.display(),
+                        field,
+                        len,
+                        max
+                    ));
+                }
+            }
+        }
+        for (field, min) in &self.coverage {
+            if documents.is_empty() {
+                continue;
+            }
+            let missing: Vec<String> = documents
+                .iter()
+                .filter(|(_, value, fm)| {
+                    spreadsheet::cell(spreadsheet::lookup(value, fm, field)).is_empty()
+                })
+                .map(|(path, _, _)| path.display().to_string())
+                .collect();
+            let percent = 100.0 * (documents.len() - missing.len()) as f64 / documents.len() as f64;
+            if percent < *min {
+                diagnostics.push(format!(
+                    "{} is set in {:.1}% of the documents, under the minimum of {}% (missing in {})",
+                    field,
+                    percent,
+                    min,
+                    missing.join(", ")
+                ));
+            }
+        }
+        diagnostics
+    }
+}
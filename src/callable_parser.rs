@@ -0,0 +1,183 @@
+// callable_parser.rs
+// Parsers written in GDScript : a Callable registered like a plugin parser, so teams
+// without Rust can extend the pipeline.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::thread::{self, ThreadId};
+
+use doke::GodotValue;
+use doke::semantic::{DokeNode, DokeNodeState, DokeParser};
+use godot::prelude::*;
+use thiserror::Error;
+
+use crate::config::variant_to_yaml;
+use crate::frontmatter::yaml_to_godot;
+use crate::import::{Conversion, Converter, frontmatter_to_dictionary};
+use crate::parser_api::OPTIONS_KEY;
+use crate::parser_context::with_current;
+
+#[derive(Debug, Error)]
+pub enum CallableParserError {
+    #[error("{0}")]
+    Rejected(String),
+    #[error("the parser returned an unusable value : {0}")]
+    InvalidValue(String),
+    #[error("GDScript parsers only run on the thread that registered them")]
+    WrongThread,
+}
+
+/// Calls `callable(statement : String, context : Dictionary) -> Dictionary` on each
+/// unresolved statement. The context has the `frontmatter`, the `parse_data` of the
+/// statement, the `options` the file type gives the parser and the `document` path.
+/// The parser returns :
+/// - an empty Dictionary to leave the statement to the other parsers
+/// - `{value = ...}` to resolve it to a value
+/// - `{type = "Heal", fields = {...}}` to resolve it to a resource
+/// - `{error = "..."}` to mark it as wrong
+#[derive(Debug)]
+pub struct CallableParser {
+    callable: ThreadBound<Callable>,
+}
+
+/// A value only reached from the thread that made it, so it can sit in the shared
+/// parser registry. Other threads get nothing, and leak it if they drop the last copy.
+struct ThreadBound<T> {
+    value: ManuallyDrop<T>,
+    thread: ThreadId,
+}
+
+// SAFETY: the value is only touched through `get` and `drop`, which both check
+// they run on the thread that made it.
+unsafe impl<T> Send for ThreadBound<T> {}
+unsafe impl<T> Sync for ThreadBound<T> {}
+
+impl<T> ThreadBound<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value: ManuallyDrop::new(value),
+            thread: thread::current().id(),
+        }
+    }
+
+    fn get(&self) -> Option<&T> {
+        (thread::current().id() == self.thread).then_some(&*self.value)
+    }
+}
+
+impl<T> Drop for ThreadBound<T> {
+    fn drop(&mut self) {
+        if thread::current().id() == self.thread {
+            // SAFETY: the value isn't used after this
+            unsafe { ManuallyDrop::drop(&mut self.value) }
+        }
+    }
+}
+
+impl<T> fmt::Debug for ThreadBound<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThreadBound")
+            .field("thread", &self.thread)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CallableParser {
+    pub fn new(callable: Callable) -> Self {
+        Self {
+            callable: ThreadBound::new(callable),
+        }
+    }
+
+    fn state(&self, node: &DokeNode, frontmatter: &Dictionary) -> Option<DokeNodeState> {
+        let Some(callable) = self.callable.get() else {
+            return Some(DokeNodeState::Error(Box::new(
+                CallableParserError::WrongThread,
+            )));
+        };
+        let returned = callable.call(&[
+            node.statement.to_variant(),
+            context(node, frontmatter).to_variant(),
+        ]);
+        state_from_result(&returned.try_to::<Dictionary>().ok()?)
+    }
+
+    fn process_node(&self, node: &mut DokeNode, frontmatter: &Dictionary) {
+        if matches!(node.state, DokeNodeState::Unresolved)
+            && let Some(state) = self.state(node, frontmatter)
+        {
+            node.state = state;
+        }
+        for child in &mut node.children {
+            self.process_node(child, frontmatter);
+        }
+    }
+}
+
+impl DokeParser for CallableParser {
+    fn process(&self, node: &mut DokeNode, frontmatter: &HashMap<String, GodotValue>) {
+        let frontmatter =
+            frontmatter_to_dictionary(frontmatter, &Conversion::default()).unwrap_or_default();
+        self.process_node(node, &frontmatter);
+    }
+}
+
+/// The context parsers get a statement with.
+pub(crate) fn context(node: &DokeNode, frontmatter: &Dictionary) -> Dictionary {
+    let mut data = node.parse_data.clone();
+    let options = data.remove(OPTIONS_KEY).unwrap_or(GodotValue::Nil);
+    let mut context = Dictionary::new();
+    context.set("frontmatter", frontmatter.clone());
+    if let Ok(document) = with_current(|c| Ok(c.document().to_string_lossy().into_owned())) {
+        context.set("document", document);
+    }
+    context.set("parse_data", data_to_dictionary(&data));
+    context.set(
+        "options",
+        Converter::new(&Conversion::default(), "options")
+            .value(options)
+            .unwrap_or_default(),
+    );
+    context
+}
+
+/// The state of a statement from what a parser returned, None to leave it to the others.
+pub(crate) fn state_from_result(returned: &Dictionary) -> Option<DokeNodeState> {
+    let result = if let Some(error) = returned.get("error") {
+        Err(CallableParserError::Rejected(error.to_string()))
+    } else if let Some(type_name) = returned.get("type") {
+        let fields = returned.get("fields").unwrap_or_default();
+        to_godot_value(&fields).and_then(|fields| match fields {
+            GodotValue::Dict(fields) | GodotValue::Resource { fields, .. } => {
+                Ok(GodotValue::Resource {
+                    type_name: type_name.to_string(),
+                    abstract_type_name: type_name.to_string(),
+                    fields,
+                })
+            }
+            GodotValue::Nil => Ok(GodotValue::Resource {
+                type_name: type_name.to_string(),
+                abstract_type_name: type_name.to_string(),
+                fields: HashMap::new(),
+            }),
+            other => Err(CallableParserError::InvalidValue(other.to_string())),
+        })
+    } else {
+        to_godot_value(&returned.get("value")?)
+    };
+    Some(match result {
+        Ok(value) => DokeNodeState::Resolved(Box::new(value)),
+        Err(e) => DokeNodeState::Error(Box::new(e)),
+    })
+}
+
+fn to_godot_value(v: &Variant) -> Result<GodotValue, CallableParserError> {
+    variant_to_yaml(v)
+        .map(yaml_to_godot)
+        .map_err(|e| CallableParserError::InvalidValue(e.to_string()))
+}
+
+fn data_to_dictionary(data: &HashMap<String, GodotValue>) -> Dictionary {
+    frontmatter_to_dictionary(data, &Conversion::default()).unwrap_or_default()
+}
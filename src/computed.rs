@@ -0,0 +1,393 @@
+// computed.rs
+// Fields computed from other fields of the document (`computed:` in the
+// config), with a small arithmetic language : `dps: damage / cooldown`.
+
+use std::collections::HashMap;
+
+use doke::GodotValue;
+use thiserror::Error;
+use yaml_rust2::Yaml;
+
+use crate::config::ConfigError;
+use crate::spreadsheet;
+
+#[derive(Debug, Error)]
+pub enum ComputedError {
+    #[error("Computed field '{0}' : '{1}' isn't set")]
+    MissingField(String, String),
+    #[error("Computed field '{0}' : '{1}' isn't a number ({2})")]
+    NotANumber(String, String, String),
+    #[error("Computed field '{0}' : division by zero")]
+    DivisionByZero(String),
+}
+
+/// `computed:` field -> expression, evaluated in order so later fields can use earlier ones.
+#[derive(Debug, Clone, Default)]
+pub struct ComputedFields {
+    fields: Vec<(String, Expr)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Int(i64),
+    Float(f64),
+    /// A field of the built resource or of the frontmatter, `a.b` for nested values
+    Field(String),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, char, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn as_f64(self) -> f64 {
+        match self {
+            Self::Int(i) => i as f64,
+            Self::Float(f) => f,
+        }
+    }
+
+    fn to_godot(self) -> GodotValue {
+        match self {
+            Self::Int(i) => GodotValue::Int(i),
+            Self::Float(f) => GodotValue::Float(f),
+        }
+    }
+}
+
+const FUNCTIONS: &[(&str, usize)] = &[
+    ("min", 2),
+    ("max", 2),
+    ("abs", 1),
+    ("round", 1),
+    ("floor", 1),
+    ("ceil", 1),
+];
+
+impl ComputedFields {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let mut computed = Self::default();
+        let Some(hash) = y.as_hash() else {
+            return Ok(computed);
+        };
+        for (k, v) in hash {
+            let Some(field) = k.as_str() else {
+                return Err(ConfigError::Invalid(
+                    "'computed' keys must be field names".into(),
+                ));
+            };
+            let source = match v {
+                Yaml::String(s) => s.clone(),
+                Yaml::Integer(i) => i.to_string(),
+                Yaml::Real(r) => r.clone(),
+                _ => {
+                    return Err(ConfigError::Invalid(format!(
+                        "Computed field '{}' must be an expression",
+                        field
+                    )));
+                }
+            };
+            let expr = Parser::new(&source)
+                .parse()
+                .map_err(|e| ConfigError::Invalid(format!("Computed field '{}' : {}", field, e)))?;
+            computed.fields.push((field.to_string(), expr));
+        }
+        Ok(computed)
+    }
+
+    /// Sets the computed fields on the built resource, from its fields and the frontmatter.
+    pub fn apply(
+        &self,
+        value: &mut GodotValue,
+        frontmatter: &HashMap<String, GodotValue>,
+    ) -> Result<(), ComputedError> {
+        for (field, expr) in &self.fields {
+            let result = eval(field, expr, value, frontmatter)?.to_godot();
+            if let GodotValue::Resource { fields, .. } = value {
+                fields.insert(field.clone(), result);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn eval(
+    field: &str,
+    expr: &Expr,
+    value: &GodotValue,
+    fm: &HashMap<String, GodotValue>,
+) -> Result<Number, ComputedError> {
+    let eval = |e: &Expr| eval(field, e, value, fm);
+    Ok(match expr {
+        Expr::Int(i) => Number::Int(*i),
+        Expr::Float(f) => Number::Float(*f),
+        Expr::Field(name) => match spreadsheet::lookup(value, fm, name) {
+            Some(GodotValue::Int(i)) => Number::Int(*i),
+            Some(GodotValue::Float(f)) => Number::Float(*f),
+            None | Some(GodotValue::Nil) => {
+                return Err(ComputedError::MissingField(field.into(), name.clone()));
+            }
+            Some(other) => {
+                return Err(ComputedError::NotANumber(
+                    field.into(),
+                    name.clone(),
+                    other.to_string(),
+                ));
+            }
+        },
+        Expr::Neg(e) => match eval(e)? {
+            Number::Int(i) => Number::Int(i.wrapping_neg()),
+            Number::Float(f) => Number::Float(-f),
+        },
+        Expr::Binary(l, op, r) => {
+            let (l, r) = (eval(l)?, eval(r)?);
+            match (l, r, op) {
+                (_, r, '/' | '%') if r.as_f64() == 0.0 => {
+                    return Err(ComputedError::DivisionByZero(field.into()));
+                }
+                // Dividing always gives a float : 7 / 2 is 3.5
+                (l, r, '/') => Number::Float(l.as_f64() / r.as_f64()),
+                (Number::Int(l), Number::Int(r), op) => Number::Int(match op {
+                    '+' => l.wrapping_add(r),
+                    '-' => l.wrapping_sub(r),
+                    '*' => l.wrapping_mul(r),
+                    _ => l.wrapping_rem(r),
+                }),
+                (l, r, op) => {
+                    let (l, r) = (l.as_f64(), r.as_f64());
+                    Number::Float(match op {
+                        '+' => l + r,
+                        '-' => l - r,
+                        '*' => l * r,
+                        _ => l % r,
+                    })
+                }
+            }
+        }
+        Expr::Call(name, args) => {
+            let args = args.iter().map(eval).collect::<Result<Vec<_>, _>>()?;
+            match (name.as_str(), args.as_slice()) {
+                ("min", [Number::Int(a), Number::Int(b)]) => Number::Int(*a.min(b)),
+                ("max", [Number::Int(a), Number::Int(b)]) => Number::Int(*a.max(b)),
+                ("min", [a, b]) => Number::Float(a.as_f64().min(b.as_f64())),
+                ("max", [a, b]) => Number::Float(a.as_f64().max(b.as_f64())),
+                ("abs", [Number::Int(a)]) => Number::Int(a.wrapping_abs()),
+                ("abs", [a]) => Number::Float(a.as_f64().abs()),
+                (_, [Number::Int(a)]) => Number::Int(*a),
+                ("round", [a]) => Number::Int(a.as_f64().round() as i64),
+                ("floor", [a]) => Number::Int(a.as_f64().floor() as i64),
+                (_, [a]) => Number::Int(a.as_f64().ceil() as i64),
+                _ => unreachable!("arity is checked when parsing"),
+            }
+        }
+    })
+}
+
+// -----------------------
+// Parsing expressions
+// -----------------------
+
+struct Parser<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Self { source, pos: 0 }
+    }
+
+    fn parse(mut self) -> Result<Expr, String> {
+        let expr = self.sum()?;
+        self.skip_spaces();
+        match self.peek() {
+            None => Ok(expr),
+            Some(c) => Err(format!("unexpected '{}' at {}", c, self.pos)),
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = &self.source[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    /// Skips spaces and consumes `c` if it comes next.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_spaces();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        loop {
+            let op = if self.eat('+') {
+                '+'
+            } else if self.eat('-') {
+                '-'
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        loop {
+            let Some(op) = ['*', '/', '%'].into_iter().find(|c| self.eat(*c)) else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        if self.eat('(') {
+            let expr = self.sum()?;
+            if !self.eat(')') {
+                return Err(format!("missing ')' at {}", self.pos));
+            }
+            return Ok(expr);
+        }
+        self.skip_spaces();
+        let start = self.pos;
+        let rest = &self.source[start..];
+        match rest.chars().next() {
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                let text = &rest[..len];
+                text.parse()
+                    .map(Expr::Int)
+                    .or_else(|_| text.parse().map(Expr::Float))
+                    .map_err(|_| format!("invalid number '{}'", text))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                let name = rest[..len].to_string();
+                if !self.eat('(') {
+                    return Ok(Expr::Field(name));
+                }
+                let Some((_, arity)) = FUNCTIONS.iter().find(|(f, _)| *f == name) else {
+                    return Err(format!("unknown function '{}'", name));
+                };
+                let mut args = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        args.push(self.sum()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        if !self.eat(',') {
+                            return Err(format!("expected ',' or ')' at {}", self.pos));
+                        }
+                    }
+                }
+                if args.len() != *arity {
+                    return Err(format!("{} takes {} arguments", name, arity));
+                }
+                Ok(Expr::Call(name, args))
+            }
+            Some(c) => Err(format!("unexpected '{}' at {}", c, start)),
+            None => Err("unexpected end of expression".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compute(source: &str) -> Result<Number, String> {
+        let expr = Parser::new(source).parse()?;
+        let value = GodotValue::Resource {
+            type_name: "Item".into(),
+            abstract_type_name: String::new(),
+            fields: HashMap::from([
+                ("damage".into(), GodotValue::Int(12)),
+                ("cooldown".into(), GodotValue::Float(1.5)),
+            ]),
+        };
+        eval("test", &expr, &value, &HashMap::new()).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(compute("1 + 2 * 3"), Ok(Number::Int(7)));
+        assert_eq!(compute("(1 + 2) * 3"), Ok(Number::Int(9)));
+        assert_eq!(compute("10 - 4 - 3"), Ok(Number::Int(3)));
+        assert_eq!(compute("7 % 4 * 2"), Ok(Number::Int(6)));
+        assert_eq!(compute("7 / 2"), Ok(Number::Float(3.5)));
+        assert_eq!(compute("damage / cooldown"), Ok(Number::Float(8.0)));
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(compute("-3 + 5"), Ok(Number::Int(2)));
+        assert_eq!(compute("2 * -damage"), Ok(Number::Int(-24)));
+        assert_eq!(compute("--4"), Ok(Number::Int(4)));
+        assert_eq!(compute("-cooldown"), Ok(Number::Float(-1.5)));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(compute("min(damage, 5)"), Ok(Number::Int(5)));
+        assert_eq!(compute("max(damage, cooldown)"), Ok(Number::Float(12.0)));
+        assert_eq!(compute("abs(-4)"), Ok(Number::Int(4)));
+        assert_eq!(compute("round(2.5)"), Ok(Number::Int(3)));
+        assert_eq!(compute("floor(-1.5)"), Ok(Number::Int(-2)));
+        assert_eq!(compute("ceil(cooldown)"), Ok(Number::Int(2)));
+    }
+
+    #[test]
+    fn overflows_wrap() {
+        let min = "(0 - 9223372036854775807 - 1)";
+        assert_eq!(compute(&format!("-{}", min)), Ok(Number::Int(i64::MIN)));
+        assert_eq!(compute(&format!("abs{}", min)), Ok(Number::Int(i64::MIN)));
+        assert_eq!(compute(&format!("{} % -1", min)), Ok(Number::Int(0)));
+        assert_eq!(compute(&format!("{} - 1", min)), Ok(Number::Int(i64::MAX)));
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let error = Err("Computed field 'test' : division by zero".to_string());
+        assert_eq!(compute("damage / 0"), error);
+        assert_eq!(compute("damage % (2 - 2)"), error);
+        assert_eq!(compute("damage / 0.0"), error);
+    }
+
+    #[test]
+    fn arity_and_syntax_errors() {
+        assert_eq!(compute("min(1)"), Err("min takes 2 arguments".into()));
+        assert_eq!(compute("abs(1, 2)"), Err("abs takes 1 arguments".into()));
+        assert_eq!(compute("sqrt(4)"), Err("unknown function 'sqrt'".into()));
+        assert_eq!(compute("(1 + 2"), Err("missing ')' at 6".into()));
+        assert_eq!(compute("1 +"), Err("unexpected end of expression".into()));
+    }
+}
@@ -0,0 +1,136 @@
+// conditions.rs
+// Fields only kept when a condition on the document holds (`conditions:` in
+// the config), so one file type can describe several shapes of resource.
+
+use std::collections::HashMap;
+
+use doke::GodotValue;
+use yaml_rust2::Yaml;
+
+use crate::config::ConfigError;
+use crate::frontmatter::normalize_key;
+use crate::spreadsheet;
+
+/// `conditions:` field -> condition, like `type == ranged`.
+#[derive(Debug, Clone, Default)]
+pub struct FieldConditions {
+    fields: Vec<(String, Condition)>,
+}
+
+/// Alternatives (`or`) of comparisons that must all hold (`and`).
+#[derive(Debug, Clone, PartialEq)]
+struct Condition(Vec<Vec<Comparison>>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Comparison {
+    /// `field == value`, matched ignoring case and spaces like enums
+    Equals(String, String),
+    /// `field != value`
+    NotEquals(String, String),
+    /// `field` : the field is set and isn't false, 0 or empty
+    Set(String),
+    /// `not field`
+    NotSet(String),
+}
+
+impl Condition {
+    fn parse(source: &str) -> Result<Self, String> {
+        source
+            .split(" or ")
+            .map(|alternative| {
+                alternative
+                    .split(" and ")
+                    .map(|part| Comparison::parse(part.trim()))
+                    .collect()
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    fn holds(&self, value: &GodotValue, fm: &HashMap<String, GodotValue>) -> bool {
+        self.0
+            .iter()
+            .any(|all| all.iter().all(|c| c.holds(value, fm)))
+    }
+}
+
+impl Comparison {
+    fn parse(part: &str) -> Result<Self, String> {
+        let literal = |s: &str| s.trim().trim_matches(|c| c == '"' || c == '\'').to_string();
+        if let Some((field, v)) = part.split_once("!=") {
+            return Ok(Self::NotEquals(field.trim().into(), literal(v)));
+        }
+        if let Some((field, v)) = part.split_once("==") {
+            return Ok(Self::Equals(field.trim().into(), literal(v)));
+        }
+        let (negated, field) = match part.strip_prefix("not ") {
+            Some(field) => (true, field.trim()),
+            None => (false, part),
+        };
+        if field.is_empty() || field.contains(char::is_whitespace) {
+            return Err(format!("can't read '{}' (expected `field == value`)", part));
+        }
+        Ok(if negated {
+            Self::NotSet(field.into())
+        } else {
+            Self::Set(field.into())
+        })
+    }
+
+    fn holds(&self, value: &GodotValue, fm: &HashMap<String, GodotValue>) -> bool {
+        let cell = |field: &str| spreadsheet::cell(spreadsheet::lookup(value, fm, field));
+        match self {
+            Self::Equals(field, v) => normalize_key(&cell(field)) == normalize_key(v),
+            Self::NotEquals(field, v) => normalize_key(&cell(field)) != normalize_key(v),
+            Self::Set(field) => is_set(spreadsheet::lookup(value, fm, field)),
+            Self::NotSet(field) => !is_set(spreadsheet::lookup(value, fm, field)),
+        }
+    }
+}
+
+fn is_set(value: Option<&GodotValue>) -> bool {
+    match value {
+        None | Some(GodotValue::Nil) | Some(GodotValue::Bool(false)) | Some(GodotValue::Int(0)) => {
+            false
+        }
+        Some(GodotValue::String(s)) => !s.is_empty(),
+        Some(GodotValue::Array(items)) => !items.is_empty(),
+        Some(_) => true,
+    }
+}
+
+impl FieldConditions {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let mut conditions = Self::default();
+        let Some(hash) = y.as_hash() else {
+            return Ok(conditions);
+        };
+        for (k, v) in hash {
+            let (Some(field), Some(source)) = (k.as_str(), v.as_str()) else {
+                return Err(ConfigError::Invalid(
+                    "'conditions' entries must be `field: condition`".into(),
+                ));
+            };
+            let condition = Condition::parse(source)
+                .map_err(|e| ConfigError::Invalid(format!("Condition of '{}' : {}", field, e)))?;
+            conditions.fields.push((field.to_string(), condition));
+        }
+        Ok(conditions)
+    }
+
+    /// Removes the fields whose condition doesn't hold from the built resource.
+    /// Every condition is checked against the document before any field is removed.
+    pub fn apply(&self, value: &mut GodotValue, frontmatter: &HashMap<String, GodotValue>) {
+        let dropped: Vec<&str> = self
+            .fields
+            .iter()
+            .filter(|(_, condition)| !condition.holds(value, frontmatter))
+            .map(|(field, _)| field.as_str())
+            .collect();
+        if let GodotValue::Resource { fields, .. } = value {
+            for field in dropped {
+                fields.remove(field);
+            }
+        }
+    }
+}
@@ -0,0 +1,754 @@
+// config.rs
+// Importer-side settings for a file type. They live in the same yaml file as
+// the `root`/`children`/`rules` read by doke's ResourceBuilder and
+// TypedSentencesParser, which both ignore the keys handled here.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use doke::GodotValue;
+use doke::file_builder::{BuilderError, Config, FieldConfig, FieldType};
+use godot::prelude::*;
+use thiserror::Error;
+use yaml_rust2::{Yaml, YamlEmitter, YamlLoader, yaml::Hash};
+
+use crate::budgets::Budgets;
+use crate::computed::ComputedFields;
+use crate::conditions::FieldConditions;
+use crate::dispatch::Dispatch;
+use crate::fields::FieldTypes;
+use crate::file_error::{ErrorContext, FileError};
+use crate::frontmatter::{self, FrontmatterError, normalize_key, yaml_to_godot};
+use crate::frontmatter_resources::FrontmatterResources;
+use crate::import::{Conversion, FieldApplication, FrontmatterPhase};
+use crate::lint::Lints;
+use crate::localization::Localization;
+use crate::matching::{Matching, Synonyms};
+use crate::messages::Messages;
+use crate::naming::Naming;
+use crate::outputs::Outputs;
+use crate::safe_mode::SafeMode;
+use crate::sections::{HeadingFields, HeadingNumbers, SectionResources};
+use crate::tables::Tables;
+use crate::validation;
+use crate::value_types::ValueTypes;
+use crate::variables::Variables;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Can't read config : {0}")]
+    Io(#[from] FileError),
+    #[error("YAML parse error in config : {0}")]
+    Yaml(String),
+    #[error("Invalid config : {0}")]
+    Invalid(String),
+    #[error("Invalid documents pattern '{0}' : {1}")]
+    Pattern(String, glob::PatternError),
+}
+
+/// `classes:` resource classes a file type may instantiate, built-in or script ones.
+#[derive(Debug, Clone, Default)]
+pub struct ClassFilter {
+    /// `allow:` only these, when set
+    pub allow: Option<Vec<String>>,
+    /// `deny:` never these
+    pub deny: Vec<String>,
+}
+
+impl ClassFilter {
+    fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let names = |key: &str| -> Result<Option<Vec<String>>, ConfigError> {
+            match &y[key] {
+                Yaml::BadValue => Ok(None),
+                Yaml::Array(names) => names
+                    .iter()
+                    .map(|n| n.as_str().map(str::to_string))
+                    .collect::<Option<_>>()
+                    .map(Some)
+                    .ok_or_else(|| {
+                        ConfigError::Invalid(format!("classes.{} must be a list of classes", key))
+                    }),
+                _ => Err(ConfigError::Invalid(format!(
+                    "classes.{} must be a list of classes",
+                    key
+                ))),
+            }
+        };
+        Ok(Self {
+            allow: names("allow")?,
+            deny: names("deny")?.unwrap_or_default(),
+        })
+    }
+
+    pub fn allows(&self, class: &str) -> bool {
+        self.allow
+            .as_ref()
+            .is_none_or(|allow| allow.iter().any(|c| c == class))
+            && !self.deny.iter().any(|c| c == class)
+    }
+}
+
+/// `limits:` bounds on the values one document converts to Variants,
+/// so pathological generated content fails instead of overflowing the stack,
+/// and on the size and markdown nesting of the documents read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// `max_depth:` most levels of nested arrays, dictionaries and resources
+    pub max_depth: usize,
+    /// `max_elements:` most values converted for one document
+    pub max_elements: usize,
+    /// `max_file_size:` most bytes in a document file
+    pub max_file_size: usize,
+    /// `max_markdown_depth:` most levels of nested markdown (lists, blockquotes, emphasis...)
+    pub max_markdown_depth: usize,
+    /// `max_markdown_nodes:` most markdown nodes in a document
+    pub max_markdown_nodes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: 64,
+            max_elements: 100_000,
+            max_file_size: 16 << 20,
+            max_markdown_depth: 48,
+            max_markdown_nodes: 200_000,
+        }
+    }
+}
+
+impl Limits {
+    /// Reads the `max_*` keys over `base`. Returns the invalid key on error.
+    pub fn from_yaml(y: &Yaml, base: Self) -> Result<Self, &'static str> {
+        let mut limits = base;
+        for (key, limit) in [
+            ("max_depth", &mut limits.max_depth),
+            ("max_elements", &mut limits.max_elements),
+            ("max_file_size", &mut limits.max_file_size),
+            ("max_markdown_depth", &mut limits.max_markdown_depth),
+            ("max_markdown_nodes", &mut limits.max_markdown_nodes),
+        ] {
+            match &y[key] {
+                Yaml::BadValue => {}
+                Yaml::Integer(i) if *i > 0 => *limit = *i as usize,
+                _ => return Err(key),
+            }
+        }
+        Ok(limits)
+    }
+
+    /// The lowest of both limits.
+    pub fn min(self, other: Self) -> Self {
+        Self {
+            max_depth: self.max_depth.min(other.max_depth),
+            max_elements: self.max_elements.min(other.max_elements),
+            max_file_size: self.max_file_size.min(other.max_file_size),
+            max_markdown_depth: self.max_markdown_depth.min(other.max_markdown_depth),
+            max_markdown_nodes: self.max_markdown_nodes.min(other.max_markdown_nodes),
+        }
+    }
+}
+
+/// A type a frontmatter value is always converted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coercion {
+    Int,
+    Float,
+    String,
+    Bool,
+}
+
+impl Coercion {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "int" => Some(Self::Int),
+            "float" => Some(Self::Float),
+            "string" => Some(Self::String),
+            "bool" => Some(Self::Bool),
+            _ => None,
+        }
+    }
+
+    /// Converts a scalar, or every element of a list.
+    /// Returns None when the value can't be represented as the target type.
+    pub fn apply(self, value: &Yaml) -> Option<Yaml> {
+        if let Yaml::Array(items) = value {
+            return items
+                .iter()
+                .map(|v| self.apply(v))
+                .collect::<Option<Vec<_>>>()
+                .map(Yaml::Array);
+        }
+        let as_text = match value {
+            Yaml::String(s) | Yaml::Real(s) => s.trim().to_string(),
+            Yaml::Integer(i) => i.to_string(),
+            Yaml::Boolean(b) => b.to_string(),
+            _ => return None,
+        };
+        match self {
+            Self::String => Some(Yaml::String(as_text)),
+            Self::Float => as_text
+                .parse::<f64>()
+                .ok()
+                .map(|f| Yaml::Real(format!("{:?}", f))),
+            Self::Int => match as_text.parse::<i64>() {
+                Ok(i) => Some(Yaml::Integer(i)),
+                Err(_) => as_text
+                    .parse::<f64>()
+                    .ok()
+                    // `as` would saturate 1e30 instead of refusing it
+                    .filter(|f| f.fract() == 0.0 && (i64::MIN as f64..i64::MAX as f64).contains(f))
+                    .map(|f| Yaml::Integer(f as i64)),
+            },
+            Self::Bool => match as_text.to_lowercase().as_str() {
+                "true" | "yes" | "1" => Some(Yaml::Boolean(true)),
+                "false" | "no" | "0" => Some(Yaml::Boolean(false)),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Importer settings for one file type.
+#[derive(Debug, Clone, Default)]
+pub struct ImportConfig {
+    /// `coerce:` normalized frontmatter key -> type it is always converted to
+    pub coerce: HashMap<String, Coercion>,
+    /// `defaults:` frontmatter values used when a document doesn't set the key
+    pub defaults: Vec<(String, Yaml)>,
+    /// `inherit_body:` whether `extends:` also pulls in the extended documents' statements
+    pub inherit_body: bool,
+    /// `documents:` glob of the documents of this type, relative to the config
+    pub documents: Option<String>,
+    /// `variables:` file of `{{variables}}` for this type, over the project ones
+    pub variables: Variables,
+    /// `fields:` Godot types (Vector2, Color...) of resource fields and frontmatter keys
+    pub fields: FieldTypes,
+    /// `dispatch:` builders picked by a frontmatter key instead of `root`/`children`
+    pub dispatch: Option<Dispatch>,
+    /// `headings:` fields set on the values under some headings
+    pub headings: HeadingFields,
+    /// `heading_numbers:` fields set to the index and number of the heading above statements
+    pub heading_numbers: HeadingNumbers,
+    /// `sections:` headings whose statements build a sub-resource
+    pub sections: SectionResources,
+    /// `rename:` and `case:` property names of the keys documents are written with
+    pub naming: Naming,
+    /// `frontmatter_resources:` frontmatter lists of maps built as sub-resources
+    pub frontmatter_resources: FrontmatterResources,
+    /// `field_defaults:` values of the built resource's fields when the statements don't set them
+    pub field_defaults: Vec<(String, GodotValue)>,
+    /// `computed:` fields computed from the others once they are set
+    pub computed: ComputedFields,
+    /// `conditions:` fields only kept when a condition on the document holds
+    pub conditions: FieldConditions,
+    /// `required:` fields every document must set
+    pub required: Vec<String>,
+    /// `lint:` style rules checked on each import
+    pub lint: Lints,
+    /// `messages:` project wording of diagnostics, by code and locale
+    pub messages: Messages,
+    /// `frontmatter_keys:` the frontmatter keys documents may set, the others are warned about
+    pub frontmatter_keys: Option<Vec<String>>,
+    /// `outputs:` other resources built from the same documents
+    pub outputs: Outputs,
+    /// `classes:` resource classes documents may instantiate
+    pub classes: ClassFilter,
+    /// `limits:` depth and size bounds of the converted values
+    pub limits: Limits,
+    /// `string_names:` fields converted to StringNames for fast lookups at runtime
+    pub string_names: Vec<String>,
+    /// `plugins:` registered plugin parsers added to the pipe after the sentence parser, with their options
+    pub plugins: Vec<Stage>,
+    /// `pipeline:` the stages of the pipe in order, replacing the default ones
+    pub pipeline: Option<Vec<Stage>>,
+    /// `dedup:` whether identical nested resources share one instance
+    pub dedup: bool,
+    /// `scripts:` script path or class_name to instantiate for a type name
+    pub scripts: HashMap<String, String>,
+    /// `frontmatter_phase:` whether `_apply_doke_frontmatter` runs before or after the fields are set
+    pub frontmatter_phase: FrontmatterPhase,
+    /// `apply_fields:` set fields directly, through `set_<field>()` setters or `_apply_doke_fields()`
+    pub apply_fields: FieldApplication,
+    /// `safe_mode:` limits for imports of untrusted documents
+    pub safe_mode: Option<SafeMode>,
+    /// `budgets:` content limits checked by `check_budgets`
+    pub budgets: Budgets,
+    /// `rules:` (target type, dokedef glob) pairs, as read by doke's TypedSentencesParser
+    pub rules: Vec<(String, String)>,
+    /// `bbcode_field:` field of the built resource set to the BBCode of the document's wiki part
+    pub bbcode_field: Option<String>,
+    /// `html_links:` URL of the pages wiki links point to in HTML exports, `{name}` being the link key
+    pub html_links: Option<String>,
+    /// `tables:` fields set to the rows of a markdown table or CSV file
+    pub tables: Tables,
+    /// `localization:` the strings `extract_translations` collects
+    pub localization: Localization,
+    /// `value_types:` resource classes the values of value type parameters (`dice`) are built as
+    pub value_types: ValueTypes,
+    /// `matching:` how loosely statements may be written to match the sentences
+    pub matching: Matching,
+    /// `synonyms:` other wordings of sentences, rewritten to them before matching
+    pub synonyms: Synonyms,
+    /// `children:` the fields of the root the statements fill, in the order documents are written
+    pub children: Vec<FieldConfig>,
+    /// Directory of the config file, that relative paths start from
+    pub base_dir: PathBuf,
+}
+
+/// Reads the first YAML document of a config file.
+pub fn load_yaml(path: &Path) -> Result<Yaml, ConfigError> {
+    let s = std::fs::read_to_string(path).in_file(path)?;
+    let docs = YamlLoader::load_from_str(&s).map_err(|e| ConfigError::Yaml(e.to_string()))?;
+    docs.into_iter()
+        .next()
+        .ok_or_else(|| ConfigError::Yaml("Empty YAML file".into()))
+}
+
+// -----------------------
+// Pipe stages
+// -----------------------
+
+/// A stage of a file type's DokePipe : a built-in or registered parser, and its options.
+pub type Stage = (String, GodotValue);
+
+/// Fills `{{frontmatter}}` templates in the statements.
+pub const FRONTMATTER_TEMPLATES_STAGE: &str = "frontmatter_templates";
+/// Rewrites the statements to match the sentences as written, see `matching:` and `synonyms:`.
+pub const MATCHING_STAGE: &str = "tolerant_matching";
+/// The sentence patterns of the config.
+pub const SENTENCES_STAGE: &str = "sentences";
+/// Reads the parameters of value types (`dice`...) the sentences matched.
+pub const VALUE_TYPES_STAGE: &str = "value_types";
+/// Prints the statements to the output.
+pub const DEBUG_STAGE: &str = "debug";
+
+pub fn is_builtin_stage(name: &str) -> bool {
+    [
+        FRONTMATTER_TEMPLATES_STAGE,
+        MATCHING_STAGE,
+        SENTENCES_STAGE,
+        VALUE_TYPES_STAGE,
+        DEBUG_STAGE,
+    ]
+    .contains(&name)
+}
+
+/// Stages written as names, or `name: options`.
+fn parse_stages(y: &Yaml, key: &str) -> Result<Vec<Stage>, ConfigError> {
+    let invalid = || {
+        ConfigError::Invalid(format!(
+            "'{}' entries must be parser names, or `name: options`",
+            key
+        ))
+    };
+    let Some(stages) = y.as_vec() else {
+        return match y {
+            Yaml::BadValue | Yaml::Null => Ok(Vec::new()),
+            _ => Err(invalid()),
+        };
+    };
+    stages
+        .iter()
+        .map(|stage| match stage {
+            Yaml::String(name) => Ok((name.clone(), GodotValue::Nil)),
+            Yaml::Hash(h) if h.len() == 1 => {
+                let (name, options) = h.iter().next().ok_or_else(invalid)?;
+                let name = name.as_str().ok_or_else(invalid)?;
+                Ok((name.to_string(), yaml_to_godot(options.clone())))
+            }
+            _ => Err(invalid()),
+        })
+        .collect()
+}
+
+// -----------------------
+// Configs authored as Godot Dictionaries
+// -----------------------
+
+/// Converts a Variant (Dictionary, Array, scalars...) to the yaml it would be written as.
+pub fn variant_to_yaml(v: &Variant) -> Result<Yaml, ConfigError> {
+    Ok(match v.get_type() {
+        VariantType::NIL => Yaml::Null,
+        VariantType::BOOL => Yaml::Boolean(v.to::<bool>()),
+        VariantType::INT => Yaml::Integer(v.to::<i64>()),
+        VariantType::FLOAT => Yaml::Real(format!("{:?}", v.to::<f64>())),
+        VariantType::STRING | VariantType::STRING_NAME => Yaml::String(v.to_string()),
+        VariantType::ARRAY => Yaml::Array(
+            v.to::<VariantArray>()
+                .iter_shared()
+                .map(|item| variant_to_yaml(&item))
+                .collect::<Result<_, _>>()?,
+        ),
+        VariantType::PACKED_STRING_ARRAY => Yaml::Array(
+            v.to::<PackedStringArray>()
+                .as_slice()
+                .iter()
+                .map(|s| Yaml::String(s.to_string()))
+                .collect(),
+        ),
+        VariantType::DICTIONARY => {
+            let mut hash = Hash::new();
+            for (k, item) in v.to::<Dictionary>().iter_shared() {
+                hash.insert(Yaml::String(k.to_string()), variant_to_yaml(&item)?);
+            }
+            Yaml::Hash(hash)
+        }
+        other => {
+            return Err(ConfigError::Invalid(format!(
+                "Can't use a {:?} in a config : {}",
+                other, v
+            )));
+        }
+    })
+}
+
+/// Writes yaml back to text, for the doke loaders that only read text.
+pub fn emit_yaml(y: &Yaml) -> String {
+    let mut out = String::new();
+    // Writing to a String can't fail
+    let _ = YamlEmitter::new(&mut out).dump(y);
+    out
+}
+
+/// Reads `root` and `children` like doke's ResourceBuilder::from_file does.
+pub fn builder_config(y: &Yaml) -> Result<Config, BuilderError> {
+    let root = y["root"]
+        .as_str()
+        .ok_or_else(|| BuilderError::Config("Missing 'root' string key".into()))?
+        .to_string();
+    let entries = y["children"].as_vec().ok_or_else(|| {
+        BuilderError::Config("Missing or invalid 'children' (must be a sequence)".into())
+    })?;
+
+    let mut children = Vec::new();
+    for entry in entries {
+        let Some((raw_name, value)) = entry
+            .as_hash()
+            .filter(|h| h.len() == 1)
+            .and_then(|h| h.front())
+        else {
+            return Err(BuilderError::Config(format!(
+                "Each child must be a map with exactly one key, got {:?}",
+                entry
+            )));
+        };
+        let raw_name = raw_name
+            .as_str()
+            .ok_or_else(|| BuilderError::Config("Child field name must be string".into()))?;
+        let (name, optional) = match raw_name.strip_suffix('?') {
+            Some(name) => (name.to_string(), true),
+            None => (raw_name.to_string(), false),
+        };
+        let ty = match value {
+            Yaml::String(s) => FieldType::Single(s.clone()),
+            Yaml::Array(items) => match &items[..] {
+                [Yaml::String(s)] => FieldType::Array(s.clone()),
+                _ => {
+                    return Err(BuilderError::Config(format!(
+                        "Array field {} must have exactly one type, got {:?}",
+                        name, items
+                    )));
+                }
+            },
+            _ => {
+                return Err(BuilderError::Config(format!(
+                    "Invalid type spec for field {}",
+                    name
+                )));
+            }
+        };
+        children.push(FieldConfig { name, ty, optional });
+    }
+    Ok(Config { root, children })
+}
+
+impl ImportConfig {
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let base_dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        Self::from_yaml(&load_yaml(path)?, &base_dir)
+    }
+
+    pub fn from_yaml(y: &Yaml, base_dir: &Path) -> Result<Self, ConfigError> {
+        let mut config = Self {
+            base_dir: base_dir.to_path_buf(),
+            ..Default::default()
+        };
+        if let Some(coerce) = y["coerce"].as_hash() {
+            for (k, v) in coerce {
+                let (Some(key), Some(ty)) = (k.as_str(), v.as_str()) else {
+                    return Err(ConfigError::Invalid(
+                        "'coerce' entries must be `key: type`".into(),
+                    ));
+                };
+                let coercion = Coercion::from_name(ty).ok_or_else(|| {
+                    ConfigError::Invalid(format!(
+                        "Unknown coercion type '{}' for '{}' (expected int, float, String or bool)",
+                        ty, key
+                    ))
+                })?;
+                config.coerce.insert(normalize_key(key), coercion);
+            }
+        }
+        if let Some(defaults) = y["defaults"].as_hash() {
+            for (k, v) in defaults {
+                let key = k.as_str().ok_or_else(|| {
+                    ConfigError::Invalid("'defaults' keys must be strings".into())
+                })?;
+                config.defaults.push((key.to_string(), v.clone()));
+            }
+        }
+        config.fields = FieldTypes::from_yaml(&y["fields"])?;
+        config.budgets = Budgets::from_yaml(&y["budgets"])?;
+        config.headings = HeadingFields::from_yaml(&y["headings"])?;
+        config.heading_numbers = HeadingNumbers::from_yaml(&y["heading_numbers"])?;
+        config.dispatch = Dispatch::from_yaml(&y["dispatch"], base_dir)?;
+        config.sections = SectionResources::from_yaml(&y["sections"])?;
+        config.outputs = Outputs::from_yaml(&y["outputs"])?;
+        config.required = validation::required_from_yaml(&y["required"])?;
+        config.lint = Lints::from_yaml(&y["lint"])?;
+        config.messages = Messages::from_yaml(&y["messages"])?;
+        if !y["frontmatter_keys"].is_badvalue() {
+            let keys = validation::names_from_yaml(&y["frontmatter_keys"], "frontmatter_keys")?;
+            config.frontmatter_keys = Some(keys);
+        }
+        config.naming = Naming::from_yaml(&y["rename"], &y["case"])?;
+        config.safe_mode = SafeMode::from_yaml(&y["safe_mode"])?;
+        config.classes = ClassFilter::from_yaml(&y["classes"])?;
+        if let Some(scripts) = y["scripts"].as_hash() {
+            for (k, v) in scripts {
+                let (Some(type_name), Some(script)) = (k.as_str(), v.as_str()) else {
+                    return Err(ConfigError::Invalid(
+                        "'scripts' entries must be `Type: res://path/to/script.gd` or `Type: ClassName`"
+                            .into(),
+                    ));
+                };
+                config
+                    .scripts
+                    .insert(type_name.to_string(), script.to_string());
+            }
+            if config.safe_mode.is_some() && !config.scripts.is_empty() {
+                return Err(ConfigError::Invalid(
+                    "'scripts' can't be used in safe mode".into(),
+                ));
+            }
+        }
+        if let Some(phase) = y["frontmatter_phase"].as_str() {
+            config.frontmatter_phase = FrontmatterPhase::from_name(phase).ok_or_else(|| {
+                ConfigError::Invalid(format!(
+                    "Unknown frontmatter_phase '{}' (expected before, after or both)",
+                    phase
+                ))
+            })?;
+        }
+        if let Some(mode) = y["apply_fields"].as_str() {
+            config.apply_fields = FieldApplication::from_name(mode).ok_or_else(|| {
+                ConfigError::Invalid(format!(
+                    "Unknown apply_fields '{}' (expected set, setters or method)",
+                    mode
+                ))
+            })?;
+        }
+        if let Some(fields) = y["string_names"].as_vec() {
+            config.string_names = fields
+                .iter()
+                .map(|f| f.as_str().map(str::to_string))
+                .collect::<Option<_>>()
+                .ok_or_else(|| {
+                    ConfigError::Invalid("'string_names' must be a list of fields".into())
+                })?;
+        }
+        config.plugins = parse_stages(&y["plugins"], "plugins")?;
+        if !y["pipeline"].is_badvalue() {
+            if !config.plugins.is_empty() {
+                return Err(ConfigError::Invalid(
+                    "with a 'pipeline', list the plugins in it instead of in 'plugins'".into(),
+                ));
+            }
+            config.pipeline = Some(parse_stages(&y["pipeline"], "pipeline")?);
+        }
+        config.limits = Limits::from_yaml(&y["limits"], Limits::default())
+            .map_err(|key| ConfigError::Invalid(format!("limits.{} is invalid", key)))?;
+        config.computed = ComputedFields::from_yaml(&y["computed"])?;
+        config.frontmatter_resources =
+            FrontmatterResources::from_yaml(&y["frontmatter_resources"])?;
+        config.tables = Tables::from_yaml(&y["tables"])?;
+        config.localization = Localization::from_yaml(&y["localization"])?;
+        config.value_types = ValueTypes::from_yaml(&y["value_types"])?;
+        config.matching = Matching::from_yaml(&y["matching"])?;
+        config.synonyms = Synonyms::from_yaml(&y["synonyms"], config.matching.ignore_case())?;
+        config.conditions = FieldConditions::from_yaml(&y["conditions"])?;
+        if let Some(defaults) = y["field_defaults"].as_hash() {
+            for (k, v) in defaults {
+                let key = k.as_str().ok_or_else(|| {
+                    ConfigError::Invalid("'field_defaults' keys must be strings".into())
+                })?;
+                config
+                    .field_defaults
+                    .push((key.to_string(), yaml_to_godot(v.clone())));
+            }
+        }
+        config.inherit_body = y["inherit_body"].as_bool().unwrap_or(false);
+        config.dedup = y["dedup"].as_bool().unwrap_or(false);
+        config.documents = y["documents"].as_str().map(str::to_string);
+        config.bbcode_field = y["bbcode_field"].as_str().map(str::to_string);
+        config.html_links = y["html_links"].as_str().map(str::to_string);
+        // The builder reports its own errors
+        config.children = builder_config(y).map(|c| c.children).unwrap_or_default();
+        if let Some(rules) = y["rules"].as_vec() {
+            config.rules = rules
+                .iter()
+                .filter_map(|r| Some((r["for"].as_str()?.into(), r["parser"].as_str()?.into())))
+                .collect();
+        }
+        if let Some(path) = y["variables"].as_str() {
+            config.variables = Variables::from_file(&base_dir.join(path))?;
+        }
+        Ok(config)
+    }
+
+    /// Paths of every document of this type, from the `documents:` glob.
+    pub fn document_paths(&self) -> Result<Vec<PathBuf>, ConfigError> {
+        let Some(pattern) = &self.documents else {
+            return Err(ConfigError::Invalid(
+                "No 'documents' pattern to list the documents of this type".into(),
+            ));
+        };
+        let full = self.base_dir.join(pattern).to_string_lossy().into_owned();
+        let paths = glob::glob(&full).map_err(|e| ConfigError::Pattern(full.clone(), e))?;
+        let mut paths: Vec<PathBuf> = paths
+            .flatten()
+            .filter(|p| p.extension().is_some_and(|e| e == "md"))
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Whether documents need their frontmatter rewritten before parsing.
+    pub fn has_frontmatter_rules(&self) -> bool {
+        !self.coerce.is_empty() || !self.defaults.is_empty()
+    }
+
+    /// The `limits`, lowered by the `safe_mode` ones.
+    pub fn limits(&self) -> Limits {
+        match &self.safe_mode {
+            Some(safe) => self.limits.min(safe.limits),
+            None => self.limits,
+        }
+    }
+
+    /// How this type's values become Godot objects. The importer adds its resource cache.
+    pub fn conversion(&self) -> Conversion {
+        Conversion {
+            fields: self.fields.clone(),
+            classes: self.classes.clone(),
+            limits: self.limits(),
+            string_names: self.string_names.clone(),
+            scripts: self.scripts.clone(),
+            frontmatter_phase: self.frontmatter_phase,
+            apply_fields: self.apply_fields,
+            resource_cache: None,
+            cache_scope: String::new(),
+            spans: Default::default(),
+            safe_mode: self.safe_mode.clone(),
+        }
+    }
+
+    /// The stages of the pipe : the `pipeline` if set, otherwise the frontmatter templates,
+    /// the tolerant matching with `matching` or `synonyms`, the sentences, the value types,
+    /// the `plugins` and the debug printer.
+    pub fn stages(&self) -> Vec<Stage> {
+        if let Some(pipeline) = &self.pipeline {
+            return pipeline.clone();
+        }
+        let stage = |name: &str| (name.to_string(), GodotValue::Nil);
+        let mut stages = vec![stage(FRONTMATTER_TEMPLATES_STAGE)];
+        if !self.matching.is_empty() || !self.synonyms.is_empty() {
+            stages.push(stage(MATCHING_STAGE));
+        }
+        stages.push(stage(SENTENCES_STAGE));
+        stages.push(stage(VALUE_TYPES_STAGE));
+        stages.extend(self.plugins.iter().cloned());
+        stages.push(stage(DEBUG_STAGE));
+        stages
+    }
+
+    /// Sets the `field_defaults` the built resource doesn't have.
+    pub fn apply_field_defaults(&self, value: &mut GodotValue) {
+        if let GodotValue::Resource { fields, .. } = value {
+            for (field, default) in &self.field_defaults {
+                if matches!(fields.get(field), None | Some(GodotValue::Nil)) {
+                    fields.insert(field.clone(), default.clone());
+                }
+            }
+        }
+    }
+
+    /// The keys of a document's frontmatter that aren't in `frontmatter_keys`, sorted.
+    /// Keys with `coerce:` or `defaults:` rules and `extends` are known too.
+    pub fn unknown_frontmatter_keys(
+        &self,
+        frontmatter: &HashMap<String, GodotValue>,
+    ) -> Vec<String> {
+        let Some(known) = &self.frontmatter_keys else {
+            return Vec::new();
+        };
+        let is_known = |key: &str| {
+            key == frontmatter::EXTENDS_KEY
+                || self.coerce.contains_key(key)
+                || known.iter().any(|k| normalize_key(k) == key)
+                || self.defaults.iter().any(|(k, _)| normalize_key(k) == key)
+        };
+        let mut unknown: Vec<String> = frontmatter
+            .keys()
+            .filter(|k| !is_known(k))
+            .cloned()
+            .collect();
+        unknown.sort();
+        unknown
+    }
+
+    /// Applies the frontmatter rules (defaults, then coercion) to a parsed frontmatter.
+    pub fn apply_frontmatter_rules(&self, fm: &mut Hash) -> Result<(), FrontmatterError> {
+        for (key, value) in &self.defaults {
+            let present = fm
+                .keys()
+                .filter_map(Yaml::as_str)
+                .any(|k| normalize_key(k) == normalize_key(key));
+            if !present {
+                fm.insert(Yaml::String(key.clone()), value.clone());
+            }
+        }
+        for (k, v) in fm.iter_mut() {
+            let Some(key) = k.as_str() else { continue };
+            if let Some(coercion) = self.coerce.get(&normalize_key(key)) {
+                *v = coercion
+                    .apply(v)
+                    .ok_or_else(|| FrontmatterError::Coercion(key.to_string(), *coercion))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn int_coercion_refuses_what_an_i64_cant_hold() {
+        let int = |text: &str| Coercion::Int.apply(&Yaml::Real(text.into()));
+        assert_eq!(int("12.0"), Some(Yaml::Integer(12)));
+        assert_eq!(int("-9.0e3"), Some(Yaml::Integer(-9000)));
+        for text in [
+            "2.5",
+            "1e30",
+            "-1e30",
+            "9223372036854775808.0",
+            "NaN",
+            "inf",
+        ] {
+            assert_eq!(int(text), None, "{text}");
+        }
+    }
+}
@@ -0,0 +1,36 @@
+// converters.rs
+// Field types defined in Rust, for the project's own types (`DamageFormula`...)
+// that `fields:` can't describe. Register them before loading the configs
+// that name them : `fields: {damage: {converter: damage_formula}}`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use doke::GodotValue;
+use godot::prelude::*;
+
+/// Converts the parsed value of a field to the Variant set on the resource.
+pub trait FieldConverter: Send + Sync {
+    /// The converted value, or a message saying why the value can't be converted.
+    fn convert(&self, value: &GodotValue) -> Result<Variant, String>;
+}
+
+static CONVERTERS: LazyLock<RwLock<HashMap<String, Arc<dyn FieldConverter>>>> =
+    LazyLock::new(Default::default);
+
+/// Makes a converter available to configs under `name`, replacing any converter of that name.
+pub fn register_field_converter(name: &str, converter: impl FieldConverter + 'static) {
+    CONVERTERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.to_string(), Arc::new(converter));
+}
+
+/// The converter registered under `name`.
+pub(crate) fn field_converter(name: &str) -> Option<Arc<dyn FieldConverter>> {
+    CONVERTERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .cloned()
+}
@@ -0,0 +1,110 @@
+// coverage.rs
+// The markdown constructs a document's statements leave out. doke only makes statements
+// of paragraphs, headings, code blocks and lists and skips everything else without a word,
+// so authors are told what is dropped instead.
+
+use markdown::ParseOptions;
+use markdown::mdast::Node;
+
+/// A block of the body that no statement comes from.
+#[derive(Debug, Clone)]
+pub struct Dropped {
+    pub kind: &'static str,
+    /// The first line of the block, to find it in the document
+    pub excerpt: String,
+}
+
+/// What the statements do with a block, for every mdast node.
+enum Coverage {
+    /// Becomes a statement, its inline content being the statement's text
+    Statement,
+    /// Holds statements (lists and their items)
+    Container,
+    Dropped(&'static str),
+}
+
+fn coverage(node: &Node) -> Coverage {
+    use Coverage::*;
+    match node {
+        Node::Paragraph(_) | Node::Heading(_) | Node::Code(_) => Statement,
+        Node::Root(_) | Node::List(_) | Node::ListItem(_) => Container,
+        // The frontmatter is split off before, these only show up as leftovers
+        Node::Yaml(_) => Dropped("yaml"),
+        Node::Toml(_) => Dropped("toml"),
+        Node::Blockquote(_) => Dropped("blockquote"),
+        Node::ThematicBreak(_) => Dropped("thematic break"),
+        Node::Html(_) => Dropped("html"),
+        Node::Definition(_) => Dropped("link definition"),
+        Node::FootnoteDefinition(_) => Dropped("footnote"),
+        Node::Table(_) | Node::TableRow(_) | Node::TableCell(_) => Dropped("table"),
+        Node::Math(_) => Dropped("math block"),
+        Node::MdxJsxFlowElement(_) | Node::MdxjsEsm(_) | Node::MdxFlowExpression(_) => {
+            Dropped("mdx")
+        }
+        // Inline content is part of its statement's text, it is only dropped on its own
+        Node::Break(_) => Dropped("line break"),
+        Node::InlineCode(_) => Dropped("inline code"),
+        Node::InlineMath(_) => Dropped("inline math"),
+        Node::Delete(_) | Node::Emphasis(_) | Node::Strong(_) | Node::Text(_) => Dropped("text"),
+        Node::Image(_) => Dropped("image"),
+        Node::ImageReference(_) => Dropped("image reference"),
+        Node::Link(_) => Dropped("link"),
+        Node::LinkReference(_) => Dropped("link reference"),
+        Node::FootnoteReference(_) => Dropped("footnote reference"),
+        Node::MdxTextExpression(_) | Node::MdxJsxTextElement(_) => Dropped("mdx"),
+    }
+}
+
+/// The blocks of a document body (without its frontmatter) that no statement comes from,
+/// `root` being its mdast (see `parse`).
+pub fn dropped_constructs(root: &Node, body: &str) -> Vec<Dropped> {
+    let mut dropped = Vec::new();
+    collect(root, body, &mut dropped);
+    dropped
+}
+
+/// The mdast of a document body, parsed the way `DokePipe::run_markdown` does.
+/// Parsed once per import and borrowed by the checks walking it.
+pub fn parse(body: &str) -> Option<Node> {
+    markdown::to_mdast(body, &ParseOptions::default()).ok()
+}
+
+/// The text of the first paragraph of a markdown text, without its formatting.
+pub fn first_paragraph(markdown: &str) -> Option<String> {
+    let root = parse(markdown)?;
+    root.children()?.iter().find_map(|node| match node {
+        Node::Paragraph(_) => Some(node.to_string()),
+        _ => None,
+    })
+}
+
+fn collect(node: &Node, body: &str, dropped: &mut Vec<Dropped>) {
+    match coverage(node) {
+        Coverage::Statement => {}
+        Coverage::Container => {
+            for child in node.children().into_iter().flatten() {
+                collect(child, body, dropped);
+            }
+        }
+        Coverage::Dropped(kind) => {
+            let excerpt = node
+                .position()
+                .and_then(|p| body.get(p.start.offset..p.end.offset))
+                .and_then(|text| text.lines().next())
+                .unwrap_or_default();
+            dropped.push(Dropped {
+                kind,
+                excerpt: excerpt.trim().chars().take(60).collect(),
+            });
+        }
+    }
+}
+
+/// One line listing what a document drops, for a warning.
+pub fn describe(dropped: &[Dropped]) -> String {
+    let blocks: Vec<String> = dropped
+        .iter()
+        .map(|d| format!("{} `{}`", d.kind, d.excerpt))
+        .collect();
+    format!("ignored markdown : {}", blocks.join(", "))
+}
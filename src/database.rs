@@ -0,0 +1,171 @@
+// database.rs
+// The runtime access point to doke content : documents looked up by id or type
+// from a saved DokeIndex or folders of resources, loaded once. Add DokeDatabase as an autoload.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use godot::classes::{DirAccess, ResourceLoader};
+use godot::global::{push_error, push_warning};
+use godot::prelude::*;
+
+use crate::doke_index::DokeIndex;
+use crate::query::Filter;
+
+#[derive(GodotClass)]
+#[class(init, base=Node)]
+/// Documents of a game, as `{id, path, type, ...}` entries loaded from DokeIndex resources
+/// or folders, and the resources already loaded from them.
+pub struct DokeDatabase {
+    entries: Vec<Dictionary>,
+    /// The loaded indexes, for their words
+    indexes: Vec<Gd<DokeIndex>>,
+    /// path -> loaded resource
+    cache: HashMap<String, Gd<Resource>>,
+    base: Base<Node>,
+}
+
+impl DokeDatabase {
+    fn load_entry(&mut self, entry: &Dictionary) -> Option<Gd<Resource>> {
+        let path = DokeIndex::field(entry, "path");
+        if let Some(res) = self.cache.get(&path) {
+            return Some(res.clone());
+        }
+        let Some(res) = ResourceLoader::singleton().load(&path) else {
+            push_error(&[Variant::from(format!("DokeDatabase : can't load {}", path))]);
+            return None;
+        };
+        self.cache.insert(path, res.clone());
+        Some(res)
+    }
+
+    fn load_all(&mut self, keep: impl Fn(&Dictionary) -> bool) -> Array<Gd<Resource>> {
+        let entries: Vec<_> = self.entries.iter().filter(|e| keep(e)).cloned().collect();
+        entries
+            .iter()
+            .filter_map(|entry| self.load_entry(entry))
+            .collect()
+    }
+}
+
+#[godot_api]
+impl DokeDatabase {
+    #[func]
+    /// Adds the documents of a DokeIndex saved by `DokeImporter.save_doke_index`.
+    /// Returns the number of documents added, or -1 if it can't be loaded.
+    fn load_index(&mut self, path: String) -> i64 {
+        let index = ResourceLoader::singleton()
+            .load(&path)
+            .and_then(|res| res.try_cast::<DokeIndex>().ok());
+        let Some(index) = index else {
+            push_error(&[Variant::from(format!(
+                "DokeDatabase : {} isn't a DokeIndex",
+                path
+            ))]);
+            return -1;
+        };
+        let documents = index.bind().entries();
+        self.entries.extend(documents.iter_shared());
+        self.indexes.push(index);
+        documents.len() as i64
+    }
+
+    #[func]
+    /// Adds the saved resources (`.tres`, `.res`) of a folder as documents of `type_name`,
+    /// their id being their file name. Returns the number of documents added.
+    fn load_directory(&mut self, dir: String, type_name: String) -> i64 {
+        let mut added = 0;
+        for file in DirAccess::get_files_at(&dir).as_slice() {
+            // Exported games list the remapped files
+            let file = file.to_string();
+            let file = file.strip_suffix(".remap").unwrap_or(&file);
+            let Some(id) = file
+                .strip_suffix(".tres")
+                .or_else(|| file.strip_suffix(".res"))
+            else {
+                continue;
+            };
+            let mut entry = Dictionary::new();
+            entry.set("id", id);
+            entry.set("path", format!("{}/{}", dir.trim_end_matches('/'), file));
+            entry.set("type", type_name.as_str());
+            self.entries.push(entry);
+            added += 1;
+        }
+        added
+    }
+
+    #[func]
+    /// The document with that id, loaded on first use. Null when there is none.
+    fn get_by_id(&mut self, id: String) -> Option<Gd<Resource>> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|e| DokeIndex::field(e, "id") == id)
+            .cloned();
+        match entry {
+            Some(entry) => self.load_entry(&entry),
+            None => {
+                push_warning(&[Variant::from(format!(
+                    "DokeDatabase : no document '{}'",
+                    id
+                ))]);
+                None
+            }
+        }
+    }
+
+    #[func]
+    fn get_all_of_type(&mut self, type_name: String) -> Array<Gd<Resource>> {
+        self.load_all(|e| DokeIndex::field(e, "type") == type_name)
+    }
+
+    #[func]
+    /// The documents matching `filter`, see `DokeIndex.query`.
+    fn query(&mut self, filter: Dictionary) -> Array<Gd<Resource>> {
+        match Filter::from_dictionary(&filter) {
+            Ok(filter) => self.load_all(|e| filter.matches(e)),
+            Err(e) => {
+                push_error(&[Variant::from(e)]);
+                Array::new()
+            }
+        }
+    }
+
+    #[func]
+    /// The documents of the loaded indexes containing every word of `query`, best matches
+    /// first, of file type `type_filter` or any when it's empty. See `DokeIndex.search`.
+    fn search(&mut self, query: String, type_filter: String) -> Array<Gd<Resource>> {
+        let mut found: Vec<(i64, Dictionary)> = self
+            .indexes
+            .iter()
+            .flat_map(|index| index.bind().search_entries(&query, &type_filter))
+            .collect();
+        found.sort_by_key(|(score, _)| Reverse(*score));
+        found
+            .iter()
+            .filter_map(|(_, entry)| self.load_entry(entry))
+            .collect()
+    }
+
+    #[func]
+    /// The ids of the documents of `type_name`, all of them when it's empty.
+    fn get_ids(&self, type_name: String) -> PackedStringArray {
+        self.entries
+            .iter()
+            .filter(|e| type_name.is_empty() || DokeIndex::field(e, "type") == type_name)
+            .map(|e| GString::from(DokeIndex::field(e, "id")))
+            .collect()
+    }
+
+    #[func]
+    fn has_id(&self, id: String) -> bool {
+        self.entries.iter().any(|e| DokeIndex::field(e, "id") == id)
+    }
+
+    #[func]
+    /// Forgets the loaded resources, so the next lookups load them again.
+    fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+}
@@ -0,0 +1,406 @@
+// diagnostic.rs
+// Import problems as objects rather than strings, so editor plugins can list
+// them, jump to their place in the document and filter them by parser.
+
+use godot::classes::Json;
+use godot::prelude::*;
+
+use crate::frontmatter::FrontmatterError;
+use crate::import::ImportError;
+use crate::parser_context::ContextError;
+use crate::span::SourceSpan;
+use crate::validation::ValidationError;
+
+// -----------------------
+// Codes : stable across versions, new problems get new codes
+// -----------------------
+
+pub const DROPPED_MARKDOWN: &str = "DOKE100";
+pub const EMPTY_HEADING: &str = "DOKE101";
+pub const UNKNOWN_FRONTMATTER_KEYS: &str = "DOKE102";
+
+/// The code of an import error. Errors at a path in the resource have the code of the cause.
+pub fn error_code(error: &ImportError) -> &'static str {
+    match error {
+        ImportError::InvalidExtension(_) => "DOKE001",
+        ImportError::CantReadFile(_) => "DOKE002",
+        ImportError::MissingParserError() => "DOKE003",
+        ImportError::ConfigError(_) => "DOKE004",
+        ImportError::IoError(_) => "DOKE005",
+        ImportError::AlreadyImporting(_) => "DOKE006",
+        ImportError::SaveError(..) => "DOKE007",
+        ImportError::ParseError(_) => "DOKE010",
+        ImportError::DokeValidationError(_) => "DOKE011",
+        ImportError::BuilderError(_) => "DOKE012",
+        ImportError::DispatchError(_) => "DOKE013",
+        ImportError::MiddlewareError(_) => "DOKE014",
+        ImportError::ValidationError(e) => validation_code(e),
+        ImportError::FrontmatterError(e) => frontmatter_code(e),
+        ImportError::ResInstanciationError(_) => "DOKE040",
+        ImportError::ClassNotAllowed(_) => "DOKE041",
+        ImportError::ConvertError(_) => "DOKE042",
+        ImportError::NotAResource(_) => "DOKE043",
+        ImportError::FieldError(_) => "DOKE044",
+        ImportError::FrontmatterHookFailed(_) => "DOKE045",
+        ImportError::FrontmatterHookRejected(_) => "DOKE046",
+        ImportError::UnknownProperty(..) => "DOKE047",
+        ImportError::TypeMismatch(..) => "DOKE048",
+        ImportError::ComputedError(_) => "DOKE049",
+        ImportError::TooDeep(_) => "DOKE050",
+        ImportError::TooManyElements(_) => "DOKE051",
+        ImportError::SafeModeError(_) => "DOKE052",
+        ImportError::FileTooLarge(..) => "DOKE053",
+        ImportError::ContextError(ContextError::FileTooLarge(..)) => "DOKE053",
+        ImportError::ContextError(_) => "DOKE054",
+        ImportError::AtPath(_, e) | ImportError::AtStatement(_, e) => error_code(e),
+    }
+}
+
+fn validation_code(error: &ValidationError) -> &'static str {
+    match error {
+        ValidationError::MissingFields(..) => "DOKE020",
+        ValidationError::Statement(e) if e.unmatched => "DOKE021",
+        ValidationError::Statement(_) => "DOKE022",
+        ValidationError::Many(_) => "DOKE023",
+        ValidationError::Lint(_, finding) => finding.code,
+        ValidationError::TooDeep(..) => "DOKE024",
+        ValidationError::TooManyNodes(..) => "DOKE025",
+    }
+}
+
+fn frontmatter_code(error: &FrontmatterError) -> &'static str {
+    match error {
+        FrontmatterError::Yaml(_) | FrontmatterError::Syntax(..) => "DOKE030",
+        FrontmatterError::Coercion(..) => "DOKE031",
+        FrontmatterError::Extends(..) | FrontmatterError::ExtendsPath(_) => "DOKE032",
+        FrontmatterError::ExtendsCycle(_) => "DOKE033",
+        FrontmatterError::Variable(_) => "DOKE034",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    /// Doesn't stop the import
+    Warning,
+}
+
+impl Severity {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// One problem found importing a document.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: String,
+    pub span: Option<SourceSpan>,
+    /// Empty for problems without a code
+    pub code: String,
+    /// The parser that rejected the statement, empty for other problems
+    pub parser: String,
+    /// Values of the problem that `messages:` templates can use, as `{statement}`
+    pub details: Vec<(&'static str, String)>,
+    /// Set by a `messages:` template, over the hint of the code
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(file: &str, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+            file: file.to_string(),
+            span: None,
+            code: String::new(),
+            parser: String::new(),
+            details: Vec::new(),
+            hint: None,
+        }
+    }
+
+    pub fn warning(file: &str, code: &str, message: String, span: Option<SourceSpan>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            span,
+            code: code.to_string(),
+            ..Self::error(file, message)
+        }
+    }
+
+    /// The diagnostics of a failed import, one per problem when several are reported at once.
+    pub fn from_import_error(file: &str, error: &ImportError) -> Vec<Self> {
+        match error {
+            ImportError::ValidationError(e) => from_validation_error(file, e),
+            ImportError::FrontmatterError(FrontmatterError::Syntax(span, message)) => {
+                vec![Self {
+                    span: Some(*span),
+                    code: error_code(error).to_string(),
+                    details: vec![("reason", message.clone())],
+                    ..Self::error(
+                        file,
+                        format!("YAML parse error in frontmatter : {}", message),
+                    )
+                }]
+            }
+            // Conversion errors at a statement of the document
+            ImportError::AtStatement(span, e) => Self::from_import_error(file, e)
+                .into_iter()
+                .map(|d| Self {
+                    span: d.span.or(Some(*span)),
+                    ..d
+                })
+                .collect(),
+            e => vec![Self {
+                code: error_code(e).to_string(),
+                ..Self::error(file, e.to_string())
+            }],
+        }
+    }
+
+    /// The fields of a DokeDiagnostic, for JSON.
+    pub fn to_dictionary(&self) -> Dictionary {
+        let span = self.span.unwrap_or(SourceSpan::point(0, 0));
+        let mut d = Dictionary::new();
+        d.set("severity", self.severity.as_str());
+        d.set("code", self.code.as_str());
+        d.set("message", self.message.as_str());
+        d.set("file", self.file.as_str());
+        d.set("line", span.start_line as i64);
+        d.set("column", span.start_column as i64);
+        d.set("end_line", span.end_line as i64);
+        d.set("end_column", span.end_column as i64);
+        d.set("parser", self.parser.as_str());
+        d
+    }
+
+    pub fn to_godot(&self) -> Gd<DokeDiagnostic> {
+        let span = self.span.unwrap_or(SourceSpan::point(0, 0));
+        Gd::from_init_fn(|base| DokeDiagnostic {
+            severity: self.severity.as_str().into(),
+            message: self.message.as_str().into(),
+            file: self.file.as_str().into(),
+            line: span.start_line as i64,
+            column: span.start_column as i64,
+            end_line: span.end_line as i64,
+            end_column: span.end_column as i64,
+            code: self.code.as_str().into(),
+            parser: self.parser.as_str().into(),
+            base,
+        })
+    }
+}
+
+fn from_validation_error(file: &str, error: &ValidationError) -> Vec<Diagnostic> {
+    match error {
+        ValidationError::Many(errors) => errors
+            .iter()
+            .flat_map(|e| from_validation_error(file, e))
+            .collect(),
+        ValidationError::Statement(e) => vec![Diagnostic {
+            span: Some(e.span),
+            parser: e.parser.clone().unwrap_or_default(),
+            code: validation_code(error).to_string(),
+            details: vec![
+                ("statement", e.statement.clone()),
+                ("reason", e.message.clone()),
+                ("suggestion", e.suggestion.clone().unwrap_or_default()),
+            ],
+            ..Diagnostic::error(file, format!("'{}' {}", e.statement, e.message))
+        }],
+        ValidationError::Lint(_, finding) => vec![Diagnostic {
+            span: Some(finding.span),
+            code: finding.code.to_string(),
+            ..Diagnostic::error(file, finding.message.clone())
+        }],
+        ValidationError::MissingFields(_, span, missing) => vec![Diagnostic {
+            span: Some(*span),
+            code: validation_code(error).to_string(),
+            details: vec![("fields", missing.join(", "))],
+            ..Diagnostic::error(
+                file,
+                format!("missing required fields {}", missing.join(", ")),
+            )
+        }],
+        ValidationError::TooDeep(_, span, max) => vec![Diagnostic {
+            span: Some(*span),
+            code: validation_code(error).to_string(),
+            ..Diagnostic::error(
+                file,
+                format!("markdown nests deeper than the limit of {} levels", max),
+            )
+        }],
+        ValidationError::TooManyNodes(_, max) => vec![Diagnostic {
+            code: validation_code(error).to_string(),
+            ..Diagnostic::error(
+                file,
+                format!("more than the limit of {} markdown nodes", max),
+            )
+        }],
+    }
+}
+
+/// What usually fixes the problems of a code.
+fn hint(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "DOKE020" => "set them in the frontmatter or in a statement",
+        "DOKE021" => "`explain_no_match` shows how close each sentence of the file type came",
+        "DOKE030" => "check the indentation, and quote values containing `:` or `#`",
+        "DOKE024" | "DOKE025" => {
+            "split the document, or raise the `limits` of the config if it is meant to be this big"
+        }
+        "DOKE033" => "a document can't extend itself through its `extends` chain",
+        "DOKE053" => "split the document, or raise `limits.max_file_size` in the config",
+        DROPPED_MARKDOWN => "move this text out of the Doké section, after its closing `---`",
+        EMPTY_HEADING => "add statements under it, or remove it",
+        UNKNOWN_FRONTMATTER_KEYS => "fix their spelling, or add them to `frontmatter_keys`",
+        _ => return None,
+    })
+}
+
+/// The diagnostic with the lines of `source` it is about, the span underlined, and a hint :
+///
+/// ```text
+/// error[DOKE021] : 'Adds ten to health' matches no sentence
+///  --> res://items/sword.md:7:3-20
+///   |
+/// 7 | - Adds ten to health
+///   |   ^^^^^^^^^^^^^^^^^^
+///   = hint : `explain_no_match` shows how close each sentence of the file type came
+/// ```
+pub fn render(diagnostic: &Diagnostic, source: Option<&str>) -> String {
+    let mut out = match diagnostic.code.as_str() {
+        "" => format!("{} : {}", diagnostic.severity.as_str(), diagnostic.message),
+        code => format!(
+            "{}[{}] : {}",
+            diagnostic.severity.as_str(),
+            code,
+            diagnostic.message
+        ),
+    };
+    let location = match diagnostic.span {
+        Some(span) => format!("{}:{}", diagnostic.file, span),
+        None => diagnostic.file.clone(),
+    };
+    let lines: Vec<&str> = source.map(|s| s.lines().collect()).unwrap_or_default();
+    let excerpt = diagnostic
+        .span
+        .filter(|span| span.start_line >= 1 && span.start_line <= lines.len());
+    let gutter = excerpt.map_or(0, |span| span.end_line.to_string().len());
+    out.push_str(&format!("\n{:gutter$}--> {}", "", location));
+    if let Some(span) = excerpt {
+        out.push_str(&format!("\n{:gutter$} |", ""));
+        // Long spans (whole frontmatters) show their first lines
+        let last = span.end_line.min(span.start_line + 4).min(lines.len());
+        for number in span.start_line..=last {
+            let line = lines[number - 1];
+            out.push_str(&format!("\n{:>gutter$} | {}", number, line));
+            let width = line.chars().count();
+            let from = if number == span.start_line {
+                span.start_column
+            } else {
+                1
+            };
+            let to = if number == span.end_line {
+                span.end_column
+            } else {
+                width
+            };
+            if to >= from && width > 0 {
+                out.push_str(&format!(
+                    "\n{:gutter$} | {}{}",
+                    "",
+                    " ".repeat(from - 1),
+                    "^".repeat(to - from + 1)
+                ));
+            }
+        }
+    }
+    if let Some(hint) = diagnostic.hint.as_deref().or(hint(&diagnostic.code)) {
+        out.push_str(&format!("\n{:gutter$} = hint : {}", "", hint));
+    }
+    out
+}
+
+pub fn to_array(diagnostics: &[Diagnostic]) -> Array<Gd<DokeDiagnostic>> {
+    diagnostics.iter().map(Diagnostic::to_godot).collect()
+}
+
+/// `{documents, errors, warnings, diagnostics}` as indented JSON, for CI scripts and editors.
+pub fn to_json(documents: usize, diagnostics: &[Diagnostic]) -> String {
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    };
+    let list: Array<Dictionary> = diagnostics.iter().map(Diagnostic::to_dictionary).collect();
+    let mut report = Dictionary::new();
+    report.set("documents", documents as i64);
+    report.set("errors", count(Severity::Error) as i64);
+    report.set("warnings", count(Severity::Warning) as i64);
+    report.set("diagnostics", list);
+    Json::stringify_ex(&report.to_variant())
+        .indent("  ")
+        .sort_keys(false)
+        .done()
+        .to_string()
+}
+
+#[derive(GodotClass)]
+#[class(init, base=Resource)]
+/// A problem found importing a document. `line` and `column` are 1-based,
+/// and 0 when the problem isn't about a place in the document.
+pub struct DokeDiagnostic {
+    /// `error` or `warning`
+    #[var]
+    severity: GString,
+    #[var]
+    message: GString,
+    #[var]
+    file: GString,
+    #[var]
+    line: i64,
+    #[var]
+    column: i64,
+    #[var]
+    end_line: i64,
+    #[var]
+    end_column: i64,
+    #[var]
+    code: GString,
+    /// The parser that rejected the statement, if any
+    #[var]
+    parser: GString,
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl DokeDiagnostic {
+    #[func]
+    /// `file:line:column : message`, like the errors pushed by the importer.
+    fn format(&self) -> GString {
+        if self.line == 0 {
+            return format!("{} : {}", self.file, self.message).into();
+        }
+        format!(
+            "{}:{}:{} : {}",
+            self.file, self.line, self.column, self.message
+        )
+        .into()
+    }
+}
@@ -0,0 +1,189 @@
+// diff.rs
+// What changed in a document between two imports, field by field, so live-reload
+// listeners and review tools can show the edit instead of the whole resource.
+
+use std::collections::{HashMap, VecDeque};
+
+use doke::GodotValue;
+use godot::prelude::*;
+
+use crate::middleware;
+
+type Document = (GodotValue, HashMap<String, GodotValue>);
+
+/// Documents kept by the history, the least recently imported are forgotten first.
+const MAX_DOCUMENTS: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl ChangeKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Changed => "changed",
+        }
+    }
+}
+
+/// A value that changed, at its path in the document (`stats.damage`, `abilities.0`,
+/// `frontmatter.tags`).
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub old: GodotValue,
+    pub new: GodotValue,
+}
+
+/// The last value imported from each document, and what changed from the one before.
+#[derive(Debug, Default)]
+pub struct ImportHistory {
+    last: HashMap<String, Document>,
+    changes: HashMap<String, Vec<Change>>,
+    /// The documents, least recently imported first
+    order: VecDeque<String>,
+}
+
+impl ImportHistory {
+    /// Keeps the value imported from `md_path`, and its changes when it was imported before.
+    pub fn record(&mut self, md_path: &str, document: Document) {
+        let changes = match self.last.get(md_path) {
+            Some(previous) => diff_documents(previous, &document),
+            None => Vec::new(),
+        };
+        self.order.retain(|path| path != md_path);
+        self.order.push_back(md_path.to_string());
+        if self.order.len() > MAX_DOCUMENTS
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.last.remove(&oldest);
+            self.changes.remove(&oldest);
+        }
+        self.changes.insert(md_path.to_string(), changes);
+        self.last.insert(md_path.to_string(), document);
+    }
+
+    /// The changes of the last import of `md_path` from the one before.
+    pub fn changes(&self, md_path: &str) -> &[Change] {
+        self.changes
+            .get(md_path)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+/// The changes from `old` to `new`, the built values first, then the frontmatters,
+/// sorted by path within each.
+pub fn diff_documents(old: &Document, new: &Document) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff(&old.0, &new.0, "", &mut changes);
+    let mut frontmatter = Vec::new();
+    diff_maps(&old.1, &new.1, "frontmatter", &mut frontmatter);
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    frontmatter.sort_by(|a, b| a.path.cmp(&b.path));
+    changes.extend(frontmatter);
+    changes
+}
+
+fn join(path: &str, key: &str) -> String {
+    match path {
+        "" => key.to_string(),
+        _ => format!("{}.{}", path, key),
+    }
+}
+
+/// Resources of the same type, maps and lists are compared element by element,
+/// anything else as a whole.
+fn diff(old: &GodotValue, new: &GodotValue, path: &str, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (
+            GodotValue::Resource {
+                type_name: old_type,
+                fields: old_fields,
+                ..
+            },
+            GodotValue::Resource {
+                type_name: new_type,
+                fields: new_fields,
+                ..
+            },
+        ) if old_type == new_type => diff_maps(old_fields, new_fields, path, changes),
+        (GodotValue::Dict(old_map), GodotValue::Dict(new_map)) => {
+            diff_maps(old_map, new_map, path, changes)
+        }
+        (GodotValue::Array(old_items), GodotValue::Array(new_items)) => {
+            for i in 0..old_items.len().max(new_items.len()) {
+                let key = join(path, &i.to_string());
+                match (old_items.get(i), new_items.get(i)) {
+                    (Some(o), Some(n)) => diff(o, n, &key, changes),
+                    (Some(o), None) => {
+                        changes.push(change(key, ChangeKind::Removed, o, &GodotValue::Nil))
+                    }
+                    (None, Some(n)) => {
+                        changes.push(change(key, ChangeKind::Added, &GodotValue::Nil, n))
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+        _ if old != new => changes.push(change(path.to_string(), ChangeKind::Changed, old, new)),
+        _ => {}
+    }
+}
+
+fn diff_maps(
+    old: &HashMap<String, GodotValue>,
+    new: &HashMap<String, GodotValue>,
+    path: &str,
+    changes: &mut Vec<Change>,
+) {
+    for (key, o) in old {
+        match new.get(key) {
+            Some(n) => diff(o, n, &join(path, key), changes),
+            None => changes.push(change(
+                join(path, key),
+                ChangeKind::Removed,
+                o,
+                &GodotValue::Nil,
+            )),
+        }
+    }
+    for (key, n) in new {
+        if !old.contains_key(key) {
+            changes.push(change(
+                join(path, key),
+                ChangeKind::Added,
+                &GodotValue::Nil,
+                n,
+            ));
+        }
+    }
+}
+
+fn change(path: String, kind: ChangeKind, old: &GodotValue, new: &GodotValue) -> Change {
+    Change {
+        path,
+        kind,
+        old: old.clone(),
+        new: new.clone(),
+    }
+}
+
+/// path -> `{change, old, new}`, `change` being `added`, `removed` or `changed`.
+pub fn to_dictionary(changes: &[Change]) -> Dictionary {
+    let mut d = Dictionary::new();
+    for c in changes {
+        let mut entry = Dictionary::new();
+        entry.set("change", c.kind.name());
+        entry.set("old", middleware::to_variant(&c.old));
+        entry.set("new", middleware::to_variant(&c.new));
+        d.set(c.path.as_str(), entry);
+    }
+    d
+}
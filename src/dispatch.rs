@@ -0,0 +1,99 @@
+// dispatch.rs
+// Builders picked by a frontmatter key (`dispatch:` in the config), so one
+// folder can mix documents of several resource types under one file type.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use doke::GodotValue;
+use doke::file_builder::ResourceBuilder;
+use thiserror::Error;
+use yaml_rust2::Yaml;
+
+use crate::config::{self, ConfigError};
+use crate::frontmatter::normalize_key;
+
+#[derive(Debug, Error)]
+pub enum DispatchError {
+    #[error("{0}: '{1}' isn't one of {2}")]
+    UnknownValue(String, String, String),
+}
+
+/// `dispatch:` the frontmatter `key`, and a builder per value of it.
+#[derive(Debug, Clone)]
+pub struct Dispatch {
+    key: String,
+    /// (value, builder), values as written in the config
+    builders: Vec<(String, Arc<ResourceBuilder>)>,
+}
+
+impl Dispatch {
+    /// Builders are inline `root`/`children` maps, or paths to config files relative to `base_dir`.
+    pub fn from_yaml(y: &Yaml, base_dir: &Path) -> Result<Option<Self>, ConfigError> {
+        if y.is_badvalue() {
+            return Ok(None);
+        }
+        let key = y["key"]
+            .as_str()
+            .ok_or_else(|| ConfigError::Invalid("'dispatch' needs a frontmatter 'key'".into()))?;
+        let builders = y["builders"].as_hash().ok_or_else(|| {
+            ConfigError::Invalid("'dispatch.builders' must be a map of value: builder".into())
+        })?;
+        let mut dispatch = Self {
+            key: key.to_string(),
+            builders: Vec::new(),
+        };
+        for (value, builder) in builders {
+            let value = match value {
+                Yaml::String(s) => s.clone(),
+                Yaml::Integer(i) => i.to_string(),
+                _ => {
+                    return Err(ConfigError::Invalid(
+                        "'dispatch.builders' keys must be values of the key".into(),
+                    ));
+                }
+            };
+            let builder = match builder {
+                Yaml::String(path) => ResourceBuilder::from_file(&base_dir.join(path)),
+                _ => config::builder_config(builder).and_then(ResourceBuilder::from_config),
+            }
+            .map_err(|e| ConfigError::Invalid(format!("Builder for '{}' : {}", value, e)))?;
+            dispatch.builders.push((value, builder.into()));
+        }
+        Ok(Some(dispatch))
+    }
+
+    /// The builder for the document's value of the key, or None if it doesn't set the key.
+    /// Values are matched ignoring case and spaces.
+    pub fn builder_for(
+        &self,
+        frontmatter: &HashMap<String, GodotValue>,
+    ) -> Result<Option<&Arc<ResourceBuilder>>, DispatchError> {
+        let key = normalize_key(&self.key);
+        let found = frontmatter
+            .iter()
+            .find(|(k, _)| normalize_key(k) == key)
+            .map(|(_, v)| v);
+        let value = match found {
+            None | Some(GodotValue::Nil) => return Ok(None),
+            Some(GodotValue::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+        };
+        self.builders
+            .iter()
+            .find(|(v, _)| normalize_key(v) == normalize_key(&value))
+            .map(|(_, builder)| Some(builder))
+            .ok_or_else(|| {
+                DispatchError::UnknownValue(
+                    self.key.clone(),
+                    value,
+                    self.builders
+                        .iter()
+                        .map(|(v, _)| v.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                )
+            })
+    }
+}
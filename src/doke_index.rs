@@ -0,0 +1,130 @@
+// doke_index.rs
+// The documents of a project saved as a resource, so games can enumerate the
+// content of a type at runtime without touching the filesystem.
+
+use godot::global::push_error;
+use godot::prelude::*;
+
+use crate::index;
+use crate::query::Filter;
+use crate::search;
+
+#[derive(GodotClass)]
+#[class(init, base=Resource)]
+/// Every document of the loaded file types, as `{id, path, type, tags, links, hash, frontmatter}` :
+/// `id` is the `id` frontmatter key or the file name, `links` the link keys of its
+/// `[[wiki links]]` and `hash` the SHA-256 of the file when it was indexed.
+/// Made by `DokeImporter.save_doke_index`.
+pub struct DokeIndex {
+    #[export]
+    documents: Array<Dictionary>,
+    /// The words of the documents, `{term: {document: count}}`, documents by their position.
+    #[export]
+    terms: Dictionary,
+    base: Base<Resource>,
+}
+
+impl DokeIndex {
+    pub fn create(documents: Array<Dictionary>, terms: Dictionary) -> Gd<Self> {
+        Gd::from_init_fn(|base| Self {
+            documents,
+            terms,
+            base,
+        })
+    }
+
+    pub fn entries(&self) -> Array<Dictionary> {
+        self.documents.clone()
+    }
+
+    pub fn field(document: &Dictionary, key: &str) -> String {
+        document.get(key).map(|v| v.to_string()).unwrap_or_default()
+    }
+
+    /// The documents of file type `type_name` (any when it's empty) matching `query`,
+    /// with their scores, from the best match.
+    pub fn search_entries(&self, query: &str, type_name: &str) -> Vec<(i64, Dictionary)> {
+        search::search(&self.terms, query)
+            .into_iter()
+            .filter_map(|(i, score)| Some((score, self.documents.get(i as usize)?)))
+            .filter(|(_, d)| type_name.is_empty() || Self::field(d, "type") == type_name)
+            .collect()
+    }
+
+    fn filter(&self, keep: impl Fn(&Dictionary) -> bool) -> Array<Dictionary> {
+        self.documents.iter_shared().filter(|d| keep(d)).collect()
+    }
+}
+
+#[godot_api]
+impl DokeIndex {
+    #[func]
+    /// The documents of file type `type_name`, all of them when it's empty.
+    fn get_documents_of_type(&self, type_name: String) -> Array<Dictionary> {
+        self.filter(|d| type_name.is_empty() || Self::field(d, "type") == type_name)
+    }
+
+    #[func]
+    /// The document with that `id`, empty if there is none.
+    fn get_document(&self, id: String) -> Dictionary {
+        self.documents
+            .iter_shared()
+            .find(|d| Self::field(d, "id") == id)
+            .unwrap_or_default()
+    }
+
+    #[func]
+    /// The paths of the documents of file type `type_name`, all of them when it's empty.
+    fn get_paths(&self, type_name: String) -> PackedStringArray {
+        self.get_documents_of_type(type_name)
+            .iter_shared()
+            .map(|d| GString::from(Self::field(&d, "path")))
+            .collect()
+    }
+
+    #[func]
+    fn get_documents_by_tag(&self, tag: String) -> Array<Dictionary> {
+        let tag = index::normalize_tag(&tag);
+        self.filter(|d| {
+            d.get("tags")
+                .and_then(|t| t.try_to::<PackedStringArray>().ok())
+                .is_some_and(|tags| tags.as_slice().iter().any(|t| t.to_string() == tag))
+        })
+    }
+
+    #[func]
+    /// The documents matching `filter` : `{"rarity": "epic", "level": {"<=": 10}, "tag": "weapon"}`.
+    /// Fields are read from the frontmatter, then from the document (`id`, `type`...).
+    fn query(&self, filter: Dictionary) -> Array<Dictionary> {
+        match Filter::from_dictionary(&filter) {
+            Ok(filter) => self.filter(|d| filter.matches(d)),
+            Err(e) => {
+                push_error(&[Variant::from(e)]);
+                Array::new()
+            }
+        }
+    }
+
+    #[func]
+    /// The documents containing every word of `query`, most occurrences first, of file
+    /// type `type_filter` or any when it's empty. Words match the words they start.
+    fn search(&self, query: String, type_filter: String) -> Array<Dictionary> {
+        self.search_entries(&query, &type_filter)
+            .into_iter()
+            .map(|(_, d)| d)
+            .collect()
+    }
+
+    #[func]
+    /// The file types of the documents, sorted.
+    fn get_types(&self) -> PackedStringArray {
+        let mut types: Vec<String> = self
+            .documents
+            .iter_shared()
+            .map(|d| Self::field(&d, "type"))
+            .collect();
+        types.sort();
+        types.dedup();
+        types.iter().map(GString::from).collect()
+    }
+}
@@ -0,0 +1,140 @@
+// export.rs
+// A document's parsed structure as JSON, so pipelines outside Godot (web wikis,
+// balancing spreadsheets) read the same source of truth as the game.
+
+use std::path::{Path, PathBuf};
+
+use doke::semantic::DokeNodeState;
+use doke::{DokeDocument, DokeNode};
+use godot::classes::{Json, ProjectSettings};
+use godot::prelude::*;
+
+use crate::index;
+use crate::middleware;
+use crate::parser_api::HANDLED_BY_KEY;
+use crate::span::SourceSpan;
+
+/// A node and its constituents and children : `statement`, its place in the document,
+/// `state` (`unresolved`, `resolved`, `hypotheses` or `error`) with the `value`,
+/// `hypotheses` or `error` of that state, and the `parser` that handled it.
+fn node_dictionary(node: &DokeNode, body: &str, first_line: usize) -> Dictionary {
+    let span = SourceSpan::of_range(body, node.span.start, node.span.end, first_line);
+    let mut d = Dictionary::new();
+    d.set("statement", node.statement.trim());
+    d.set("line", span.start_line as i64);
+    d.set("column", span.start_column as i64);
+    d.set("end_line", span.end_line as i64);
+    d.set("end_column", span.end_column as i64);
+    match &node.state {
+        DokeNodeState::Unresolved => d.set("state", "unresolved"),
+        DokeNodeState::Resolved(value) => {
+            d.set("state", "resolved");
+            d.set("kind", value.kind());
+            d.set("value", middleware::to_variant(&value.to_godot()));
+        }
+        DokeNodeState::Hypothesis(hypos) => {
+            d.set("state", "hypotheses");
+            let hypotheses: Array<Dictionary> = hypos
+                .iter()
+                .map(|h| {
+                    let mut hypo = Dictionary::new();
+                    hypo.set("kind", h.kind());
+                    hypo.set("confidence", h.confidence() as f64);
+                    hypo
+                })
+                .collect();
+            d.set("hypotheses", hypotheses);
+        }
+        DokeNodeState::Error(e) => {
+            d.set("state", "error");
+            d.set("error", e.to_string());
+        }
+    }
+    if let Some(parser) = node.parse_data.get(HANDLED_BY_KEY) {
+        d.set("parser", middleware::to_variant(parser));
+    }
+    let mut names: Vec<_> = node.constituents.iter().collect();
+    names.sort_by_key(|(name, _)| name.as_str());
+    let mut constituents = Dictionary::new();
+    for (name, constituent) in names {
+        constituents.set(
+            name.as_str(),
+            node_dictionary(constituent, body, first_line),
+        );
+    }
+    d.set("constituents", constituents);
+    d.set("children", nodes_array(&node.children, body, first_line));
+    d
+}
+
+fn nodes_array(nodes: &[DokeNode], body: &str, first_line: usize) -> Array<Dictionary> {
+    nodes
+        .iter()
+        .map(|node| node_dictionary(node, body, first_line))
+        .collect()
+}
+
+/// The `[[wiki links]]` of a body, `{target, path}` with the path of the document
+/// among `candidates` it points to, empty when none is called that.
+fn links_array(body: &str, candidates: &[PathBuf]) -> Array<Dictionary> {
+    let settings = ProjectSettings::singleton();
+    index::extract_wiki_links(body)
+        .into_iter()
+        .map(|target| {
+            let path = candidates
+                .iter()
+                .find(|p| index::document_name(p).to_lowercase() == target)
+                .map(|p| {
+                    settings
+                        .localize_path(p.to_string_lossy().as_ref())
+                        .to_string()
+                })
+                .unwrap_or_default();
+            let mut link = Dictionary::new();
+            link.set("target", target);
+            link.set("path", path);
+            link
+        })
+        .collect()
+}
+
+/// The documents a link of `md_path` may point to when its file type doesn't list them :
+/// the markdown files of its folder and below.
+pub fn sibling_documents(md_path: &str) -> Vec<PathBuf> {
+    let path = ProjectSettings::singleton()
+        .globalize_path(md_path)
+        .to_string();
+    Path::new(&path)
+        .parent()
+        .map(index::markdown_files)
+        .unwrap_or_default()
+}
+
+/// `{path, file_type, frontmatter, nodes, links}` as indented JSON, `body` being
+/// the document's markdown after its frontmatter, starting at `first_line`.
+pub fn to_json(
+    md_path: &str,
+    file_type: &str,
+    doc: &DokeDocument,
+    body: &str,
+    first_line: usize,
+    candidates: &[PathBuf],
+) -> String {
+    let mut frontmatter: Vec<_> = doc.frontmatter.iter().collect();
+    frontmatter.sort_by_key(|(k, _)| k.as_str());
+    let frontmatter: Dictionary = frontmatter
+        .into_iter()
+        .map(|(k, v)| (k.to_variant(), middleware::to_variant(v)))
+        .collect();
+    let mut export = Dictionary::new();
+    export.set("path", md_path);
+    export.set("file_type", file_type);
+    export.set("frontmatter", frontmatter);
+    export.set("nodes", nodes_array(&doc.nodes, body, first_line));
+    export.set("links", links_array(body, candidates));
+    Json::stringify_ex(&export.to_variant())
+        .indent("  ")
+        .sort_keys(false)
+        .done()
+        .to_string()
+}
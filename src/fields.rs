@@ -0,0 +1,506 @@
+// fields.rs
+// Godot types declared for fields in a config's `fields:` map. Yaml has no
+// notion of vectors, colors, enums or typed arrays, so documents write them
+// as lists or strings and they are converted when the values become Variants.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use doke::GodotValue;
+use godot::classes::{Curve, Gradient, ResourceLoader};
+use godot::prelude::*;
+use thiserror::Error;
+use yaml_rust2::Yaml;
+
+use crate::config::ConfigError;
+use crate::converters;
+use crate::frontmatter::normalize_key;
+
+#[derive(Debug, Error)]
+pub enum FieldError {
+    #[error("Field '{0}' : can't make a {1} from {2}")]
+    Convert(String, FieldKind, String),
+    #[error("Field '{0}' : '{1}' isn't one of {2}")]
+    NotInEnum(String, String, String),
+    #[error("Field '{0}' : can't load a resource from '{1}'")]
+    Load(String, String),
+    #[error("Field '{0}' : {1} : {2}")]
+    Converter(String, String, String),
+}
+
+/// A Godot type a field is converted to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldKind {
+    Vector2,
+    Vector3,
+    Color,
+    Rect2,
+    StringName,
+    NodePath,
+    /// A Curve from a list of points, `[x, y]` each
+    Curve,
+    /// A Gradient from a list of stops, `[offset, color]` each
+    Gradient,
+    Enum(EnumField),
+    PackedInt32Array,
+    PackedFloat32Array,
+    PackedStringArray,
+    /// `Array[Class]` : a list whose elements all have this type
+    TypedArray(String),
+    /// `Resource` or `Resource[Class]` : a path loaded with the ResourceLoader
+    Resource(Option<String>),
+    /// `converter:` name of a FieldConverter registered from Rust
+    Converter(String),
+    /// `Dictionary[Key, Value]` : a map whose keys and values are converted to these types
+    TypedDictionary(Scalar, Scalar),
+}
+
+/// Types of the keys and values of a typed Dictionary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scalar {
+    Int,
+    Float,
+    Bool,
+    String,
+    StringName,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.trim() {
+            "int" => Some(Self::Int),
+            "float" => Some(Self::Float),
+            "bool" => Some(Self::Bool),
+            "String" => Some(Self::String),
+            "StringName" => Some(Self::StringName),
+            _ => None,
+        }
+    }
+
+    /// Converts a value, or text when it is a map's key.
+    fn convert(self, value: &GodotValue) -> Option<Variant> {
+        match (self, value) {
+            (Self::Int, GodotValue::Int(i)) => Some(i.to_variant()),
+            (Self::Int, GodotValue::String(s)) => {
+                s.trim().parse::<i64>().ok().map(|i| i.to_variant())
+            }
+            (Self::Float, GodotValue::Int(i)) => Some((*i as f64).to_variant()),
+            (Self::Float, GodotValue::Float(f)) => Some(f.to_variant()),
+            (Self::Float, GodotValue::String(s)) => {
+                s.trim().parse::<f64>().ok().map(|f| f.to_variant())
+            }
+            (Self::Bool, GodotValue::Bool(b)) => Some(b.to_variant()),
+            (Self::Bool, GodotValue::String(s)) => {
+                s.trim().parse::<bool>().ok().map(|b| b.to_variant())
+            }
+            (Self::String, GodotValue::String(s)) => Some(s.to_variant()),
+            (Self::StringName, GodotValue::String(s)) => Some(StringName::from(s).to_variant()),
+            (Self::String, GodotValue::Int(_) | GodotValue::Float(_) | GodotValue::Bool(_)) => {
+                Some(value.to_string().to_variant())
+            }
+            (Self::StringName, GodotValue::Int(_) | GodotValue::Float(_) | GodotValue::Bool(_)) => {
+                Some(StringName::from(&value.to_string()).to_variant())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int => write!(f, "int"),
+            Self::Float => write!(f, "float"),
+            Self::Bool => write!(f, "bool"),
+            Self::String => write!(f, "String"),
+            Self::StringName => write!(f, "StringName"),
+        }
+    }
+}
+
+/// `enum:` allowed names, as a list (0, 1, 2...) or a map of name -> int.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumField {
+    values: Vec<(String, i64)>,
+    /// `as: StringName` keeps the name instead of the int
+    as_string_name: bool,
+}
+
+impl EnumField {
+    fn from_yaml(y: &Yaml) -> Result<Self, String> {
+        let values = match &y["enum"] {
+            Yaml::Array(names) => names
+                .iter()
+                .zip(0..)
+                .map(|(name, i)| Some((name.as_str()?.to_string(), i)))
+                .collect::<Option<Vec<_>>>(),
+            Yaml::Hash(map) => map
+                .iter()
+                .map(|(name, i)| Some((name.as_str()?.to_string(), i.as_i64()?)))
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        }
+        .ok_or("'enum' must be a list of names or a map of name: int")?;
+        let as_string_name = match y["as"].as_str() {
+            None | Some("int") => false,
+            Some("StringName") => true,
+            Some(other) => return Err(format!("enums are int or StringName, not '{}'", other)),
+        };
+        Ok(Self {
+            values,
+            as_string_name,
+        })
+    }
+
+    /// Names are matched ignoring case and spaces, ints must be one of the values.
+    fn convert(&self, field: &str, value: &GodotValue) -> Result<Variant, FieldError> {
+        let found = match value {
+            GodotValue::String(s) => self
+                .values
+                .iter()
+                .find(|(name, _)| normalize_key(name) == normalize_key(s)),
+            GodotValue::Int(i) => self.values.iter().find(|(_, v)| v == i),
+            _ => None,
+        };
+        match found {
+            Some((name, _)) if self.as_string_name => Ok(StringName::from(name).to_variant()),
+            Some((_, i)) => Ok(i.to_variant()),
+            None => Err(FieldError::NotInEnum(
+                field.to_string(),
+                value.to_string(),
+                self.values
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )),
+        }
+    }
+}
+
+impl FieldKind {
+    fn from_yaml(y: &Yaml) -> Result<Self, String> {
+        match y.as_str() {
+            Some("Vector2") => Ok(Self::Vector2),
+            Some("Vector3") => Ok(Self::Vector3),
+            Some("Color") => Ok(Self::Color),
+            Some("Rect2") => Ok(Self::Rect2),
+            Some("StringName") => Ok(Self::StringName),
+            Some("NodePath") => Ok(Self::NodePath),
+            Some("Curve") => Ok(Self::Curve),
+            Some("Gradient") => Ok(Self::Gradient),
+            Some("PackedInt32Array") => Ok(Self::PackedInt32Array),
+            Some("PackedFloat32Array") => Ok(Self::PackedFloat32Array),
+            Some("PackedStringArray") => Ok(Self::PackedStringArray),
+            Some(ty) if ty.starts_with("Array[") && ty.ends_with(']') => {
+                Ok(Self::TypedArray(ty[6..ty.len() - 1].trim().to_string()))
+            }
+            Some(ty) if ty.starts_with("Dictionary[") && ty.ends_with(']') => {
+                let types = &ty[11..ty.len() - 1];
+                types
+                    .split_once(',')
+                    .and_then(|(k, v)| Some(Self::TypedDictionary(Scalar::from_name(k)?, Scalar::from_name(v)?)))
+                    .ok_or_else(|| {
+                        format!(
+                            "'{}' : dictionaries take a key and a value type among int, float, bool, String and StringName",
+                            ty
+                        )
+                    })
+            }
+            Some("Resource") => Ok(Self::Resource(None)),
+            Some(ty) if ty.starts_with("Resource[") && ty.ends_with(']') => {
+                Ok(Self::Resource(Some(ty[9..ty.len() - 1].trim().to_string())))
+            }
+            Some(other) => Err(format!(
+                "unknown type '{}' (expected Vector2, Vector3, Color, Rect2, StringName, NodePath, \
+                 Curve, Gradient, \
+                 a packed array, Array[Type], Dictionary[Key, Value], Resource[Type] or an enum)",
+                other
+            )),
+            None if !y["converter"].is_badvalue() => {
+                let name = y["converter"]
+                    .as_str()
+                    .ok_or("'converter' must be a name")?;
+                if converters::field_converter(name).is_none() {
+                    return Err(format!("no converter is registered as '{}'", name));
+                }
+                Ok(Self::Converter(name.to_string()))
+            }
+            None => EnumField::from_yaml(y).map(Self::Enum),
+        }
+    }
+
+    pub fn convert(&self, field: &str, value: &GodotValue) -> Result<Variant, FieldError> {
+        match self {
+            Self::Enum(e) => return e.convert(field, value),
+            Self::Resource(class) => return load_resource(field, class.as_deref(), value),
+            Self::Converter(name) => {
+                let error = |e: String| FieldError::Converter(field.to_string(), name.clone(), e);
+                let converter = converters::field_converter(name)
+                    .ok_or_else(|| error("no converter is registered with this name".into()))?;
+                return converter.convert(value).map_err(error);
+            }
+            _ => {}
+        }
+        self.convert_shape(value)
+            .ok_or_else(|| FieldError::Convert(field.to_string(), self.clone(), value.to_string()))
+    }
+
+    /// Converts a value, or returns None if it has the wrong shape.
+    fn convert_shape(&self, value: &GodotValue) -> Option<Variant> {
+        match self {
+            Self::Vector2 => {
+                let [x, y] = components(value, &["x", "y"])?;
+                Some(Vector2::new(x, y).to_variant())
+            }
+            Self::Vector3 => {
+                let [x, y, z] = components(value, &["x", "y", "z"])?;
+                Some(Vector3::new(x, y, z).to_variant())
+            }
+            Self::Rect2 => {
+                let [x, y, w, h] = components(value, &["x", "y", "w", "h"])?;
+                Some(Rect2::new(Vector2::new(x, y), Vector2::new(w, h)).to_variant())
+            }
+            Self::StringName => match value {
+                GodotValue::String(s) => Some(StringName::from(s).to_variant()),
+                _ => None,
+            },
+            Self::NodePath => match value {
+                GodotValue::String(s) => Some(NodePath::from(s).to_variant()),
+                _ => None,
+            },
+            Self::Curve => {
+                let points = list(value)?
+                    .iter()
+                    .map(|p| components(p, &["x", "y"]).map(|[x, y]| Vector2::new(x, y)))
+                    .collect::<Option<Vec<_>>>()?;
+                let mut curve = Curve::new_gd();
+                // Curves default to [0, 1] on both axes and clamp points to it
+                let (mut min, mut max) = (Vector2::ZERO, Vector2::ONE);
+                for p in &points {
+                    min = min.coord_min(*p);
+                    max = max.coord_max(*p);
+                }
+                curve.set_min_domain(min.x);
+                curve.set_max_domain(max.x);
+                curve.set_min_value(min.y);
+                curve.set_max_value(max.y);
+                for p in points {
+                    curve.add_point(p);
+                }
+                Some(curve.to_variant())
+            }
+            Self::Gradient => {
+                let stops = list(value)?
+                    .iter()
+                    .map(|stop| {
+                        let (offset, color) = match stop {
+                            GodotValue::Array(pair) if pair.len() == 2 => (&pair[0], &pair[1]),
+                            GodotValue::Dict(map) => (map.get("offset")?, map.get("color")?),
+                            _ => return None,
+                        };
+                        let offset = match offset {
+                            GodotValue::Int(i) => *i as f32,
+                            GodotValue::Float(f) => *f as f32,
+                            _ => return None,
+                        };
+                        let color = Self::Color.convert_shape(color)?.to::<Color>();
+                        Some((offset, color))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                let mut gradient = Gradient::new_gd();
+                gradient.set_offsets(&stops.iter().map(|(o, _)| *o).collect());
+                gradient.set_colors(&stops.iter().map(|(_, c)| *c).collect());
+                Some(gradient.to_variant())
+            }
+            Self::TypedDictionary(key, val) => match value {
+                GodotValue::Dict(map) => {
+                    let mut dict = Dictionary::new();
+                    for (k, v) in map {
+                        dict.set(
+                            key.convert(&GodotValue::String(k.clone()))?,
+                            val.convert(v)?,
+                        );
+                    }
+                    Some(dict.to_variant())
+                }
+                _ => None,
+            },
+            Self::Color => match value {
+                // Html codes (`#ff8800`) and named colors (`red`)
+                GodotValue::String(s) => Color::from_string(s.as_str()).map(|c| c.to_variant()),
+                _ => components(value, &["r", "g", "b", "a"])
+                    .or_else(|| components(value, &["r", "g", "b"]).map(|[r, g, b]| [r, g, b, 1.0]))
+                    .map(|[r, g, b, a]| Color::from_rgba(r, g, b, a).to_variant()),
+            },
+            Self::PackedInt32Array => list(value)?
+                .iter()
+                .map(|v| match v {
+                    GodotValue::Int(i) => i32::try_from(*i).ok(),
+                    _ => None,
+                })
+                .collect::<Option<PackedInt32Array>>()
+                .map(|a| a.to_variant()),
+            Self::PackedFloat32Array => list(value)?
+                .iter()
+                .map(|v| match v {
+                    GodotValue::Int(i) => Some(*i as f32),
+                    GodotValue::Float(f) => Some(*f as f32),
+                    _ => None,
+                })
+                .collect::<Option<PackedFloat32Array>>()
+                .map(|a| a.to_variant()),
+            Self::PackedStringArray => list(value)?
+                .iter()
+                .map(|v| match v {
+                    GodotValue::String(s) => Some(GString::from(s.as_str())),
+                    GodotValue::Int(_) | GodotValue::Float(_) | GodotValue::Bool(_) => {
+                        Some(GString::from(v.to_string().as_str()))
+                    }
+                    _ => None,
+                })
+                .collect::<Option<PackedStringArray>>()
+                .map(|a| a.to_variant()),
+            // Converted by `convert`, or built by the importer for typed arrays (see check_typed_array)
+            Self::Enum(_) | Self::TypedArray(_) | Self::Resource(_) | Self::Converter(_) => None,
+        }
+    }
+
+    /// Checks every element of an `Array[Class]` field has the type, before the importer builds them.
+    pub fn check_typed_array(&self, field: &str, value: &GodotValue) -> Result<(), FieldError> {
+        let Self::TypedArray(class) = self else {
+            return Ok(());
+        };
+        let error =
+            |v: &GodotValue| FieldError::Convert(field.to_string(), self.clone(), v.to_string());
+        for item in list(value).ok_or_else(|| error(value))? {
+            let ok = match (class.as_str(), item) {
+                ("int", GodotValue::Int(_))
+                | ("float", GodotValue::Float(_) | GodotValue::Int(_))
+                | ("String", GodotValue::String(_))
+                | ("bool", GodotValue::Bool(_))
+                | ("Resource", GodotValue::Resource { .. }) => true,
+                (
+                    class,
+                    GodotValue::Resource {
+                        type_name,
+                        abstract_type_name,
+                        ..
+                    },
+                ) => type_name == class || abstract_type_name == class,
+                _ => false,
+            };
+            if !ok {
+                return Err(error(item));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Loads the resource at a `res://` or `uid://` path, null for an empty value.
+fn load_resource(
+    field: &str,
+    class: Option<&str>,
+    value: &GodotValue,
+) -> Result<Variant, FieldError> {
+    let path = match value {
+        GodotValue::Nil => return Ok(Variant::nil()),
+        GodotValue::String(s) if s.trim().is_empty() => return Ok(Variant::nil()),
+        GodotValue::String(s) => s.trim(),
+        other => {
+            return Err(FieldError::Convert(
+                field.to_string(),
+                FieldKind::Resource(class.map(str::to_string)),
+                other.to_string(),
+            ));
+        }
+    };
+    let res = ResourceLoader::singleton()
+        .load(path)
+        .ok_or_else(|| FieldError::Load(field.to_string(), path.to_string()))?;
+    if let Some(class) = class
+        && !res.is_class(class)
+    {
+        return Err(FieldError::Convert(
+            field.to_string(),
+            FieldKind::Resource(Some(class.to_string())),
+            format!("{} ({})", path, res.get_class()),
+        ));
+    }
+    Ok(res.to_variant())
+}
+
+fn list(value: &GodotValue) -> Option<&[GodotValue]> {
+    match value {
+        GodotValue::Array(items) => Some(items),
+        _ => None,
+    }
+}
+
+impl fmt::Display for FieldKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Enum(_) => write!(f, "enum"),
+            Self::TypedArray(class) => write!(f, "Array[{}]", class),
+            Self::Resource(Some(class)) => write!(f, "Resource[{}]", class),
+            Self::Resource(None) => write!(f, "Resource"),
+            Self::Converter(name) => write!(f, "{}", name),
+            Self::TypedDictionary(k, v) => write!(f, "Dictionary[{}, {}]", k, v),
+            other => fmt::Debug::fmt(other, f),
+        }
+    }
+}
+
+/// Reads N numbers from a list (`[3, 4]`), a map (`{x: 3, y: 4}`) or a comma separated string (`3, 4`).
+fn components<const N: usize>(value: &GodotValue, names: &[&str; N]) -> Option<[f32; N]> {
+    let number = |v: &GodotValue| match v {
+        GodotValue::Int(i) => Some(*i as f32),
+        GodotValue::Float(f) => Some(*f as f32),
+        GodotValue::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    let numbers: Vec<f32> = match value {
+        GodotValue::Array(items) => items.iter().map(number).collect::<Option<_>>()?,
+        GodotValue::Dict(map) => names
+            .iter()
+            .map(|n| map.get(*n).and_then(number))
+            .collect::<Option<_>>()?,
+        GodotValue::String(s) => s
+            .trim_matches(|c| "()[] ".contains(c))
+            .split(',')
+            .map(|part| part.trim().parse().ok())
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+    numbers.try_into().ok()
+}
+
+/// `fields:` normalized field name -> Godot type.
+#[derive(Debug, Clone, Default)]
+pub struct FieldTypes {
+    types: HashMap<String, FieldKind>,
+}
+
+impl FieldTypes {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let mut fields = Self::default();
+        let Some(hash) = y.as_hash() else {
+            return Ok(fields);
+        };
+        for (k, v) in hash {
+            let key = k
+                .as_str()
+                .ok_or_else(|| ConfigError::Invalid("'fields' keys must be strings".into()))?;
+            let kind = FieldKind::from_yaml(v)
+                .map_err(|e| ConfigError::Invalid(format!("Field '{}' : {}", key, e)))?;
+            fields.types.insert(normalize_key(key), kind);
+        }
+        Ok(fields)
+    }
+
+    /// The type declared for a field, if any.
+    pub fn get(&self, field: &str) -> Option<&FieldKind> {
+        self.types.get(&normalize_key(field))
+    }
+}
@@ -0,0 +1,28 @@
+// file_error.rs
+// IO errors name the file they happened on : `std::io::Error` alone says
+// "No such file or directory" without saying which one.
+
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("{path} : {source}")]
+pub struct FileError {
+    pub path: PathBuf,
+    pub source: std::io::Error,
+}
+
+/// `.in_file(path)?` on IO results, instead of a bare `?`.
+pub trait ErrorContext<T> {
+    fn in_file(self, path: impl AsRef<Path>) -> Result<T, FileError>;
+}
+
+impl<T> ErrorContext<T> for std::io::Result<T> {
+    fn in_file(self, path: impl AsRef<Path>) -> Result<T, FileError> {
+        self.map_err(|source| FileError {
+            path: path.as_ref().to_path_buf(),
+            source,
+        })
+    }
+}
@@ -0,0 +1,328 @@
+// frontmatter.rs
+// Reading the YAML header of doke documents on its own, without running the
+// markdown pipeline. Keys and values are normalized the same way `DokePipe` does,
+// so anything read here lines up with what parsers and builders see.
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use doke::GodotValue;
+use thiserror::Error;
+use yaml_rust2::{ScanError, Yaml, YamlEmitter, YamlLoader, yaml::Hash};
+
+use crate::config::Coercion;
+use crate::file_error::{ErrorContext, FileError};
+use crate::parser_context::{ContextError, ParserContext};
+use crate::span::SourceSpan;
+use crate::variables::VariableError;
+
+#[derive(Debug, Error)]
+pub enum FrontmatterError {
+    #[error("YAML parse error in frontmatter : {0}")]
+    Yaml(String),
+    #[error("{0} : YAML parse error in frontmatter : {1}")]
+    Syntax(SourceSpan, String),
+    #[error("Can't coerce frontmatter value '{0}' to {1:?}")]
+    Coercion(String, Coercion),
+    #[error("Can't read extended document {0}")]
+    Extends(FileError),
+    #[error("Can't extend {0}")]
+    ExtendsPath(#[from] ContextError),
+    #[error("Cycle in extends chain : {0}")]
+    ExtendsCycle(String),
+    #[error("{0}")]
+    Variable(#[from] VariableError),
+}
+
+pub const EXTENDS_KEY: &str = "extends";
+
+/// Reads the doke part of a document : everything before the third `---` line.
+pub fn read_doke_section(path: &Path) -> Result<String, FileError> {
+    read_until_separator(path, 3, true).in_file(path)
+}
+
+/// Reads only the frontmatter of a document, up to its closing `---` line.
+pub fn read_header(path: &Path) -> Result<String, FileError> {
+    read_until_separator(path, 2, false).in_file(path)
+}
+
+/// Reads the wiki part of a document : what follows its third `---` line.
+pub fn read_wiki_section(path: &Path) -> Result<String, FileError> {
+    let text = std::fs::read_to_string(path).in_file(path)?;
+    Ok(wiki_section(&text).to_string())
+}
+
+/// The text after the third `---` line, empty without one.
+pub fn wiki_section(text: &str) -> &str {
+    let mut separators = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim() == "---" {
+            separators += 1;
+            if separators == 3 {
+                return &text[offset..];
+            }
+        }
+    }
+    ""
+}
+
+/// Reads lines straight into one buffer, sized to the whole file when `whole_file`
+/// is likely, instead of growing it line after line. Line ends become `\n`.
+fn read_until_separator(
+    path: &Path,
+    separators: usize,
+    whole_file: bool,
+) -> std::io::Result<String> {
+    let file = std::fs::File::open(path)?;
+    let capacity = if whole_file {
+        file.metadata()?.len() as usize + 1
+    } else {
+        0
+    };
+    let mut input = String::with_capacity(capacity);
+    let mut reader = std::io::BufReader::new(file);
+    let mut separator_count = 0;
+    loop {
+        let start = input.len();
+        if reader.read_line(&mut input)? == 0 {
+            break;
+        }
+        let line = &input[start..];
+        if line.trim() == "---" {
+            separator_count += 1;
+            if separator_count == separators {
+                input.truncate(start);
+                break; // stop reading at the last "---" we care about
+            }
+        }
+        let content = match line.strip_suffix('\n') {
+            Some(line) => line.strip_suffix('\r').unwrap_or(line),
+            None => line,
+        };
+        input.truncate(start + content.len());
+        input.push('\n');
+    }
+    Ok(input)
+}
+
+/// Splits a document into its frontmatter and the rest of the markdown.
+/// Mirrors the splitting done by `DokePipe::run_markdown`.
+pub fn split(input: &str) -> (Option<&str>, &str) {
+    let mut parts = input.splitn(3, "---");
+    let _first = parts.next();
+    if let Some(fm) = parts.next() {
+        let rest = parts.next().unwrap_or("").trim_start_matches(['\r', '\n']);
+        return (Some(fm.trim()), rest);
+    }
+    (None, input)
+}
+
+/// The markdown after the frontmatter, and the line of the document it starts at,
+/// the doke section starting the document.
+pub fn body(input: &str) -> (&str, usize) {
+    let (_, body) = split(input);
+    let first_line = 1 + input[..input.len() - body.len()].matches('\n').count();
+    (body, first_line)
+}
+
+/// The frontmatter starts on the line after the document's first `---`.
+fn syntax_error(e: ScanError) -> FrontmatterError {
+    let marker = e.marker();
+    let span = SourceSpan::point(marker.line() + 1, marker.col() + 1);
+    FrontmatterError::Syntax(span, e.info().to_string())
+}
+
+/// Parses a frontmatter string into a normalized map.
+pub fn parse(fm: &str) -> Result<HashMap<String, GodotValue>, FrontmatterError> {
+    let docs = YamlLoader::load_from_str(fm).map_err(syntax_error)?;
+    let mut map = HashMap::new();
+    if let Some(Yaml::Hash(h)) = docs.into_iter().next() {
+        for (k, v) in h {
+            if let Yaml::String(s) = k {
+                map.insert(normalize_key(&s), yaml_to_godot(v));
+            }
+        }
+    }
+    Ok(map)
+}
+
+/// Reads the frontmatter of a whole document, empty if it has none.
+pub fn read(input: &str) -> Result<HashMap<String, GodotValue>, FrontmatterError> {
+    match split(input) {
+        (Some(fm), _) => parse(fm),
+        (None, _) => Ok(HashMap::new()),
+    }
+}
+
+/// Lets `f` edit the frontmatter of a document, and writes the document back
+/// so the pipe (and its templating) sees the edited values.
+/// Documents without frontmatter get one if `f` adds keys.
+pub fn rewrite(
+    input: &str,
+    f: impl FnOnce(&mut Hash) -> Result<(), FrontmatterError>,
+) -> Result<String, FrontmatterError> {
+    let (fm, rest) = split(input);
+    let mut hash = parse_hash(fm)?;
+    f(&mut hash)?;
+    if fm.is_none() && hash.is_empty() {
+        return Ok(input.to_string());
+    }
+    render(hash, rest)
+}
+
+/// Resolves the `extends:` chain of the document of `context`.
+///
+/// Each extended document's frontmatter is merged under the extending one,
+/// so the closest document wins. Paths are relative to the extending document
+/// or `res://` ones, `.md` is optional, and documents out of the project are refused.
+/// With `inherit_body`, the doke sections of the extended documents are appended
+/// after the document's own statements.
+/// `prepare` runs on each extended document as it is read, like it did on `input`,
+/// and documents over the context's `max_file_size` aren't read.
+pub fn resolve_extends(
+    input: &str,
+    context: &ParserContext,
+    inherit_body: bool,
+    prepare: &dyn Fn(String) -> Result<String, FrontmatterError>,
+) -> Result<String, FrontmatterError> {
+    let bases: Vec<String> = extends_chain(input, context, prepare)?
+        .into_iter()
+        .map(|(_, text)| text)
+        .collect();
+    if bases.is_empty() {
+        return Ok(input.to_string());
+    }
+
+    let mut merged = Hash::new();
+    for text in bases.iter().rev().map(String::as_str).chain([input]) {
+        for (k, v) in parse_hash(split(text).0)? {
+            if let Some(key) = k.as_str() {
+                let key = normalize_key(key);
+                merged.retain(|old, _| old.as_str().is_none_or(|o| normalize_key(o) != key));
+            }
+            merged.insert(k, v);
+        }
+    }
+    let mut body = split(input).1.to_string();
+    if inherit_body {
+        for text in &bases {
+            body.push('\n');
+            body.push_str(split(text).1);
+        }
+    }
+    render(merged, &body)
+}
+
+/// The documents `input` extends, closest first, like [`resolve_extends`] finds them.
+pub fn extended_documents(
+    input: &str,
+    context: &ParserContext,
+    prepare: &dyn Fn(String) -> Result<String, FrontmatterError>,
+) -> Result<Vec<PathBuf>, FrontmatterError> {
+    Ok(extends_chain(input, context, prepare)?
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect())
+}
+
+/// The path and prepared doke section of each extended document, closest first.
+fn extends_chain(
+    input: &str,
+    context: &ParserContext,
+    prepare: &dyn Fn(String) -> Result<String, FrontmatterError>,
+) -> Result<Vec<(PathBuf, String)>, FrontmatterError> {
+    let mut bases = Vec::new();
+    let Some(mut target) = extends_target(input)? else {
+        return Ok(bases);
+    };
+    let mut visited = vec![canonical(context.document())];
+    let mut context = context.clone();
+    loop {
+        if Path::new(&target).extension().is_none() {
+            target.push_str(".md");
+        }
+        let base_path = context.file_to_read(&target)?;
+        if visited.contains(&base_path) {
+            visited.push(base_path);
+            let chain: Vec<String> = visited.iter().map(|p| p.display().to_string()).collect();
+            return Err(FrontmatterError::ExtendsCycle(chain.join(" -> ")));
+        }
+        visited.push(base_path.clone());
+        let text = read_doke_section(&base_path).map_err(FrontmatterError::Extends)?;
+        let text = prepare(text)?;
+        let next = extends_target(&text)?;
+        context = context.with_document(&base_path);
+        bases.push((base_path, text));
+        match next {
+            Some(next) => target = next,
+            None => break,
+        }
+    }
+    Ok(bases)
+}
+
+/// The `extends:` value of a document, if it has one.
+pub fn extends_target(input: &str) -> Result<Option<String>, FrontmatterError> {
+    let hash = parse_hash(split(input).0)?;
+    Ok(hash
+        .iter()
+        .find_map(|(k, v)| match (k.as_str(), v.as_str()) {
+            (Some(k), Some(v)) if normalize_key(k) == EXTENDS_KEY => Some(v.to_string()),
+            _ => None,
+        }))
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn parse_hash(fm: Option<&str>) -> Result<Hash, FrontmatterError> {
+    let Some(fm) = fm else {
+        return Ok(Hash::new());
+    };
+    let docs = YamlLoader::load_from_str(fm).map_err(syntax_error)?;
+    match docs.into_iter().next() {
+        Some(Yaml::Hash(h)) => Ok(h),
+        _ => Ok(Hash::new()),
+    }
+}
+
+/// Writes a frontmatter and a body back into a document.
+fn render(hash: Hash, body: &str) -> Result<String, FrontmatterError> {
+    let mut out = String::new();
+    YamlEmitter::new(&mut out)
+        .dump(&Yaml::Hash(hash))
+        .map_err(|e| FrontmatterError::Yaml(format!("{:?}", e)))?;
+    out.push_str("\n---\n");
+    out.push_str(body);
+    Ok(out)
+}
+
+/// Normalize frontmatter keys: lowercase + spaces → _
+pub fn normalize_key(key: &str) -> String {
+    key.trim().to_lowercase().replace(' ', "_")
+}
+
+/// Convert yaml_rust2::Yaml → GodotValue
+pub fn yaml_to_godot(y: Yaml) -> GodotValue {
+    match y {
+        Yaml::String(s) => GodotValue::String(s),
+        Yaml::Integer(i) => GodotValue::Int(i),
+        Yaml::Real(f) => GodotValue::Float(f.parse().unwrap_or(0.0)),
+        Yaml::Boolean(b) => GodotValue::Bool(b),
+        Yaml::Array(a) => GodotValue::Array(a.into_iter().map(yaml_to_godot).collect()),
+        Yaml::Hash(h) => {
+            let mut map = HashMap::new();
+            for (k, v) in h {
+                if let Yaml::String(s) = k {
+                    map.insert(normalize_key(&s), yaml_to_godot(v));
+                }
+            }
+            GodotValue::Dict(map)
+        }
+        _ => GodotValue::Nil,
+    }
+}
@@ -0,0 +1,103 @@
+// frontmatter_resources.rs
+// Frontmatter lists of maps (`drops: [{item: ..., chance: 0.1}]`) built as
+// sub-resources of the document's resource, instead of plain Dictionaries.
+
+use std::collections::HashMap;
+
+use doke::GodotValue;
+use yaml_rust2::Yaml;
+
+use crate::config::ConfigError;
+use crate::frontmatter::normalize_key;
+
+/// `frontmatter_resources:` frontmatter key -> how to build its elements.
+#[derive(Debug, Clone, Default)]
+pub struct FrontmatterResources {
+    lists: Vec<ResourceList>,
+}
+
+#[derive(Debug, Clone)]
+struct ResourceList {
+    key: String,
+    /// `type:` class of every element
+    type_name: String,
+    /// `field:` field of the document's resource the list goes in, the key by default
+    field: String,
+    /// `map:` element key -> property, for the keys that aren't named like the property
+    map: HashMap<String, String>,
+}
+
+impl FrontmatterResources {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let mut resources = Self::default();
+        let Some(hash) = y.as_hash() else {
+            return Ok(resources);
+        };
+        for (key, list) in hash {
+            let key = key.as_str().ok_or_else(|| {
+                ConfigError::Invalid("'frontmatter_resources' keys must be strings".into())
+            })?;
+            let invalid =
+                |e: &str| ConfigError::Invalid(format!("frontmatter_resources.{} : {}", key, e));
+            let type_name = list["type"]
+                .as_str()
+                .ok_or_else(|| invalid("needs the 'type' of its elements"))?;
+            let mut map = HashMap::new();
+            if let Some(entries) = list["map"].as_hash() {
+                for (k, v) in entries {
+                    let (Some(k), Some(v)) = (k.as_str(), v.as_str()) else {
+                        return Err(invalid("'map' entries must be `key: property`"));
+                    };
+                    map.insert(normalize_key(k), v.to_string());
+                }
+            }
+            resources.lists.push(ResourceList {
+                key: key.to_string(),
+                type_name: type_name.to_string(),
+                field: list["field"].as_str().unwrap_or(key).to_string(),
+                map,
+            });
+        }
+        Ok(resources)
+    }
+
+    /// Sets the configured frontmatter lists on the document's resource, one sub-resource per map.
+    /// Lists the document doesn't have are left alone.
+    pub fn apply(&self, value: &mut GodotValue, frontmatter: &HashMap<String, GodotValue>) {
+        let GodotValue::Resource { fields, .. } = value else {
+            return;
+        };
+        for list in &self.lists {
+            let key = normalize_key(&list.key);
+            let Some(GodotValue::Array(items)) = frontmatter
+                .iter()
+                .find(|(k, _)| normalize_key(k) == key)
+                .map(|(_, v)| v)
+            else {
+                continue;
+            };
+            let resources = items.iter().map(|item| list.build(item)).collect();
+            fields.insert(list.field.clone(), GodotValue::Array(resources));
+        }
+    }
+}
+
+impl ResourceList {
+    /// Maps become resources, anything else is kept as is for the property checks to report.
+    fn build(&self, item: &GodotValue) -> GodotValue {
+        let GodotValue::Dict(map) = item else {
+            return item.clone();
+        };
+        GodotValue::Resource {
+            type_name: self.type_name.clone(),
+            abstract_type_name: self.type_name.clone(),
+            fields: map
+                .iter()
+                .map(|(k, v)| {
+                    let property = self.map.get(&normalize_key(k)).cloned();
+                    (property.unwrap_or_else(|| k.clone()), v.clone())
+                })
+                .collect(),
+        }
+    }
+}
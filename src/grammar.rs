@@ -0,0 +1,353 @@
+// grammar.rs
+// The sentence patterns of a file type, reloaded from its dokedef files to
+// explain to grammar authors why a statement didn't match any of them.
+
+use std::fs;
+use std::sync::{LazyLock, OnceLock};
+
+use doke::GodotValue;
+use doke::parsers::SentenceParser;
+use regex::Regex;
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::config::{ConfigError, ImportConfig};
+use crate::file_error::ErrorContext;
+
+/// One sentence pattern, with the type of the rule it was loaded for.
+#[derive(Debug)]
+pub struct Pattern {
+    pub target: String,
+    pub section: String,
+    pub pattern: String,
+    /// The name and type of each parameter of the pattern, in order
+    pub parameters: Vec<(String, String)>,
+    /// What a statement matching it makes
+    pub returns: Returns,
+    segments: Vec<Segment>,
+    full: Regex,
+    /// The regexes of the pattern's growing prefixes, compiled on the first explanation
+    prefixes: OnceLock<Vec<Regex>>,
+    /// `full` ignoring case, compiled on first use
+    caseless: OnceLock<Option<Regex>>,
+}
+
+/// A `{parameter}` or `{parameter:type}` of a pattern
+pub static PARAM_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{([^}:]+)(?::([^}]+))?\}").expect("valid regex"));
+
+/// The right hand side of a rule, as doke reads it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Returns {
+    /// A resource of that type, its fields set from the parameters
+    Type(String),
+    /// `l"text"`, a number or a bool
+    Literal(GodotValue),
+    /// `f"text with {parameters}"`
+    Format(String),
+}
+
+impl Returns {
+    /// Mirrors doke's reading of right hand sides, which it doesn't expose.
+    fn from_yaml(y: &Yaml, section: &str) -> Self {
+        let text = match y {
+            Yaml::Integer(i) => return Self::Literal(GodotValue::Int(*i)),
+            Yaml::Real(r) => return Self::Literal(GodotValue::Float(r.parse().unwrap_or(0.0))),
+            Yaml::Boolean(b) => return Self::Literal(GodotValue::Bool(*b)),
+            Yaml::String(s) => s.trim(),
+            _ => return Self::Type(section.to_string()),
+        };
+        if let Some(inner) = text.strip_prefix("l\"").and_then(|t| t.strip_suffix('"')) {
+            return Self::Literal(GodotValue::String(inner.to_string()));
+        }
+        if let Some(inner) = text.strip_prefix("f\"").and_then(|t| t.strip_suffix('"')) {
+            return Self::Format(inner.to_string());
+        }
+        if let Ok(i) = text.parse() {
+            return Self::Literal(GodotValue::Int(i));
+        }
+        if let Ok(f) = text.parse() {
+            return Self::Literal(GodotValue::Float(f));
+        }
+        match text.to_lowercase().as_str() {
+            "true" | "yes" | "1" => Self::Literal(GodotValue::Bool(true)),
+            "false" | "no" | "0" => Self::Literal(GodotValue::Bool(false)),
+            _ => Self::Type(text.to_string()),
+        }
+    }
+}
+
+/// The right hand sides of a dokedef file's rules, in the order doke makes its phrases.
+fn rule_returns(text: &str) -> Vec<Returns> {
+    let mut returns = Vec::new();
+    for doc in YamlLoader::load_from_str(text).unwrap_or_default() {
+        let Yaml::Hash(sections) = doc else {
+            continue;
+        };
+        for (section, items) in &sections {
+            let Some(section) = section.as_str() else {
+                continue;
+            };
+            for item in items.as_vec().into_iter().flatten() {
+                match item {
+                    Yaml::String(_) => returns.push(Returns::Type(section.to_string())),
+                    Yaml::Hash(map) => {
+                        returns.extend(map.values().map(|rhs| Returns::from_yaml(rhs, section)))
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    returns
+}
+
+/// A literal run of text or a `{parameter}` of a pattern, and the regex doke matches it with.
+#[derive(Debug)]
+struct Segment {
+    description: String,
+    regex: String,
+}
+
+/// How far a statement got into a pattern.
+pub struct Explanation<'a> {
+    pub pattern: &'a Pattern,
+    pub matched: bool,
+    /// Bytes of the statement matched before diverging
+    pub matched_len: usize,
+    /// What the pattern expected where the statement diverged, None if it matched
+    pub expected: Option<&'a str>,
+}
+
+/// Loads every pattern of the config's rules, like doke's TypedSentencesParser does.
+pub fn load_patterns(config: &ImportConfig) -> Result<Vec<Pattern>, ConfigError> {
+    let mut patterns = Vec::new();
+    for (target, parser_glob) in &config.rules {
+        let full = config
+            .base_dir
+            .join(parser_glob)
+            .to_string_lossy()
+            .into_owned();
+        let paths = glob::glob(&full).map_err(|e| ConfigError::Pattern(full.clone(), e))?;
+        for path in paths.flatten() {
+            if !path.to_string_lossy().ends_with(".dokedef.yaml") {
+                continue;
+            }
+            let text = fs::read_to_string(&path).in_file(&path)?;
+            let parser = SentenceParser::from_yaml(target.clone(), &text)
+                .map_err(|e| ConfigError::Invalid(format!("{} : {}", path.display(), e)))?;
+            let returns = rule_returns(&text);
+            for (phrase, returns) in parser.phrases.into_iter().zip(returns) {
+                patterns.push(Pattern {
+                    target: target.clone(),
+                    section: phrase.section,
+                    segments: segments(&phrase.pattern),
+                    pattern: phrase.pattern,
+                    parameters: phrase
+                        .parameters
+                        .into_iter()
+                        .map(|p| (p.name, p.param_type))
+                        .collect(),
+                    returns,
+                    full: phrase.regex,
+                    prefixes: OnceLock::new(),
+                    caseless: OnceLock::new(),
+                });
+            }
+        }
+    }
+    Ok(patterns)
+}
+
+/// Splits a pattern the way doke builds its regex : literals with flexible
+/// whitespace, and one capture per parameter depending on its type.
+fn segments(pattern: &str) -> Vec<Segment> {
+    let mut out = Vec::new();
+    let mut last_end = 0;
+    // One segment per word, so the divergence is pinned to the word that differs
+    let literal = |out: &mut Vec<Segment>, text: &str| {
+        let mut regex = String::new();
+        let mut word = String::new();
+        let mut in_space = false;
+        for ch in text.chars() {
+            if !ch.is_whitespace() {
+                regex.push_str(&regex::escape(ch.encode_utf8(&mut [0; 4])));
+                word.push(ch);
+            } else if !in_space {
+                if !word.is_empty() {
+                    out.push(Segment {
+                        description: format!("\"{}\"", std::mem::take(&mut word)),
+                        regex: std::mem::take(&mut regex),
+                    });
+                }
+                regex.push_str(r"\s+");
+            }
+            in_space = ch.is_whitespace();
+        }
+        match out.last_mut() {
+            // Trailing whitespace goes with the last word
+            Some(last) if word.is_empty() => last.regex.push_str(&regex),
+            _ => out.push(Segment {
+                description: format!("\"{}\"", word),
+                regex,
+            }),
+        }
+    };
+    for cap in PARAM_RE.captures_iter(pattern) {
+        let m = cap.get(0).expect("whole match");
+        if m.start() > last_end {
+            literal(&mut out, &pattern[last_end..m.start()]);
+        }
+        let mut name = cap[1].trim().to_string();
+        let ty = cap
+            .get(2)
+            .map(|t| t.as_str().trim().to_string())
+            .unwrap_or_else(|| "string".into());
+        let optional = name.ends_with(":?");
+        if optional {
+            name.truncate(name.len() - 2);
+        }
+        let group = parameter_regex(&ty);
+        out.push(Segment {
+            description: format!("{{{}}} ({})", name, ty),
+            regex: if optional {
+                format!(r"(?:\s+{})?", group)
+            } else {
+                format!("(?:{})", group)
+            },
+        });
+        last_end = m.end();
+    }
+    if last_end < pattern.len() {
+        literal(&mut out, &pattern[last_end..]);
+    }
+    out
+}
+
+/// The pattern closest to a statement that matches none, if one is close enough to
+/// be a likely typo : at most a third of its text differs.
+pub fn closest<'a>(patterns: &'a [Pattern], statement: &str) -> Option<&'a Pattern> {
+    let statement = clean_statement(statement);
+    patterns
+        .iter()
+        .map(|p| (p.distance(statement), p))
+        .filter(|(distance, p)| *distance <= (p.literal_len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, p)| p)
+}
+
+/// What doke matches a parameter of type `ty` with.
+pub fn parameter_regex(ty: &str) -> &'static str {
+    match ty.to_lowercase().as_str() {
+        "int" => r"[-+]?(?:0[bB][01]+|0[oO][0-7]+|0[xX][0-9a-fA-F]+|\d+)",
+        "float" => r"[-+]?(?:\d+\.\d*|\.\d+|\d+)(?:[eE][-+]?\d+)?",
+        "bool" => r"(?:true|false|yes|no|1|0)",
+        _ => r".+?",
+    }
+}
+
+/// Cleans a statement like doke does before matching it.
+pub fn clean_statement(line: &str) -> &str {
+    line.trim().trim_end_matches(|c| ".:".contains(c))
+}
+
+/// Lowercase, with single spaces between words. Spaces next to parameters go, which
+/// stand for them anyway.
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl Pattern {
+    /// `^` and the first segment, then the first two, and so on. A prefix
+    /// that doesn't compile ends the list, as nothing can match past it.
+    fn prefixes(&self) -> &[Regex] {
+        self.prefixes.get_or_init(|| {
+            let mut prefix = String::from("^");
+            self.segments
+                .iter()
+                .map_while(|segment| {
+                    prefix.push_str(&segment.regex);
+                    Regex::new(&prefix).ok()
+                })
+                .collect()
+        })
+    }
+
+    /// Whether a statement matches the pattern as doke matches it.
+    pub fn is_match(&self, statement: &str) -> bool {
+        self.full.is_match(statement)
+    }
+
+    /// The pattern's regex ignoring case, a capture group per parameter.
+    pub fn caseless(&self) -> Option<&Regex> {
+        self.caseless
+            .get_or_init(|| Regex::new(&format!("(?i){}", self.full.as_str())).ok())
+            .as_ref()
+    }
+
+    /// Characters of the pattern out of its parameters.
+    pub fn literal_len(&self) -> usize {
+        PARAM_RE.replace_all(&self.pattern, "").chars().count()
+    }
+
+    /// Edits turning the statement into a match : the Levenshtein distance to the pattern,
+    /// its parameters standing for any text, case and runs of whitespace ignored.
+    pub fn distance(&self, statement: &str) -> usize {
+        // None for a parameter
+        let mut pattern: Vec<Option<char>> = Vec::new();
+        let mut last = 0;
+        for m in PARAM_RE.find_iter(&self.pattern) {
+            pattern.extend(normalize(&self.pattern[last..m.start()]).chars().map(Some));
+            pattern.push(None);
+            last = m.end();
+        }
+        pattern.extend(normalize(&self.pattern[last..]).chars().map(Some));
+        let statement: Vec<char> = normalize(statement).chars().collect();
+        // Distances from the previous row of the pattern to each prefix of the statement
+        let mut row: Vec<usize> = (0..=statement.len()).collect();
+        for token in &pattern {
+            let mut next = vec![row[0] + usize::from(token.is_some()); statement.len() + 1];
+            for (j, c) in statement.iter().enumerate() {
+                next[j + 1] = match token {
+                    // Takes the character, or leaves it to what comes before
+                    None => row[j + 1].min(next[j]),
+                    Some(t) => (row[j] + usize::from(t != c))
+                        .min(row[j + 1] + 1)
+                        .min(next[j] + 1),
+                };
+            }
+            row = next;
+        }
+        row[statement.len()]
+    }
+
+    /// Matches growing prefixes of the pattern to find where the statement diverges.
+    pub fn explain(&self, statement: &str) -> Explanation<'_> {
+        if self.full.is_match(statement) {
+            return Explanation {
+                pattern: self,
+                matched: true,
+                matched_len: statement.len(),
+                expected: None,
+            };
+        }
+        let mut matched_len = 0;
+        let mut expected = self.segments.first().map(|s| s.description.as_str());
+        for (i, prefix) in self.prefixes().iter().enumerate() {
+            let Some(m) = prefix.find(statement) else {
+                break;
+            };
+            matched_len = m.end();
+            expected = self.segments.get(i + 1).map(|s| s.description.as_str());
+        }
+        Explanation {
+            pattern: self,
+            matched: false,
+            matched_len,
+            // Every segment matched : the statement goes on after the pattern's end
+            expected: Some(expected.unwrap_or("end of sentence")),
+        }
+    }
+}
@@ -0,0 +1,513 @@
+// graph.rs
+// The link graph of a vault (document -> documents it links to), built from
+// the index, with a few metrics to keep content structure maintainable and
+// config-driven limits reported as diagnostics.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use yaml_rust2::Yaml;
+
+use crate::config::ConfigError;
+use crate::index::{VaultIndex, document_name};
+
+/// Directed graph of resolved links between indexed documents.
+#[derive(Debug, Default)]
+pub struct LinkGraph {
+    pub nodes: Vec<PathBuf>,
+    /// edges[i] = indices of the documents node i links to
+    pub edges: Vec<Vec<usize>>,
+    /// extends[i] = index of the document node i extends
+    pub extends: Vec<Option<usize>>,
+    /// Links pointing to documents that aren't in the vault : (from, link key)
+    pub unresolved: Vec<(PathBuf, String)>,
+}
+
+impl LinkGraph {
+    pub fn from_index(index: &VaultIndex) -> Self {
+        let mut nodes: Vec<PathBuf> = index.documents().map(|(p, _)| p.clone()).collect();
+        nodes.sort();
+        let ids: HashMap<&PathBuf, usize> = nodes.iter().enumerate().map(|(i, p)| (p, i)).collect();
+
+        let mut edges = vec![Vec::new(); nodes.len()];
+        let mut extends = vec![None; nodes.len()];
+        let mut unresolved = Vec::new();
+        for (i, path) in nodes.iter().enumerate() {
+            let Some(doc) = index.get(path) else { continue };
+            let mut seen = HashSet::new();
+            if let Some(base) = &doc.extends {
+                extends[i] = index.resolve_link(base).and_then(|t| ids.get(t)).copied();
+            }
+            for link in doc.links.iter().chain(&doc.extends) {
+                match index.resolve_link(link).and_then(|target| ids.get(target)) {
+                    Some(&j) => {
+                        if seen.insert(j) {
+                            edges[i].push(j);
+                        }
+                    }
+                    None => unresolved.push((path.clone(), link.clone())),
+                }
+            }
+        }
+        Self {
+            nodes,
+            edges,
+            extends,
+            unresolved,
+        }
+    }
+
+    /// Weakly connected components, largest first.
+    pub fn clusters(&self) -> Vec<Vec<PathBuf>> {
+        let mut undirected = vec![Vec::new(); self.nodes.len()];
+        for (i, targets) in self.edges.iter().enumerate() {
+            for &j in targets {
+                undirected[i].push(j);
+                undirected[j].push(i);
+            }
+        }
+        let mut seen = vec![false; self.nodes.len()];
+        let mut clusters = Vec::new();
+        for start in 0..self.nodes.len() {
+            if seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let mut stack = vec![start];
+            let mut cluster = Vec::new();
+            while let Some(n) = stack.pop() {
+                cluster.push(self.nodes[n].clone());
+                for &m in &undirected[n] {
+                    if !seen[m] {
+                        seen[m] = true;
+                        stack.push(m);
+                    }
+                }
+            }
+            cluster.sort();
+            clusters.push(cluster);
+        }
+        clusters.sort_by_key(|c| std::cmp::Reverse(c.len()));
+        clusters
+    }
+
+    /// Documents that can't be reached by following links from any of `roots`.
+    pub fn unreachable_from(&self, roots: &[PathBuf]) -> Vec<PathBuf> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut stack: Vec<usize> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| roots.iter().any(|r| p.ends_with(r)))
+            .map(|(i, _)| i)
+            .collect();
+        for &i in &stack {
+            seen[i] = true;
+        }
+        while let Some(n) = stack.pop() {
+            for &m in &self.edges[n] {
+                if !seen[m] {
+                    seen[m] = true;
+                    stack.push(m);
+                }
+            }
+        }
+        self.nodes
+            .iter()
+            .zip(seen)
+            .filter(|(_, s)| !s)
+            .map(|(p, _)| p.clone())
+            .collect()
+    }
+
+    /// Length of the longest chain of links starting at each document.
+    /// Links closing a cycle are not followed.
+    pub fn depths(&self) -> Vec<usize> {
+        fn visit(
+            graph: &LinkGraph,
+            n: usize,
+            memo: &mut Vec<Option<usize>>,
+            on_stack: &mut Vec<bool>,
+        ) -> usize {
+            if let Some(d) = memo[n] {
+                return d;
+            }
+            on_stack[n] = true;
+            let mut depth = 0;
+            for &m in &graph.edges[n] {
+                if !on_stack[m] {
+                    depth = depth.max(1 + visit(graph, m, memo, on_stack));
+                }
+            }
+            on_stack[n] = false;
+            memo[n] = Some(depth);
+            depth
+        }
+        let mut memo = vec![None; self.nodes.len()];
+        let mut on_stack = vec![false; self.nodes.len()];
+        (0..self.nodes.len())
+            .map(|n| visit(self, n, &mut memo, &mut on_stack))
+            .collect()
+    }
+
+    /// Length of the `extends:` chain of each document. Cycles stop the count.
+    pub fn extends_depths(&self) -> Vec<usize> {
+        (0..self.nodes.len())
+            .map(|start| {
+                let mut seen = HashSet::from([start]);
+                let mut current = start;
+                while let Some(base) = self.extends[current] {
+                    if !seen.insert(base) {
+                        break;
+                    }
+                    current = base;
+                }
+                seen.len() - 1
+            })
+            .collect()
+    }
+
+    /// Number of distinct documents linking to each document.
+    pub fn incoming(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.nodes.len()];
+        for targets in &self.edges {
+            for &j in targets {
+                counts[j] += 1;
+            }
+        }
+        counts
+    }
+
+    /// Documents sorted by how many documents link to them, most linked first.
+    pub fn most_linked(&self, count: usize) -> Vec<(PathBuf, usize)> {
+        let mut ranked: Vec<(PathBuf, usize)> = self
+            .nodes
+            .iter()
+            .cloned()
+            .zip(self.incoming())
+            .filter(|(_, c)| *c > 0)
+            .collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(count);
+        ranked
+    }
+}
+
+// -----------------------
+// Export to graph tools
+// -----------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+}
+
+impl GraphFormat {
+    /// From a format name, or the extension of the output path when the name is empty.
+    pub fn from_name(name: &str, path: &Path) -> Option<Self> {
+        let name = if name.is_empty() {
+            path.extension()?.to_string_lossy().into_owned()
+        } else {
+            name.to_string()
+        };
+        match name.to_lowercase().as_str() {
+            "dot" | "gv" => Some(Self::Dot),
+            "graphml" => Some(Self::GraphMl),
+            _ => None,
+        }
+    }
+}
+
+impl LinkGraph {
+    pub fn export(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::GraphMl => self.to_graphml(),
+        }
+    }
+
+    /// Graphviz DOT. Links to missing documents point to dashed nodes.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph doke {\n");
+        for (i, path) in self.nodes.iter().enumerate() {
+            out.push_str(&format!(
+                "  n{} [label=\"{}\", tooltip=\"{}\"];\n",
+                i,
+                dot_escape(&document_name(path)),
+                dot_escape(&path.to_string_lossy())
+            ));
+        }
+        for (i, targets) in self.edges.iter().enumerate() {
+            for j in targets {
+                out.push_str(&format!("  n{} -> n{};\n", i, j));
+            }
+        }
+        for (k, (link, sources)) in self.missing_targets().iter().enumerate() {
+            out.push_str(&format!(
+                "  m{} [label=\"{}\", style=dashed];\n",
+                k,
+                dot_escape(link)
+            ));
+            for i in sources {
+                out.push_str(&format!("  n{} -> m{} [style=dashed];\n", i, k));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// GraphML, with the document name and path as node data.
+    pub fn to_graphml(&self) -> String {
+        let mut out = [
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#,
+            r#"  <key id="name" for="node" attr.name="name" attr.type="string"/>"#,
+            r#"  <key id="path" for="node" attr.name="path" attr.type="string"/>"#,
+            r#"  <key id="missing" for="node" attr.name="missing" attr.type="boolean"/>"#,
+            r#"  <graph id="doke" edgedefault="directed">"#,
+            "",
+        ]
+        .join("\n");
+        for (i, path) in self.nodes.iter().enumerate() {
+            out.push_str(&format!(
+                "    <node id=\"n{}\"><data key=\"name\">{}</data><data key=\"path\">{}</data></node>\n",
+                i,
+                xml_escape(&document_name(path)),
+                xml_escape(&path.to_string_lossy())
+            ));
+        }
+        let missing = self.missing_targets();
+        for (k, (link, _)) in missing.iter().enumerate() {
+            out.push_str(&format!(
+                "    <node id=\"m{}\"><data key=\"name\">{}</data><data key=\"missing\">true</data></node>\n",
+                k,
+                xml_escape(link)
+            ));
+        }
+        let mut e = 0;
+        for (i, targets) in self.edges.iter().enumerate() {
+            for j in targets {
+                out.push_str(&format!(
+                    "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\"/>\n",
+                    e, i, j
+                ));
+                e += 1;
+            }
+        }
+        for (k, (_, sources)) in missing.iter().enumerate() {
+            for i in sources {
+                out.push_str(&format!(
+                    "    <edge id=\"e{}\" source=\"n{}\" target=\"m{}\"/>\n",
+                    e, i, k
+                ));
+                e += 1;
+            }
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    /// Unresolved link keys, with the indices of the documents using them.
+    fn missing_targets(&self) -> Vec<(String, Vec<usize>)> {
+        let ids: HashMap<&PathBuf, usize> =
+            self.nodes.iter().enumerate().map(|(i, p)| (p, i)).collect();
+        let mut missing: Vec<(String, Vec<usize>)> = Vec::new();
+        for (path, link) in &self.unresolved {
+            let Some(&i) = ids.get(path) else { continue };
+            match missing.iter_mut().find(|(l, _)| l == link) {
+                Some((_, sources)) => {
+                    if !sources.contains(&i) {
+                        sources.push(i)
+                    }
+                }
+                None => missing.push((link.clone(), vec![i])),
+            }
+        }
+        missing
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// -----------------------
+// Limits, from the `graph:` section of a config
+// -----------------------
+
+#[derive(Debug, Clone, Default)]
+pub struct GraphLimits {
+    /// Longest allowed chain of links
+    pub max_depth: Option<usize>,
+    /// Longest allowed `extends:` chain
+    pub max_extends_depth: Option<usize>,
+    /// Most links a single document may make
+    pub max_links: Option<usize>,
+    /// Most disconnected clusters the vault may have
+    pub max_clusters: Option<usize>,
+    /// Entry documents every other document should be reachable from
+    pub roots: Vec<PathBuf>,
+    /// Whether links to documents outside the vault are reported
+    pub deny_unresolved: bool,
+}
+
+impl GraphLimits {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let limit = |key: &str| -> Result<Option<usize>, ConfigError> {
+            match &y[key] {
+                Yaml::BadValue => Ok(None),
+                Yaml::Integer(i) if *i >= 0 => Ok(Some(*i as usize)),
+                other => Err(ConfigError::Invalid(format!(
+                    "graph.{} must be a positive integer, got {:?}",
+                    key, other
+                ))),
+            }
+        };
+        Ok(Self {
+            max_depth: limit("max_depth")?,
+            max_extends_depth: limit("max_extends_depth")?,
+            max_links: limit("max_links")?,
+            max_clusters: limit("max_clusters")?,
+            roots: y["roots"]
+                .as_vec()
+                .map(|v| {
+                    v.iter()
+                        .filter_map(Yaml::as_str)
+                        .map(PathBuf::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            deny_unresolved: y["deny_unresolved"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// Checks the graph against the limits, one message per violation.
+    pub fn check(&self, graph: &LinkGraph) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if let Some(max) = self.max_depth {
+            for (path, depth) in graph.nodes.iter().zip(graph.depths()) {
+                if depth > max {
+                    diagnostics.push(format!(
+                        "{} : dependency depth {} exceeds the maximum of {}",
+                        path.display(),
+                        depth,
+                        max
+                    ));
+                }
+            }
+        }
+        if let Some(max) = self.max_extends_depth {
+            for (path, depth) in graph.nodes.iter().zip(graph.extends_depths()) {
+                if depth > max {
+                    diagnostics.push(format!(
+                        "{} : extends chain of {} documents exceeds the maximum of {}",
+                        path.display(),
+                        depth,
+                        max
+                    ));
+                }
+            }
+        }
+        if let Some(max) = self.max_links {
+            for (path, targets) in graph.nodes.iter().zip(&graph.edges) {
+                if targets.len() > max {
+                    diagnostics.push(format!(
+                        "{} : links to {} documents, the maximum is {}",
+                        path.display(),
+                        targets.len(),
+                        max
+                    ));
+                }
+            }
+        }
+        if let Some(max) = self.max_clusters {
+            let clusters = graph.clusters();
+            if clusters.len() > max {
+                diagnostics.push(format!(
+                    "The vault has {} disconnected clusters, the maximum is {}",
+                    clusters.len(),
+                    max
+                ));
+            }
+        }
+        if !self.roots.is_empty() {
+            for path in graph.unreachable_from(&self.roots) {
+                diagnostics.push(format!(
+                    "{} : unreachable from the root documents",
+                    path.display()
+                ));
+            }
+        }
+        if self.deny_unresolved {
+            for (path, link) in &graph.unresolved {
+                diagnostics.push(format!(
+                    "{} : link [[{}]] doesn't point to any document",
+                    path.display(),
+                    link
+                ));
+            }
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_graph() -> LinkGraph {
+        let root = std::env::temp_dir().join(format!("doke_graph_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        for (name, content) in [
+            ("a.md", "---\n---\n- Needs [[B]] and [[C]]\n"),
+            (
+                "b.md",
+                "---\nextends: c\n---\n- Needs [[C]] and [[Missing]]\n",
+            ),
+            ("c.md", "---\n---\n- Alone\n"),
+        ] {
+            std::fs::write(root.join(name), content).unwrap();
+        }
+        let mut index = VaultIndex::new(&root);
+        assert!(index.scan().is_empty());
+        LinkGraph::from_index(&index)
+    }
+
+    #[test]
+    fn dot_has_an_edge_per_link() {
+        let dot = sample_graph().to_dot();
+        // Nodes are sorted by path : a is n0, b is n1, c is n2
+        for edge in [
+            "n0 -> n1;",
+            "n0 -> n2;",
+            "n1 -> n2;",
+            "n1 -> m0 [style=dashed];",
+        ] {
+            assert!(dot.contains(edge), "missing {} in\n{}", edge, dot);
+        }
+        assert_eq!(dot.matches(" -> ").count(), 4);
+    }
+
+    #[test]
+    fn graphml_has_an_edge_per_link() {
+        let graphml = sample_graph().to_graphml();
+        for edge in [
+            r#"source="n0" target="n1""#,
+            r#"source="n0" target="n2""#,
+            r#"source="n1" target="n2""#,
+            r#"source="n1" target="m0""#,
+        ] {
+            assert!(graphml.contains(edge), "missing {} in\n{}", edge, graphml);
+        }
+        assert_eq!(graphml.matches("<edge ").count(), 4);
+    }
+}
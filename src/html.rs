@@ -0,0 +1,145 @@
+// html.rs
+// A document as a standalone HTML page, so a dokedex can be published as a web
+// reference without a separate static-site toolchain.
+
+use doke::semantic::DokeNodeState;
+use doke::{DokeDocument, DokeNode};
+use markdown::Options;
+
+use crate::index::{self, WikiPiece};
+
+/// Where wiki links point when the file type doesn't say : a page next to this one.
+pub const DEFAULT_LINK_URL: &str = "{name}.html";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `template` with `{name}` replaced by the link key.
+fn link_url(template: &str, key: &str) -> String {
+    template.replace("{name}", key)
+}
+
+/// Escaped text with its `[[wiki links]]` as anchors.
+fn text_html(text: &str, link_template: &str) -> String {
+    index::wiki_pieces(text)
+        .into_iter()
+        .map(|piece| match piece {
+            WikiPiece::Text(t) => escape(t),
+            WikiPiece::Link { key, label } => format!(
+                "<a href=\"{}\">{}</a>",
+                escape(&link_url(link_template, &key)),
+                escape(label)
+            ),
+        })
+        .collect()
+}
+
+/// Markdown with its `[[wiki links]]` as markdown links, for the markdown renderer.
+/// Brackets in labels are escaped, and angle brackets in urls percent-encoded, so
+/// neither ends the link early.
+fn markdown_links(text: &str, link_template: &str) -> String {
+    index::wiki_pieces(text)
+        .into_iter()
+        .map(|piece| match piece {
+            WikiPiece::Text(t) => t.to_string(),
+            WikiPiece::Link { key, label } => {
+                let label = label
+                    .replace('\\', "\\\\")
+                    .replace('[', "\\[")
+                    .replace(']', "\\]");
+                let url = link_url(link_template, &key)
+                    .replace('<', "%3C")
+                    .replace('>', "%3E");
+                format!("[{}](<{}>)", label, url)
+            }
+        })
+        .collect()
+}
+
+/// Statements as nested lists, each item classed by its state (`doke-resolved`...).
+fn nodes_html(nodes: &[DokeNode], link_template: &str, out: &mut String) {
+    if nodes.is_empty() {
+        return;
+    }
+    out.push_str("<ul>\n");
+    for node in nodes {
+        let state = match node.state {
+            DokeNodeState::Unresolved => "unresolved",
+            DokeNodeState::Resolved(_) => "resolved",
+            DokeNodeState::Hypothesis(_) => "hypotheses",
+            DokeNodeState::Error(_) => "error",
+        };
+        out.push_str(&format!(
+            "<li class=\"doke-{}\">{}",
+            state,
+            text_html(node.statement.trim(), link_template)
+        ));
+        if !node.children.is_empty() {
+            out.push('\n');
+            nodes_html(&node.children, link_template, out);
+        }
+        out.push_str("</li>\n");
+    }
+    out.push_str("</ul>\n");
+}
+
+/// The page of a document : its frontmatter as a definition list, its statements,
+/// then its wiki part rendered from markdown. Links go to `link_template`, `{name}`
+/// being the link key of the document they point to.
+pub fn to_html(title: &str, doc: &DokeDocument, wiki: &str, link_template: &str) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!(
+        "<title>{}</title>\n</head>\n<body>\n",
+        escape(title)
+    ));
+    out.push_str(&format!("<h1>{}</h1>\n", escape(title)));
+    let mut frontmatter: Vec<_> = doc.frontmatter.iter().collect();
+    frontmatter.sort_by_key(|(k, _)| k.as_str());
+    if !frontmatter.is_empty() {
+        out.push_str("<dl class=\"doke-frontmatter\">\n");
+        for (key, value) in frontmatter {
+            out.push_str(&format!(
+                "<dt>{}</dt><dd>{}</dd>\n",
+                escape(key),
+                text_html(&value.to_string(), link_template)
+            ));
+        }
+        out.push_str("</dl>\n");
+    }
+    out.push_str("<section class=\"doke-statements\">\n");
+    nodes_html(&doc.nodes, link_template, &mut out);
+    out.push_str("</section>\n");
+    let wiki = markdown_links(wiki, link_template);
+    // Raw HTML of the wiki part is escaped, the GFM defaults
+    if let Ok(wiki) = markdown::to_html_with_options(&wiki, &Options::gfm()) {
+        out.push_str("<article class=\"doke-wiki\">\n");
+        out.push_str(&wiki);
+        out.push_str("</article>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_links_keep_brackets_inside() {
+        let text = "Made of [[ore<1>|iron] ore]] and [[gold]].";
+        let links = markdown_links(text, DEFAULT_LINK_URL);
+        assert_eq!(
+            links,
+            "Made of [iron\\] ore](<ore%3C1%3E.html>) and [gold](<gold.html>)."
+        );
+        assert_eq!(
+            markdown::to_html(&links),
+            "<p>Made of <a href=\"ore%3C1%3E.html\">iron] ore</a> and <a href=\"gold.html\">gold</a>.</p>"
+        );
+    }
+}
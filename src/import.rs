@@ -9,20 +9,37 @@
 // (ClassDb, ResourceLoader, ProjectSettings, ResourceSaver, Script, Object). You
 // may need to adapt small API surface names to your exact GDExtension crate.
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::rc::Rc;
 
 use doke::GodotValue;
 use doke::file_builder::BuilderError;
-use doke::semantic::{DokeErrors, DokeValidationError};
-use godot::classes::{ProjectSettings, ResourceLoader, Script};
+use doke::semantic::{DokeErrors, DokeNode, DokeNodeState, DokeValidationError};
+use godot::classes::{ProjectSettings, ResourceLoader, ResourceSaver, ResourceUid, Script};
 use godot::{classes::ClassDb, prelude::*};
 use thiserror::Error;
 
+use crate::computed::ComputedError;
+use crate::config::{ClassFilter, ConfigError, Limits};
+use crate::dispatch::DispatchError;
+use crate::fields::{FieldError, FieldKind, FieldTypes};
+use crate::file_error::FileError;
+use crate::frontmatter::FrontmatterError;
+use crate::middleware::MiddlewareError;
+use crate::parser_context::ContextError;
+use crate::safe_mode::{SafeMode, SafeModeError};
+use crate::span::SourceSpan;
+use crate::validation::ValidationError;
+
 pub type Result<T> = std::result::Result<T, ImportError>;
 #[derive(Debug, Error)]
 pub enum ImportError {
     #[error("couldn't instanciate a resource of type {0}")]
     ResInstanciationError(String),
+    #[error("resources of type {0} aren't allowed for this file type")]
+    ClassNotAllowed(String),
     #[error("conversion error : {0}")]
     ConvertError(#[from] ConvertError),
     #[error("Parse Errors : {0}")]
@@ -38,61 +55,528 @@ pub enum ImportError {
     #[error("Parsed value is not a resource : {0}")]
     NotAResource(GodotValue),
     #[error("Can't read file : {0}")]
-    CantReadFile(#[from] std::io::Error),
+    CantReadFile(#[from] FileError),
     #[error("Validation failed : {0}")]
     DokeValidationError(#[from] DokeValidationError),
+    #[error("Config Error : {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("Frontmatter Error : {0}")]
+    FrontmatterError(#[from] FrontmatterError),
+    #[error("Field Error : {0}")]
+    FieldError(#[from] FieldError),
+    #[error("Validation Error : {0}")]
+    ValidationError(#[from] ValidationError),
+    #[error("_apply_doke_frontmatter failed with error {0}")]
+    FrontmatterHookFailed(i64),
+    #[error("_apply_doke_frontmatter rejected {0}")]
+    FrontmatterHookRejected(String),
+    #[error("{0} has no property '{1}'")]
+    UnknownProperty(String, String),
+    #[error("property '{0}' is a {1}, can't set it to a {2}")]
+    TypeMismatch(String, String, String),
+    #[error("values nest deeper than the limit of {0} levels")]
+    TooDeep(usize),
+    #[error("more than the limit of {0} values in one document")]
+    TooManyElements(usize),
+    #[error("the document is {0} bytes, over the limit of {1}")]
+    FileTooLarge(u64, usize),
+    #[error("couldn't save {0} : {1:?}")]
+    SaveError(String, godot::global::Error),
+    #[error("{0} is already being imported")]
+    AlreadyImporting(String),
+    #[error("at {0} : {1}")]
+    AtPath(String, Box<ImportError>),
+    #[error("line {0} : {1}")]
+    AtStatement(SourceSpan, Box<ImportError>),
+    #[error("{0}")]
+    DispatchError(#[from] DispatchError),
+    #[error("{0}")]
+    ComputedError(#[from] ComputedError),
+    #[error("{0}")]
+    SafeModeError(#[from] SafeModeError),
+    #[error("{0}")]
+    MiddlewareError(#[from] MiddlewareError),
+    #[error("{0}")]
+    ContextError(#[from] ContextError),
 }
 
 // -----------------------
 // Helpers: Convert GodotValue -> Variant
 // !!! This recursively tries to make any Resource
 // -----------------------
-pub fn godot_value_to_variant(value: GodotValue) -> Result<Variant> {
-    match value {
-        GodotValue::Nil => Ok(Variant::nil()),
-        GodotValue::Bool(b) => Ok(Variant::from(b)),
-        GodotValue::Int(i) => Ok(Variant::from(i)),
-        GodotValue::Float(f) => Ok(Variant::from(f)),
-        GodotValue::String(s) => Ok(Variant::from(s)),
-        GodotValue::Array(arr) => {
-            let mut array: Array<Variant> = array![];
-            for v in arr {
-                let v_as_variant = godot_value_to_variant(v)?;
-                array.push(&v_as_variant);
+
+/// When `_apply_doke_frontmatter` is called, relative to setting the fields.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FrontmatterPhase {
+    Before,
+    #[default]
+    After,
+    /// Before and after, for resources that prepare state and then derive from the fields
+    Both,
+}
+
+impl FrontmatterPhase {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "before" => Some(Self::Before),
+            "after" => Some(Self::After),
+            "both" => Some(Self::Both),
+            _ => None,
+        }
+    }
+}
+
+/// How fields are set on the resources, for scripts that validate in setters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FieldApplication {
+    /// `Object::set`
+    #[default]
+    Set,
+    /// `set_<field>(value)` when the resource has such a method
+    Setters,
+    /// One `_apply_doke_fields(fields)` call when the resource has that method
+    Method,
+}
+
+impl FieldApplication {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "set" => Some(Self::Set),
+            "setters" => Some(Self::Setters),
+            "method" => Some(Self::Method),
+            _ => None,
+        }
+    }
+}
+
+/// How a file type's values become Godot objects, from its config.
+#[derive(Debug, Clone, Default)]
+pub struct Conversion {
+    pub fields: FieldTypes,
+    pub classes: ClassFilter,
+    pub limits: Limits,
+    /// `string_names:` fields whose strings and dictionary keys become StringNames
+    pub string_names: Vec<String>,
+    /// Shared nested resources, when the config sets `dedup: true`
+    pub resource_cache: Option<Rc<RefCell<ResourceCache>>>,
+    /// The file type the shared resources are kept under, each config builds its own
+    pub cache_scope: String,
+    /// `scripts:` type name -> script path or class_name to instantiate instead
+    pub scripts: HashMap<String, String>,
+    /// `frontmatter_phase:` when the frontmatter hook is called
+    pub frontmatter_phase: FrontmatterPhase,
+    /// `apply_fields:` how fields are set on the resources
+    pub apply_fields: FieldApplication,
+    pub safe_mode: Option<SafeMode>,
+    /// The statements of the imported document, to point errors at, see [`value_spans`]
+    pub spans: ValueSpans,
+}
+
+/// The spans of the statements resolved to resources, by hash of their content.
+pub type ValueSpans = HashMap<u64, SourceSpan>;
+
+/// Where the resources of a validated document come from, so conversion errors
+/// can point at the statement like the parser diagnostics do.
+pub fn value_spans(nodes: &[DokeNode], body: &str, first_line: usize) -> ValueSpans {
+    fn walk(node: &DokeNode, body: &str, first_line: usize, spans: &mut ValueSpans) {
+        if let DokeNodeState::Resolved(out) = &node.state {
+            let value = out.to_godot();
+            if matches!(value, GodotValue::Resource { .. }) {
+                let span = SourceSpan::of_range(body, node.span.start, node.span.end, first_line);
+                spans.entry(content_hash(&value)).or_insert(span);
             }
-            Ok(Variant::from(array))
         }
-        GodotValue::Dict(map) => {
-            let mut gd = Dictionary::new();
-            for (k, v) in map {
-                let v_as_variant = godot_value_to_variant(v)?;
-                gd.insert(k, v_as_variant);
+        for child in node.children.iter().chain(node.constituents.values()) {
+            walk(child, body, first_line, spans);
+        }
+    }
+    let mut spans = ValueSpans::new();
+    for node in nodes {
+        walk(node, body, first_line, &mut spans);
+    }
+    spans
+}
+
+/// Nested resources already built, by file type and hash of their content.
+/// Documents embedding the same nested resource get the same instance.
+pub type ResourceCache = HashMap<(String, u64), Gd<Resource>>;
+
+/// Hashes a value regardless of the order of its fields.
+pub(crate) fn content_hash(value: &GodotValue) -> u64 {
+    fn feed(value: &GodotValue, h: &mut DefaultHasher) {
+        std::mem::discriminant(value).hash(h);
+        match value {
+            GodotValue::Nil => {}
+            GodotValue::Bool(b) => b.hash(h),
+            GodotValue::Int(i) => i.hash(h),
+            GodotValue::Float(f) => f.to_bits().hash(h),
+            GodotValue::String(s) => s.hash(h),
+            GodotValue::Array(items) => {
+                items.len().hash(h);
+                items.iter().for_each(|v| feed(v, h));
+            }
+            GodotValue::Dict(map) | GodotValue::Resource { fields: map, .. } => {
+                if let GodotValue::Resource { type_name, .. } = value {
+                    type_name.hash(h);
+                }
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_by_key(|(k, _)| k.as_str());
+                entries.len().hash(h);
+                for (k, v) in entries {
+                    k.hash(h);
+                    feed(v, h);
+                }
             }
-            Ok(Variant::from(gd))
         }
-        GodotValue::Resource {
-            type_name,
-            fields,
-            abstract_type_name: _,
-        } => {
-            // Nested resources are instanced fresh (no resource_path lookup)
-            let mut res = instantiate_resource(&type_name)?;
-            for (k, v) in fields {
-                res.set(&StringName::from(k), &godot_value_to_variant(v)?);
+    }
+    let mut h = DefaultHasher::new();
+    feed(value, &mut h);
+    h.finish()
+}
+
+/// Converts the values of one document, counting them against the limits
+/// and keeping the path of the value being converted for error messages.
+pub struct Converter<'a> {
+    conversion: &'a Conversion,
+    elements: usize,
+    /// `root`, `.abilities`, `[2]`... down to the current value
+    path: Vec<String>,
+    /// The statement of the innermost resource being built, when known
+    statement: Option<SourceSpan>,
+}
+
+impl<'a> Converter<'a> {
+    /// `root` names the values in error messages, like `root` or `frontmatter`.
+    pub fn new(conversion: &'a Conversion, root: &str) -> Self {
+        Self {
+            conversion,
+            elements: 0,
+            path: vec![root.to_string()],
+            statement: None,
+        }
+    }
+
+    /// The statement a value was resolved from, when it's a resource of the document.
+    fn span_of(&self, value: &GodotValue) -> Option<SourceSpan> {
+        match value {
+            GodotValue::Resource { .. } if !self.conversion.spans.is_empty() => {
+                self.conversion.spans.get(&content_hash(value)).copied()
+            }
+            _ => None,
+        }
+    }
+
+    /// How many levels of arrays, dictionaries and resources the current value is in.
+    fn depth(&self) -> usize {
+        self.path.len() - 1
+    }
+
+    /// Counts the current value against the limits.
+    fn count(&mut self) -> Result<()> {
+        let limits = self.conversion.limits;
+        self.elements += 1;
+        if self.depth() > limits.max_depth {
+            return Err(ImportError::TooDeep(limits.max_depth));
+        }
+        if self.elements > limits.max_elements {
+            return Err(ImportError::TooManyElements(limits.max_elements));
+        }
+        Ok(())
+    }
+
+    /// The current path as written in `string_names:`, like `abilities.name` : no root, no indices.
+    fn field_path(&self) -> String {
+        self.path[1..]
+            .iter()
+            .filter(|segment| !segment.starts_with('['))
+            .map(|segment| segment.trim_start_matches('.'))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    fn wants_string_name(&self) -> bool {
+        !self.conversion.string_names.is_empty()
+            && self.conversion.string_names.contains(&self.field_path())
+    }
+
+    /// Adds the current path to an error, unless a nested value already did.
+    fn at_path(&self, e: ImportError) -> ImportError {
+        match e {
+            ImportError::AtPath(..) | ImportError::AtStatement(..) => e,
+            e => ImportError::AtPath(self.path.concat(), Box::new(e)),
+        }
+    }
+
+    /// Adds the path of a field of the current value to an error.
+    fn at_path_of(&mut self, field: &str, e: ImportError) -> ImportError {
+        self.path.push(format!(".{}", field));
+        let e = self.at_path(e);
+        self.path.pop();
+        e
+    }
+
+    /// Converts a value under `segment` of the path.
+    fn child(&mut self, segment: String, value: GodotValue) -> Result<Variant> {
+        self.path.push(segment);
+        let v = self.value(value);
+        self.path.pop();
+        v
+    }
+
+    pub fn value(&mut self, value: GodotValue) -> Result<Variant> {
+        self.convert(value).map_err(|e| self.at_path(e))
+    }
+
+    fn convert(&mut self, value: GodotValue) -> Result<Variant> {
+        self.count()?;
+        if self.wants_string_name() {
+            match value {
+                GodotValue::String(s) => return Ok(StringName::from(&s).to_variant()),
+                GodotValue::Dict(map) => {
+                    let mut gd = Dictionary::new();
+                    for (k, v) in map {
+                        let v_as_variant = self.child(format!(".{}", k), v)?;
+                        gd.set(StringName::from(&k), v_as_variant);
+                    }
+                    return Ok(Variant::from(gd));
+                }
+                _ => {}
+            }
+        }
+        match value {
+            GodotValue::Nil => Ok(Variant::nil()),
+            GodotValue::Bool(b) => Ok(Variant::from(b)),
+            GodotValue::Int(i) => Ok(Variant::from(i)),
+            GodotValue::Float(f) => Ok(Variant::from(f)),
+            GodotValue::String(s) => Ok(Variant::from(s)),
+            GodotValue::Array(arr) => {
+                let mut array: Array<Variant> = array![];
+                for (i, v) in arr.into_iter().enumerate() {
+                    let v_as_variant = self.child(format!("[{}]", i), v)?;
+                    array.push(&v_as_variant);
+                }
+                Ok(Variant::from(array))
+            }
+            GodotValue::Dict(map) => {
+                let mut gd = Dictionary::new();
+                for (k, v) in map {
+                    let v_as_variant = self.child(format!(".{}", k), v)?;
+                    gd.set(k, v_as_variant);
+                }
+                Ok(Variant::from(gd))
+            }
+            GodotValue::Resource { .. } => {
+                let Some(cache) = self.conversion.resource_cache.clone() else {
+                    return Ok(Variant::from(self.resource(value)?));
+                };
+                let key = (self.conversion.cache_scope.clone(), content_hash(&value));
+                if let Some(res) = cache.borrow().get(&key) {
+                    // Shared, but still a class this import may build
+                    if let GodotValue::Resource { type_name, .. } = &value {
+                        check_class(type_name, self.conversion)?;
+                    }
+                    return Ok(Variant::from(res.clone()));
+                }
+                let res = self.resource(value)?;
+                cache.borrow_mut().insert(key, res.clone());
+                Ok(Variant::from(res))
+            }
+        }
+    }
+
+    /// Builds a nested resource, always instanced fresh (no resource_path lookup).
+    fn resource(&mut self, value: GodotValue) -> Result<Gd<Resource>> {
+        let statement = self.span_of(&value).or(self.statement);
+        let outer = std::mem::replace(&mut self.statement, statement);
+        let res = self.build_resource(value);
+        self.statement = outer;
+        res
+    }
+
+    fn build_resource(&mut self, value: GodotValue) -> Result<Gd<Resource>> {
+        let GodotValue::Resource {
+            type_name, fields, ..
+        } = value
+        else {
+            return Err(ImportError::NotAResource(value));
+        };
+        let mut res = instantiate_resource(&type_name, self.conversion)?;
+        let values = fields
+            .into_iter()
+            .map(|(k, v)| {
+                let span = self.span_of(&v);
+                Ok((k.clone(), self.child(format!(".{}", k), v)?, span))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.set_fields(&mut res, values)?;
+        Ok(res)
+    }
+
+    /// Sets converted fields on a resource, the way the config's `apply_fields` says.
+    /// Each field comes with the statement it was resolved from, if known.
+    fn set_fields(
+        &mut self,
+        res: &mut Gd<Resource>,
+        values: Vec<(String, Variant, Option<SourceSpan>)>,
+    ) -> Result<()> {
+        let mode = self.conversion.apply_fields;
+        if mode == FieldApplication::Method && res.has_method(APPLY_DOKE_FIELDS_METHOD) {
+            let fields: Dictionary = values.into_iter().map(|(k, v, _)| (k, v)).collect();
+            res.call(APPLY_DOKE_FIELDS_METHOD, &[fields.to_variant()]);
+            return Ok(());
+        }
+        let properties = property_types(res);
+        for (k, v, span) in values {
+            let setter = format!("set_{}", k);
+            if mode == FieldApplication::Setters && res.has_method(&setter) {
+                res.call(&setter, &[v]);
+                continue;
             }
-            Ok(Variant::from(res))
+            set_property(res, &properties, &k, v).map_err(|e| {
+                let e = self.at_path_of(&k, e);
+                match span.or(self.statement) {
+                    Some(span) => ImportError::AtStatement(span, Box::new(e)),
+                    None => e,
+                }
+            })?;
         }
+        Ok(())
+    }
+
+    /// Converts a top-level field, with the type declared for it in the config if any.
+    pub fn field(&mut self, field: &str, value: GodotValue) -> Result<Variant> {
+        self.path.push(format!(".{}", field));
+        let v = match self.conversion.fields.get(field) {
+            Some(kind @ FieldKind::TypedArray(_)) => kind
+                .check_typed_array(field, &value)
+                .map_err(|e| self.at_path(e.into()))
+                .and_then(|_| self.value(value)),
+            Some(FieldKind::Resource(_)) if self.conversion.safe_mode.is_some() => {
+                Err(self.at_path(SafeModeError::PathLoading(value.to_string()).into()))
+            }
+            Some(kind) => self
+                .count()
+                .and_then(|_| Ok(kind.convert(field, &value)?))
+                .map_err(|e| self.at_path(e)),
+            None => self.value(value),
+        };
+        self.path.pop();
+        v
     }
 }
 
+/// Name -> type of the properties of a resource, script ones included.
+/// Properties typed as Variant are NIL.
+pub fn property_types(res: &Gd<Resource>) -> HashMap<String, VariantType> {
+    res.get_property_list()
+        .iter_shared()
+        .filter_map(|p| {
+            let name = p.get("name")?.try_to::<GString>().ok()?.to_string();
+            let ty = p.get("type")?.try_to::<i32>().ok()?;
+            Some((name, VariantType::try_from_ord(ty)?))
+        })
+        .collect()
+}
+
+/// Whether Godot converts a value of type `from` when setting a property of type `to`,
+/// as `Variant::can_convert_strict` does it.
+fn converts(from: VariantType, to: VariantType) -> bool {
+    use VariantType as T;
+    const PACKED: [VariantType; 10] = [
+        T::PACKED_BYTE_ARRAY,
+        T::PACKED_INT32_ARRAY,
+        T::PACKED_INT64_ARRAY,
+        T::PACKED_FLOAT32_ARRAY,
+        T::PACKED_FLOAT64_ARRAY,
+        T::PACKED_STRING_ARRAY,
+        T::PACKED_VECTOR2_ARRAY,
+        T::PACKED_VECTOR3_ARRAY,
+        T::PACKED_COLOR_ARRAY,
+        T::PACKED_VECTOR4_ARRAY,
+    ];
+    if from == to || to == T::NIL {
+        return true;
+    }
+    let sources: &[VariantType] = match to {
+        T::BOOL => &[T::INT, T::FLOAT],
+        T::INT => &[T::BOOL, T::FLOAT],
+        T::FLOAT => &[T::BOOL, T::INT],
+        T::STRING => &[T::STRING_NAME, T::NODE_PATH],
+        T::STRING_NAME | T::NODE_PATH => &[T::STRING],
+        // Named colors and hex codes, and 0xRRGGBBAA ints
+        T::COLOR => &[T::STRING, T::INT],
+        T::VECTOR2 => &[T::VECTOR2I],
+        T::VECTOR2I => &[T::VECTOR2],
+        T::VECTOR3 => &[T::VECTOR3I],
+        T::VECTOR3I => &[T::VECTOR3],
+        T::VECTOR4 => &[T::VECTOR4I],
+        T::VECTOR4I => &[T::VECTOR4],
+        T::RECT2 => &[T::RECT2I],
+        T::RECT2I => &[T::RECT2],
+        T::TRANSFORM2D => &[T::TRANSFORM3D],
+        T::TRANSFORM3D => &[T::TRANSFORM2D, T::QUATERNION, T::BASIS, T::PROJECTION],
+        T::QUATERNION => &[T::BASIS],
+        T::BASIS => &[T::QUATERNION],
+        T::PROJECTION => &[T::TRANSFORM3D],
+        T::RID => &[T::OBJECT],
+        T::OBJECT => &[T::NIL],
+        T::ARRAY => &PACKED,
+        to if PACKED.contains(&to) => &[T::ARRAY],
+        _ => &[],
+    };
+    sources.contains(&from)
+}
+
+/// Sets a field, checking first the resource has such a property and that it takes the value,
+/// as Godot would otherwise set nothing without a word.
+fn set_property(
+    res: &mut Gd<Resource>,
+    properties: &HashMap<String, VariantType>,
+    field: &str,
+    value: Variant,
+) -> Result<()> {
+    let Some(expected) = properties.get(field) else {
+        return Err(ImportError::UnknownProperty(
+            res.get_class().to_string(),
+            field.to_string(),
+        ));
+    };
+    if !converts(value.get_type(), *expected) {
+        return Err(ImportError::TypeMismatch(
+            field.to_string(),
+            expected.as_str().to_string(),
+            value.get_type().as_str().to_string(),
+        ));
+    }
+    let field = StringName::from(field);
+    let value = into_typed_array(res, &field, value);
+    res.set(&field, &value);
+    Ok(())
+}
+
+/// Godot refuses an untyped Array for an `Array[Type]` property (and the same for Dictionaries) :
+/// the values are assigned into the typed array the resource already holds instead.
+fn into_typed_array(res: &Gd<Resource>, field: &StringName, value: Variant) -> Variant {
+    let current = res.get(field);
+    let ty = current.get_type();
+    if (ty == VariantType::ARRAY || ty == VariantType::DICTIONARY)
+        && value.get_type() == ty
+        && current.call("is_typed", &[]).booleanize()
+    {
+        current.call("assign", &[value]);
+        return current;
+    }
+    value
+}
+
 // -----------------------
 // Public import function
 // -----------------------
-#[allow(dead_code)]
 pub fn import_top_level_resource(
     value: GodotValue,
     frontmatter: HashMap<String, GodotValue>,
     save_path: Option<String>,
+    conversion: &Conversion,
 ) -> Result<Gd<Resource>> {
     if !matches!(
         value,
@@ -104,34 +588,55 @@ pub fn import_top_level_resource(
     ) {
         return Err(ImportError::NotAResource(value));
     }
-    let resource = build_top_level_resource(value, save_path, &frontmatter)?;
+    let resource = build_top_level_resource(value, save_path, &frontmatter, conversion)?;
     Ok(resource)
 }
 
+/// Whether the `classes:` filter and the safe mode let a document build `type_name`.
+fn check_class(type_name: &str, conversion: &Conversion) -> Result<()> {
+    if !conversion.classes.allows(type_name) {
+        return Err(ImportError::ClassNotAllowed(type_name.to_string()));
+    }
+    if let Some(safe) = &conversion.safe_mode {
+        safe.check_class(type_name)?;
+    }
+    Ok(())
+}
+
 // -----------------------
 // Instantiate resource (built-in first, then class_name fallback)
 // -----------------------
-fn instantiate_resource(type_name: &str) -> Result<Gd<Resource>> {
+fn instantiate_resource(type_name: &str, conversion: &Conversion) -> Result<Gd<Resource>> {
+    check_class(type_name, conversion)?;
+    // 0) A script or another class the config sets for this type
+    let (type_name, mut script_path) = match conversion.scripts.get(type_name) {
+        Some(path) if path.ends_with(".gd") || path.contains("://") => (type_name, path.clone()),
+        Some(class) => (class.as_str(), String::new()),
+        None => (type_name, String::new()),
+    };
+
     // 1) Built-in class via ClassDB
-    if ClassDb::singleton().class_exists(&StringName::from(type_name)) {
+    if script_path.is_empty() && ClassDb::singleton().class_exists(&StringName::from(type_name)) {
         let inst = ClassDb::singleton().instantiate(&StringName::from(type_name));
         let res = inst.try_to_relaxed::<Gd<Resource>>()?; // this does
         return Ok(res);
     }
 
     // 2) Fallback: look up ProjectSettings global_class_list for a script and make the resource ourselves
-    let global_class_list = ProjectSettings::singleton().get_global_class_list();
-    let mut script_path: String = "".into();
-
-    for dict in global_class_list.iter_shared() {
-        if let Some(class_name) = dict.get("class") {
-            if class_name == Variant::from(type_name) {
-                if let Some(path) = dict.get("path") {
-                    script_path = path.try_to_relaxed::<String>()?
-                }
+    if script_path.is_empty() {
+        let global_class_list = ProjectSettings::singleton().get_global_class_list();
+        for dict in global_class_list.iter_shared() {
+            if let Some(class_name) = dict.get("class")
+                && class_name == Variant::from(type_name)
+                && let Some(path) = dict.get("path")
+            {
+                script_path = path.try_to_relaxed::<String>()?
             }
         }
     }
+    if script_path.is_empty() {
+        return Err(ImportError::ResInstanciationError(type_name.to_string()));
+    }
     let mut script = try_load::<Script>(&script_path)?;
     let res = script.call("new", &[]);
     let res = res.try_to::<Gd<Resource>>()?;
@@ -146,56 +651,224 @@ pub fn build_top_level_resource(
     value: GodotValue,
     path: Option<String>,
     frontmatter: &HashMap<String, GodotValue>,
+    conversion: &Conversion,
 ) -> Result<Gd<Resource>> {
-    let res = match value {
+    if let (Some(_), Some(path)) = (&conversion.safe_mode, &path) {
+        return Err(SafeModeError::PathLoading(path.clone()).into());
+    }
+    let (mut res, values) = match value {
         GodotValue::Resource {
             type_name,
-            fields: _,
+            fields,
             abstract_type_name: _,
         } => {
-            // Extract resource_path if present
-
-            if let Some(path) = path {
-                // Try to load existing resource
-                if let Some(existing) = ResourceLoader::singleton().load(&path) {
-                    return Ok(existing);
-                }
-                // If load failed, fall through to instantiate fresh
-            }
-
-            // Instantiate fresh (built-in or class_name fallback)
-            instantiate_resource(&type_name)
+            // Try to load existing resource if a path is given,
+            // if load failed, fall through to instantiate fresh (built-in or class_name fallback)
+            let existing = path.and_then(|path| ResourceLoader::singleton().load(&path));
+            let res = match existing {
+                Some(existing) => existing,
+                None => instantiate_resource(&type_name, conversion)?,
+            };
+            (res, fields)
         }
         _ => Err(ImportError::NotAResource(value))?,
     };
-    let mut res = res?;
-    apply_doke_frontmatter_if_exists(&mut res, frontmatter)?;
+    let phase = conversion.frontmatter_phase;
+    if phase != FrontmatterPhase::After {
+        apply_doke_frontmatter_if_exists(&mut res, frontmatter, conversion)?;
+    }
+    let mut converter = Converter::new(conversion, "root");
+    let values = values
+        .into_iter()
+        .map(|(k, v)| {
+            let span = converter.span_of(&v);
+            Ok((k.clone(), converter.field(&k, v)?, span))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    converter.set_fields(&mut res, values)?;
+    if phase != FrontmatterPhase::Before {
+        apply_doke_frontmatter_if_exists(&mut res, frontmatter, conversion)?;
+    }
     Ok(res)
 }
 
+/// Saves an imported resource over the file at `path`, keeping the file's UID so the
+/// scenes referencing it still do, and taking over the path so the instances already
+/// loaded from it are the ones updated.
+pub fn save_resource(res: &mut Gd<Resource>, path: &str) -> Result<()> {
+    let uid = ResourceLoader::singleton().get_resource_uid(path);
+    res.take_over_path(path);
+    let error = ResourceSaver::singleton().save_ex(&*res).path(path).done();
+    if error != godot::global::Error::OK {
+        return Err(ImportError::SaveError(path.to_string(), error));
+    }
+    // -1 is ResourceUID.INVALID_ID, for files that didn't exist or have no UID
+    if uid != -1 {
+        let mut uids = ResourceUid::singleton();
+        if uids.has_id(uid) {
+            uids.set_id(uid, path);
+        } else {
+            uids.add_id(uid, path);
+        }
+    }
+    Ok(())
+}
+
 // -----------------------
 // Convert mdast::Yaml -> Godot Dictionary (Variant-compatible)
 // -----------------------
 
 const APPLY_DOKE_FM_METHOD: &str = "_apply_doke_frontmatter";
+const APPLY_DOKE_FIELDS_METHOD: &str = "_apply_doke_fields";
 // -----------------------
 // Apply frontmatter: call `_apply_doke_frontmatter` on the resource if it exists
 // -----------------------
 fn apply_doke_frontmatter_if_exists(
     resource: &mut Gd<Resource>,
     frontmatter: &HashMap<String, GodotValue>,
+    conversion: &Conversion,
 ) -> Result<()> {
-    resource.call(
-        APPLY_DOKE_FM_METHOD,
-        &[convert_fm_to_godot(frontmatter)?],
-    );
+    if resource.has_method(APPLY_DOKE_FM_METHOD) {
+        let returned = resource.call(
+            APPLY_DOKE_FM_METHOD,
+            &[convert_fm_to_godot(frontmatter, conversion)?],
+        );
+        check_hook_result(&returned)?;
+    }
     Ok(())
 }
 
-fn convert_fm_to_godot(fm: &HashMap<String, GodotValue>) -> Result<Variant> {
+/// The hook may return nothing, an error code (`OK` is 0)
+/// or a Dictionary of rejected key -> reason, which fail the import.
+fn check_hook_result(returned: &Variant) -> Result<()> {
+    match returned.get_type() {
+        VariantType::INT => match returned.to::<i64>() {
+            0 => Ok(()),
+            code => Err(ImportError::FrontmatterHookFailed(code)),
+        },
+        VariantType::DICTIONARY => {
+            let rejected = returned.to::<Dictionary>();
+            if rejected.is_empty() {
+                return Ok(());
+            }
+            let mut reasons: Vec<String> = rejected
+                .iter_shared()
+                .map(|(key, reason)| format!("{} ({})", key, reason))
+                .collect();
+            reasons.sort();
+            Err(ImportError::FrontmatterHookRejected(reasons.join(", ")))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn convert_fm_to_godot(
+    fm: &HashMap<String, GodotValue>,
+    conversion: &Conversion,
+) -> Result<Variant> {
+    Ok(Variant::from(frontmatter_to_dictionary(fm, conversion)?))
+}
+
+pub fn frontmatter_to_dictionary(
+    fm: &HashMap<String, GodotValue>,
+    conversion: &Conversion,
+) -> Result<Dictionary> {
     let mut dict = Dictionary::new();
+    let mut converter = Converter::new(conversion, "frontmatter");
     for (k, v) in fm {
-        dict.insert(Variant::from(k.clone()), godot_value_to_variant(v.clone())?);
+        dict.set(Variant::from(k.clone()), converter.field(k, v.clone())?);
+    }
+    Ok(dict)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VariantType as T;
+
+    fn assert_converts(pairs: &[(VariantType, VariantType)]) {
+        for &(from, to) in pairs {
+            assert!(converts(from, to), "{from:?} -> {to:?}");
+        }
+    }
+
+    #[test]
+    fn numbers_and_bools_convert_both_ways() {
+        assert_converts(&[
+            (T::BOOL, T::INT),
+            (T::BOOL, T::FLOAT),
+            (T::INT, T::BOOL),
+            (T::INT, T::FLOAT),
+            (T::FLOAT, T::BOOL),
+            (T::FLOAT, T::INT),
+        ]);
+    }
+
+    #[test]
+    fn strings_convert_to_names_paths_and_colors() {
+        assert_converts(&[
+            (T::STRING, T::STRING_NAME),
+            (T::STRING, T::NODE_PATH),
+            (T::STRING_NAME, T::STRING),
+            (T::NODE_PATH, T::STRING),
+            (T::STRING, T::COLOR),
+            (T::INT, T::COLOR),
+        ]);
+    }
+
+    #[test]
+    fn vectors_and_transforms_convert_like_godot() {
+        assert_converts(&[
+            (T::VECTOR2I, T::VECTOR2),
+            (T::VECTOR2, T::VECTOR2I),
+            (T::VECTOR3I, T::VECTOR3),
+            (T::VECTOR3, T::VECTOR3I),
+            (T::VECTOR4I, T::VECTOR4),
+            (T::VECTOR4, T::VECTOR4I),
+            (T::RECT2I, T::RECT2),
+            (T::RECT2, T::RECT2I),
+            (T::TRANSFORM3D, T::TRANSFORM2D),
+            (T::TRANSFORM2D, T::TRANSFORM3D),
+            (T::BASIS, T::TRANSFORM3D),
+            (T::QUATERNION, T::TRANSFORM3D),
+            (T::PROJECTION, T::TRANSFORM3D),
+            (T::TRANSFORM3D, T::PROJECTION),
+            (T::BASIS, T::QUATERNION),
+            (T::QUATERNION, T::BASIS),
+        ]);
+    }
+
+    #[test]
+    fn arrays_convert_to_and_from_packed_arrays() {
+        assert_converts(&[
+            (T::ARRAY, T::PACKED_BYTE_ARRAY),
+            (T::ARRAY, T::PACKED_INT32_ARRAY),
+            (T::ARRAY, T::PACKED_INT64_ARRAY),
+            (T::ARRAY, T::PACKED_FLOAT32_ARRAY),
+            (T::ARRAY, T::PACKED_FLOAT64_ARRAY),
+            (T::ARRAY, T::PACKED_STRING_ARRAY),
+            (T::ARRAY, T::PACKED_VECTOR2_ARRAY),
+            (T::ARRAY, T::PACKED_VECTOR3_ARRAY),
+            (T::ARRAY, T::PACKED_COLOR_ARRAY),
+            (T::ARRAY, T::PACKED_VECTOR4_ARRAY),
+            (T::PACKED_STRING_ARRAY, T::ARRAY),
+            (T::PACKED_INT64_ARRAY, T::ARRAY),
+        ]);
+    }
+
+    #[test]
+    fn other_pairs_are_refused() {
+        for (from, to) in [
+            (T::STRING, T::INT),
+            (T::STRING, T::BOOL),
+            (T::INT, T::STRING),
+            (T::FLOAT, T::COLOR),
+            (T::DICTIONARY, T::ARRAY),
+            (T::PACKED_INT32_ARRAY, T::PACKED_INT64_ARRAY),
+            (T::NIL, T::INT),
+        ] {
+            assert!(!converts(from, to), "{from:?} -> {to:?}");
+        }
+        assert!(converts(T::NIL, T::OBJECT));
     }
-    Ok(Variant::from(dict))
 }
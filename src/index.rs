@@ -0,0 +1,853 @@
+// index.rs
+// Index of a doke vault : every markdown document with its frontmatter, the
+// wiki links it contains, and the words used for search.
+// The index is kept up to date one file at a time, so a watcher only pays
+// for the files that actually changed instead of a full rescan.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use doke::GodotValue;
+use godot::global::{push_error, push_warning};
+use godot::prelude::*;
+use thiserror::Error;
+
+use crate::config;
+use crate::frontmatter::{self, FrontmatterError};
+use crate::graph::{GraphFormat, GraphLimits, LinkGraph};
+use crate::import::frontmatter_to_dictionary;
+use crate::search;
+
+#[derive(Debug, Error)]
+pub enum IndexError {
+    #[error("Can't read {0} : {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("{0} : {1}")]
+    Frontmatter(PathBuf, FrontmatterError),
+}
+
+/// What the index knows about a single document.
+#[derive(Debug, Clone)]
+pub struct IndexedDocument {
+    /// File stem, which is what wiki links refer to.
+    pub name: String,
+    pub frontmatter: HashMap<String, GodotValue>,
+    /// Link keys (see `link_key`) of every `[[wiki link]]` in the file.
+    pub links: Vec<String>,
+    /// Link key of the document named by `extends:`, if any.
+    pub extends: Option<String>,
+    /// Normalized tags, from `tags:` and inline `#tags`.
+    pub tags: Vec<String>,
+    pub terms: HashSet<String>,
+    pub modified: Option<SystemTime>,
+}
+
+#[derive(Debug, Default)]
+pub struct VaultIndex {
+    root: PathBuf,
+    documents: HashMap<PathBuf, IndexedDocument>,
+    /// link key of a document's name -> its path, the first one by path when
+    /// several documents share a name
+    names: HashMap<String, PathBuf>,
+    /// tag -> documents tagged with it
+    tags: HashMap<String, HashSet<PathBuf>>,
+    /// link key -> documents linking to it
+    backlinks: HashMap<String, HashSet<PathBuf>>,
+    /// lowercased word -> documents containing it
+    terms: HashMap<String, HashSet<PathBuf>>,
+}
+
+impl VaultIndex {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Drops everything and indexes the whole vault again.
+    /// Files that fail to index are skipped, and their errors returned.
+    pub fn scan(&mut self) -> Vec<IndexError> {
+        self.documents.clear();
+        self.names.clear();
+        self.tags.clear();
+        self.backlinks.clear();
+        self.terms.clear();
+        let mut errors = Vec::new();
+        for path in markdown_files(&self.root) {
+            if let Err(e) = self.update_file(&path) {
+                errors.push(e);
+            }
+        }
+        errors
+    }
+
+    /// (Re)indexes a single file, replacing what was known about it.
+    pub fn update_file(&mut self, path: &Path) -> Result<(), IndexError> {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| IndexError::Io(path.to_path_buf(), e))?;
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let fm = frontmatter::read(&content)
+            .map_err(|e| IndexError::Frontmatter(path.to_path_buf(), e))?;
+
+        let extends = match fm.get(frontmatter::EXTENDS_KEY) {
+            Some(GodotValue::String(target)) => Some(link_key(target)),
+            _ => None,
+        };
+        let tags = extract_tags(&fm, frontmatter::split(&content).1);
+        let doc = IndexedDocument {
+            name: document_name(path),
+            frontmatter: fm,
+            links: extract_wiki_links(&content),
+            extends,
+            tags,
+            terms: extract_terms(&content),
+            modified,
+        };
+        self.remove_file(path);
+        self.insert(path.to_path_buf(), doc);
+        Ok(())
+    }
+
+    /// Forgets a file. Returns false if it wasn't indexed.
+    pub fn remove_file(&mut self, path: &Path) -> bool {
+        let Some(old) = self.documents.remove(path) else {
+            return false;
+        };
+        let name = link_key(&old.name);
+        if self.names.get(&name).is_some_and(|p| p == path) {
+            // Another document of the same name takes over the links
+            let next = self
+                .documents
+                .iter()
+                .filter(|(_, doc)| link_key(&doc.name) == name)
+                .map(|(p, _)| p)
+                .min()
+                .cloned();
+            match next {
+                Some(next) => self.names.insert(name, next),
+                None => self.names.remove(&name),
+            };
+        }
+        for link in &old.links {
+            remove_from(&mut self.backlinks, link, path);
+        }
+        for tag in &old.tags {
+            remove_from(&mut self.tags, tag, path);
+        }
+        for term in &old.terms {
+            remove_from(&mut self.terms, term, path);
+        }
+        true
+    }
+
+    /// The watcher : compares modification times with what was indexed,
+    /// and only reindexes new or modified files. Deleted files are dropped.
+    /// Returns the paths that changed, along with the errors encountered.
+    pub fn poll_changes(&mut self) -> (Vec<PathBuf>, Vec<IndexError>) {
+        let mut changed = Vec::new();
+        let mut errors = Vec::new();
+        let on_disk: HashSet<PathBuf> = markdown_files(&self.root).into_iter().collect();
+
+        let removed: Vec<PathBuf> = self
+            .documents
+            .keys()
+            .filter(|p| !on_disk.contains(*p))
+            .cloned()
+            .collect();
+        for path in removed {
+            self.remove_file(&path);
+            changed.push(path);
+        }
+
+        for path in on_disk {
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            let known = self.documents.get(&path).and_then(|d| d.modified);
+            if known.is_some() && known == modified {
+                continue;
+            }
+            match self.update_file(&path) {
+                Ok(()) => changed.push(path),
+                Err(e) => errors.push(e),
+            }
+        }
+        (changed, errors)
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&IndexedDocument> {
+        self.documents.get(path)
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = (&PathBuf, &IndexedDocument)> {
+        self.documents.iter()
+    }
+
+    /// The document a link points to, if it is in the vault.
+    pub fn resolve_link(&self, target: &str) -> Option<&PathBuf> {
+        self.names.get(&link_key(target))
+    }
+
+    /// Documents that link to the given document name.
+    pub fn backlinks(&self, name: &str) -> Vec<&PathBuf> {
+        self.backlinks
+            .get(&link_key(name))
+            .map(|set| set.iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Documents tagged with `tag` (with or without its `#`).
+    pub fn documents_by_tag(&self, tag: &str) -> Vec<&PathBuf> {
+        self.tags
+            .get(&normalize_tag(tag))
+            .map(|set| set.iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Every tag used in the vault, sorted.
+    pub fn all_tags(&self) -> Vec<&String> {
+        let mut tags: Vec<&String> = self.tags.keys().collect();
+        tags.sort();
+        tags
+    }
+
+    /// Documents containing the given word.
+    pub fn documents_with_term(&self, term: &str) -> Vec<&PathBuf> {
+        self.terms
+            .get(&term.to_lowercase())
+            .map(|set| set.iter().collect())
+            .unwrap_or_default()
+    }
+
+    fn insert(&mut self, path: PathBuf, doc: IndexedDocument) {
+        let name = link_key(&doc.name);
+        if self.names.get(&name).is_none_or(|owner| path < *owner) {
+            self.names.insert(name, path.clone());
+        }
+        for link in &doc.links {
+            self.backlinks
+                .entry(link.clone())
+                .or_default()
+                .insert(path.clone());
+        }
+        for tag in &doc.tags {
+            self.tags
+                .entry(tag.clone())
+                .or_default()
+                .insert(path.clone());
+        }
+        for term in &doc.terms {
+            self.terms
+                .entry(term.clone())
+                .or_default()
+                .insert(path.clone());
+        }
+        self.documents.insert(path, doc);
+    }
+}
+
+fn remove_from(map: &mut HashMap<String, HashSet<PathBuf>>, key: &str, path: &Path) {
+    if let Some(set) = map.get_mut(key) {
+        set.remove(path);
+        if set.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+// -----------------------
+// Helpers: files, links and terms
+// -----------------------
+
+/// Every `.md` file under `root`, skipping hidden folders (.obsidian, .godot, .git...)
+pub fn markdown_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let hidden = path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if hidden {
+                continue;
+            }
+            if path.is_dir() {
+                stack.push(path);
+            } else if path.extension().is_some_and(|e| e == "md") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// The name wiki links use to refer to a document : its file stem.
+pub fn document_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// The title of a document : its `title` or `name` frontmatter key, or its file name.
+pub fn document_title(frontmatter: &HashMap<String, GodotValue>, path: &Path) -> String {
+    ["title", "name"]
+        .iter()
+        .find_map(|key| match frontmatter.get(*key) {
+            Some(GodotValue::String(title)) => Some(title.clone()),
+            _ => None,
+        })
+        .unwrap_or_else(|| document_name(path))
+}
+
+/// Normalizes a link target so `[[Sword]]`, `[[items/sword.md]]` and
+/// `[[sword#Stats|the sword]]` all point to the same key.
+pub fn link_key(target: &str) -> String {
+    let target = target.split(['|', '#']).next().unwrap_or_default().trim();
+    let target = target.rsplit('/').next().unwrap_or_default();
+    let target = target.strip_suffix(".md").unwrap_or(target);
+    target.to_lowercase()
+}
+
+/// Every `[[wiki link]]` in the text, as link keys, in order of appearance.
+pub fn extract_wiki_links(text: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find("]]") else {
+            break;
+        };
+        let key = link_key(&rest[..end]);
+        if !key.is_empty() {
+            links.push(key);
+        }
+        rest = &rest[end + 2..];
+    }
+    links
+}
+
+/// A run of text, or a `[[wiki link]]` with its link key and the text it shows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WikiPiece<'a> {
+    Text(&'a str),
+    Link { key: String, label: &'a str },
+}
+
+/// The text split around its `[[wiki links]]`. A link shows its `|label`,
+/// or its target without the `#heading`.
+pub fn wiki_pieces(text: &str) -> Vec<WikiPiece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let Some(end) = rest[start + 2..].find("]]").map(|e| start + 2 + e) else {
+            break;
+        };
+        let inner = &rest[start + 2..end];
+        let key = link_key(inner);
+        if key.is_empty() {
+            pieces.push(WikiPiece::Text(&rest[..end + 2]));
+        } else {
+            if start > 0 {
+                pieces.push(WikiPiece::Text(&rest[..start]));
+            }
+            let label = match inner.split_once('|') {
+                Some((_, label)) => label.trim(),
+                None => inner.split('#').next().unwrap_or_default().trim(),
+            };
+            pieces.push(WikiPiece::Link { key, label });
+        }
+        rest = &rest[end + 2..];
+    }
+    if !rest.is_empty() {
+        pieces.push(WikiPiece::Text(rest));
+    }
+    pieces
+}
+
+pub const TAGS_KEY: &str = "tags";
+
+/// Tags are compared without their `#` and case.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+/// Tags from the `tags:` frontmatter key (a list, or a comma/space separated string)
+/// and from inline `#tags` in the markdown, outside code blocks.
+pub fn extract_tags(fm: &HashMap<String, GodotValue>, markdown: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    let mut add = |tag: &str| {
+        let tag = normalize_tag(tag);
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    };
+    match fm.get(TAGS_KEY) {
+        Some(GodotValue::Array(items)) => items
+            .iter()
+            .filter_map(|t| match t {
+                GodotValue::String(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .for_each(&mut add),
+        Some(GodotValue::String(s)) => s
+            .split([',', ' '])
+            .filter(|t| !t.is_empty())
+            .for_each(&mut add),
+        _ => {}
+    }
+
+    let mut in_code = false;
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        let mut prev = ' ';
+        for (i, c) in line.char_indices() {
+            if c == '#' && prev.is_whitespace() {
+                let tag: String = line[i + 1..]
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+                    .collect();
+                // Obsidian doesn't treat purely numeric words as tags (#1)
+                if tag.chars().any(|c| !c.is_numeric()) {
+                    add(&tag);
+                }
+            }
+            prev = c;
+        }
+    }
+    tags
+}
+
+fn extract_terms(text: &str) -> HashSet<String> {
+    search::words(text).collect()
+}
+
+// -----------------------
+// NativeClass for Godot
+// -----------------------
+
+/// Godot-facing vault index.
+///
+/// Call `scan` once, then either `poll_changes` on a timer, or `update_file` /
+/// `remove_file` from your own file watcher to keep it current.
+#[derive(GodotClass)]
+#[class(init, base=RefCounted)]
+pub struct DokeVaultIndex {
+    index: VaultIndex,
+    base: Base<RefCounted>,
+}
+
+#[godot_api]
+impl DokeVaultIndex {
+    /// Emitted when `poll_changes` picked up changed, new or deleted files.
+    #[signal]
+    fn index_updated(paths: PackedStringArray);
+
+    #[func]
+    /// Indexes every markdown file under `root`. Returns the number of files that failed.
+    fn scan(&mut self, root: String) -> i64 {
+        self.index = VaultIndex::new(root);
+        let errors = self.index.scan();
+        report_errors(&errors);
+        errors.len() as i64
+    }
+
+    #[func]
+    /// Reindexes a single file. Returns 0 on success.
+    fn update_file(&mut self, path: String) -> i64 {
+        match self.index.update_file(Path::new(&path)) {
+            Ok(()) => 0,
+            Err(e) => {
+                report_errors(&[e]);
+                1
+            }
+        }
+    }
+
+    #[func]
+    fn remove_file(&mut self, path: String) -> bool {
+        self.index.remove_file(Path::new(&path))
+    }
+
+    #[func]
+    /// Picks up files modified since they were indexed. Cheap enough to call from a Timer.
+    fn poll_changes(&mut self) -> PackedStringArray {
+        let (changed, errors) = self.index.poll_changes();
+        report_errors(&errors);
+        let paths = to_packed(changed.iter());
+        if !paths.is_empty() {
+            self.base_mut()
+                .emit_signal("index_updated", &[paths.to_variant()]);
+        }
+        paths
+    }
+
+    #[func]
+    fn get_document_paths(&self) -> PackedStringArray {
+        to_packed(self.index.documents().map(|(p, _)| p))
+    }
+
+    #[func]
+    fn get_links(&self, path: String) -> PackedStringArray {
+        match self.index.get(Path::new(&path)) {
+            Some(doc) => doc.links.iter().map(GString::from).collect(),
+            None => PackedStringArray::new(),
+        }
+    }
+
+    #[func]
+    /// Path of the document a wiki link points to, empty if it isn't indexed.
+    fn resolve_link(&self, target: String) -> String {
+        self.index
+            .resolve_link(&target)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    #[func]
+    fn get_frontmatter(&self, path: String) -> Dictionary {
+        let Some(doc) = self.index.get(Path::new(&path)) else {
+            return Dictionary::new();
+        };
+        frontmatter_to_dictionary(&doc.frontmatter, &Default::default()).unwrap_or_else(|e| {
+            push_error(&[Variant::from(e.to_string())]);
+            Dictionary::new()
+        })
+    }
+
+    #[func]
+    /// Paths of the documents linking to the document called `name`.
+    fn get_backlinks(&self, name: String) -> PackedStringArray {
+        to_packed(self.index.backlinks(&name).into_iter())
+    }
+
+    #[func]
+    /// Metrics on the link graph : clusters, dependency depth, most linked documents
+    /// and links to documents that don't exist.
+    fn get_graph_metrics(&self) -> Dictionary {
+        let graph = LinkGraph::from_index(&self.index);
+        let clusters: Array<PackedStringArray> = graph
+            .clusters()
+            .iter()
+            .map(|c| to_packed(c.iter()))
+            .collect();
+        let most_linked: Array<Dictionary> = graph
+            .most_linked(10)
+            .into_iter()
+            .map(|(path, count)| {
+                let mut d = Dictionary::new();
+                d.set("path", path.to_string_lossy().as_ref());
+                d.set("count", count as i64);
+                d
+            })
+            .collect();
+        let unresolved: Array<Dictionary> = graph
+            .unresolved
+            .iter()
+            .map(|(path, link)| {
+                let mut d = Dictionary::new();
+                d.set("path", path.to_string_lossy().as_ref());
+                d.set("link", link.as_str());
+                d
+            })
+            .collect();
+
+        let mut metrics = Dictionary::new();
+        metrics.set("documents", graph.nodes.len() as i64);
+        metrics.set("clusters", clusters);
+        metrics.set(
+            "max_depth",
+            graph.depths().into_iter().max().unwrap_or(0) as i64,
+        );
+        metrics.set("most_linked", most_linked);
+        metrics.set("unresolved_links", unresolved);
+        metrics
+    }
+
+    #[func]
+    /// Checks the link graph against the `graph:` limits of a config file.
+    /// Each violation is pushed as a warning and returned.
+    fn validate_graph(&self, config_path: String) -> PackedStringArray {
+        let limits = config::load_yaml(Path::new(&config_path))
+            .and_then(|y| GraphLimits::from_yaml(&y["graph"]));
+        let limits = match limits {
+            Ok(limits) => limits,
+            Err(e) => {
+                push_error(&[Variant::from(e.to_string())]);
+                return PackedStringArray::new();
+            }
+        };
+        let diagnostics = limits.check(&LinkGraph::from_index(&self.index));
+        for d in &diagnostics {
+            push_warning(&[Variant::from(d.as_str())]);
+        }
+        diagnostics.iter().map(GString::from).collect()
+    }
+
+    #[func]
+    /// Writes the link graph to `path` as `"dot"` or `"graphml"`.
+    /// An empty format picks it from the file extension. Returns 0 on success.
+    fn export_graph(&self, path: String, format: String) -> i64 {
+        let Some(format) = GraphFormat::from_name(&format, Path::new(&path)) else {
+            push_error(&[Variant::from(format!(
+                "Unknown graph format for {} (expected dot or graphml)",
+                path
+            ))]);
+            return 1;
+        };
+        let graph = LinkGraph::from_index(&self.index);
+        match std::fs::write(&path, graph.export(format)) {
+            Ok(()) => 0,
+            Err(e) => {
+                push_error(&[Variant::from(format!("Can't write {} : {}", path, e))]);
+                1
+            }
+        }
+    }
+
+    #[func]
+    /// Paths of the documents tagged `tag`, from `tags:` or inline `#tag`.
+    fn get_documents_by_tag(&self, tag: String) -> PackedStringArray {
+        to_packed(self.index.documents_by_tag(&tag).into_iter())
+    }
+
+    #[func]
+    fn get_tags(&self, path: String) -> PackedStringArray {
+        match self.index.get(Path::new(&path)) {
+            Some(doc) => doc.tags.iter().map(GString::from).collect(),
+            None => PackedStringArray::new(),
+        }
+    }
+
+    #[func]
+    fn get_all_tags(&self) -> PackedStringArray {
+        self.index
+            .all_tags()
+            .into_iter()
+            .map(GString::from)
+            .collect()
+    }
+
+    #[func]
+    fn get_documents_with_term(&self, term: String) -> PackedStringArray {
+        to_packed(self.index.documents_with_term(&term).into_iter())
+    }
+}
+
+fn to_packed<'a>(paths: impl Iterator<Item = &'a PathBuf>) -> PackedStringArray {
+    paths
+        .map(|p| GString::from(p.to_string_lossy().as_ref()))
+        .collect()
+}
+
+fn report_errors(errors: &[IndexError]) {
+    for e in errors {
+        push_error(&[Variant::from(e.to_string())]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh vault folder under the system's temp dir.
+    fn vault(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("doke_index_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (path, content) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn links_resolve_to_documents() {
+        let root = vault(
+            "links",
+            &[
+                ("sword.md", "---\n---\n- Forged from [[Iron Ore]]\n"),
+                ("materials/iron ore.md", "---\n---\n- Found in [[Mines]]\n"),
+            ],
+        );
+        let mut index = VaultIndex::new(&root);
+        assert!(index.scan().is_empty());
+
+        let ore = root.join("materials/iron ore.md");
+        assert_eq!(index.resolve_link("Iron Ore"), Some(&ore));
+        assert_eq!(index.resolve_link("Mines"), None);
+
+        let graph = LinkGraph::from_index(&index);
+        let id = |p: &Path| graph.nodes.iter().position(|n| n == p).unwrap();
+        assert_eq!(graph.edges[id(&root.join("sword.md"))], vec![id(&ore)]);
+        assert!(graph.edges[id(&ore)].is_empty());
+        assert_eq!(graph.unresolved, vec![(ore, "mines".to_string())]);
+    }
+
+    #[test]
+    fn tags_from_frontmatter_and_text() {
+        let root = vault(
+            "tags",
+            &[
+                (
+                    "sword.md",
+                    "---
+tags: [Weapon, rare]
+---
+- Deals 5 damage #melee
+",
+                ),
+                (
+                    "bow.md",
+                    "---
+tags:
+  - weapon
+---
+- Shoots arrows
+",
+                ),
+            ],
+        );
+        let mut index = VaultIndex::new(&root);
+        index.scan();
+        let sword = root.join("sword.md");
+        let mut weapons = index.documents_by_tag("#weapon");
+        weapons.sort();
+        assert_eq!(weapons, vec![&root.join("bow.md"), &sword]);
+        assert_eq!(index.documents_by_tag("melee"), vec![&sword]);
+        assert_eq!(index.all_tags(), vec!["melee", "rare", "weapon"]);
+
+        index.remove_file(&sword);
+        assert!(index.documents_by_tag("melee").is_empty());
+        assert_eq!(index.all_tags(), vec!["weapon"]);
+    }
+
+    #[test]
+    fn shared_names_resolve_to_the_first_path() {
+        let root = vault("shared", &[("a/item.md", ""), ("b/item.md", "")]);
+        let mut index = VaultIndex::new(&root);
+        index.scan();
+        assert_eq!(index.resolve_link("item"), Some(&root.join("a/item.md")));
+
+        index.remove_file(&root.join("a/item.md"));
+        assert_eq!(index.resolve_link("item"), Some(&root.join("b/item.md")));
+        index.remove_file(&root.join("b/item.md"));
+        assert_eq!(index.resolve_link("item"), None);
+    }
+
+    /// Rewrites a file with a later modification time, as an editor saving it would.
+    fn edit(path: &Path, content: &str, seconds_later: u64) {
+        let modified = std::fs::metadata(path).unwrap().modified().unwrap();
+        std::fs::write(path, content).unwrap();
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(modified + std::time::Duration::from_secs(seconds_later))
+            .unwrap();
+    }
+
+    #[test]
+    fn update_replaces_what_was_indexed() {
+        let root = vault(
+            "update",
+            &[
+                (
+                    "sword.md",
+                    "---\ntags: [weapon]\n---\n- Forged from [[Iron]]\n",
+                ),
+                ("iron.md", ""),
+            ],
+        );
+        let sword = root.join("sword.md");
+        let mut index = VaultIndex::new(&root);
+        index.scan();
+        assert_eq!(index.backlinks("iron"), vec![&sword]);
+        assert_eq!(index.documents_by_tag("weapon"), vec![&sword]);
+
+        std::fs::write(&sword, "---\ntags: [relic]\n---\n- Forged from [[Gold]]\n").unwrap();
+        index.update_file(&sword).unwrap();
+        assert!(index.backlinks("iron").is_empty());
+        assert_eq!(index.backlinks("gold"), vec![&sword]);
+        assert!(index.documents_by_tag("weapon").is_empty());
+        assert_eq!(index.documents_by_tag("relic"), vec![&sword]);
+        assert_eq!(index.all_tags(), vec!["relic"]);
+        assert!(index.documents_with_term("iron").is_empty());
+        assert_eq!(index.documents_with_term("gold"), vec![&sword]);
+        assert_eq!(index.resolve_link("sword"), Some(&sword));
+    }
+
+    #[test]
+    fn removed_files_are_forgotten() {
+        let root = vault(
+            "remove",
+            &[(
+                "sword.md",
+                "---\ntags: [weapon]\n---\n- Forged from [[Iron]]\n",
+            )],
+        );
+        let sword = root.join("sword.md");
+        let mut index = VaultIndex::new(&root);
+        index.scan();
+
+        assert!(index.remove_file(&sword));
+        assert!(!index.remove_file(&sword));
+        assert!(index.get(&sword).is_none());
+        assert_eq!(index.resolve_link("sword"), None);
+        assert!(index.backlinks("iron").is_empty());
+        assert!(index.all_tags().is_empty());
+        assert!(index.documents_with_term("forged").is_empty());
+    }
+
+    #[test]
+    fn polling_reindexes_changed_files_only() {
+        let root = vault(
+            "poll",
+            &[
+                ("sword.md", "- Forged from [[Iron]]\n"),
+                ("bow.md", "- Shoots arrows\n"),
+                ("shield.md", "- Blocks\n"),
+            ],
+        );
+        let (sword, bow, shield) = (
+            root.join("sword.md"),
+            root.join("bow.md"),
+            root.join("shield.md"),
+        );
+        let mut index = VaultIndex::new(&root);
+        index.scan();
+        let (changed, errors) = index.poll_changes();
+        assert!(changed.is_empty() && errors.is_empty());
+
+        edit(&sword, "- Forged from [[Steel]]\n", 10);
+        std::fs::remove_file(&shield).unwrap();
+        let axe = root.join("axe.md");
+        std::fs::write(&axe, "- Chops [[Wood]]\n").unwrap();
+        // Rewritten without a new modification time, so not reindexed
+        let modified = std::fs::metadata(&bow).unwrap().modified().unwrap();
+        std::fs::write(&bow, "- Shoots [[Arrows]]\n").unwrap();
+        let file = std::fs::File::options().write(true).open(&bow).unwrap();
+        file.set_modified(modified).unwrap();
+
+        let (mut changed, errors) = index.poll_changes();
+        changed.sort();
+        assert!(errors.is_empty());
+        assert_eq!(changed, vec![axe.clone(), shield.clone(), sword.clone()]);
+        assert_eq!(index.backlinks("steel"), vec![&sword]);
+        assert!(index.backlinks("iron").is_empty());
+        assert_eq!(index.backlinks("wood"), vec![&axe]);
+        assert!(index.backlinks("arrows").is_empty());
+        assert!(index.get(&shield).is_none());
+
+        let (changed, _) = index.poll_changes();
+        assert!(changed.is_empty());
+    }
+}
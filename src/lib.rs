@@ -1,17 +1,103 @@
 // doke_importer.rs
 // GDExtension class to hold Rust Markdown parsers and provide a method
 // to parse markdown files into Godot resources using previously defined import logic.
+mod bbcode;
+mod budgets;
+mod callable_parser;
+mod computed;
+mod conditions;
+mod config;
+pub mod converters;
+mod coverage;
+mod database;
+mod diagnostic;
+mod diff;
+mod dispatch;
+mod doke_index;
+mod export;
+mod fields;
+mod file_error;
+mod frontmatter;
+mod frontmatter_resources;
+mod grammar;
+mod graph;
+mod html;
 mod import;
+mod index;
+mod lint;
+mod localization;
+mod locks;
+mod matching;
+mod messages;
+pub mod middleware;
+mod naming;
+mod outputs;
+mod parser_api;
+mod parser_context;
+mod query;
+mod registry;
+mod safe_mode;
+mod search;
+mod sections;
+mod source;
+mod span;
+mod spreadsheet;
+mod stats;
+mod tables;
+mod trace;
+mod validation;
+mod value_types;
+mod variables;
+mod vault_iterator;
+#[cfg(feature = "wasm")]
+mod wasm_parser;
+mod watch;
+mod writer;
 use doke::{
-    DokeParser, DokePipe, GodotValue,
-    file_builder::{self, ResourceBuilder},
+    DokeDocument, DokePipe, GodotValue,
+    file_builder::{BuilderError, ResourceBuilder},
     parsers::{self, TypedSentencesParser},
+    semantic::DokeValidate,
+};
+use godot::{
+    classes::{
+        FileAccess, ProjectSettings, ResourceLoader, ResourceSaver, Translation, TranslationServer,
+    },
+    global::{push_error, push_warning},
+    prelude::*,
 };
-use godot::{global::push_error, prelude::*};
 
-use std::{collections::HashMap, io::BufRead, path::Path, sync::Arc};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+    time::Instant,
+};
 
-use crate::import::ImportError;
+use crate::config::{ConfigError, ImportConfig, Limits};
+use crate::diagnostic::{Diagnostic, DokeDiagnostic};
+use crate::diff::ImportHistory;
+use crate::doke_index::DokeIndex;
+use crate::file_error::ErrorContext;
+use crate::frontmatter::FrontmatterError;
+use crate::import::{Conversion, ImportError, ResourceCache, ValueSpans};
+use crate::locks::ImportLocks;
+use crate::matching::MatchingParser;
+use crate::middleware::CallableHooks;
+use crate::parser_context::ParserContext;
+use crate::safe_mode::SafeModeError;
+use crate::sections::HeadingNumbers;
+use crate::source::Source;
+use crate::stats::{ImportStats, StageTimings};
+use crate::trace::Trace;
+use crate::validation::ValidationError;
+use crate::value_types::ValueTypeParser;
+use crate::variables::Variables;
+use crate::vault_iterator::DokeVaultIterator;
+use crate::watch::Watcher;
 
 // -----------------------
 // NativeClass for Godot
@@ -20,7 +106,28 @@ use crate::import::ImportError;
 #[class(init, base=Node)]
 pub struct DokeImporter {
     parsers: HashMap<String, Arc<DokePipe>>,
+    /// The plugin parsers of each pipe with their versions, stamped on the resources
+    parser_versions: HashMap<String, String>,
     builders: HashMap<String, Arc<ResourceBuilder>>,
+    configs: HashMap<String, Arc<ImportConfig>>,
+    /// The sentence patterns of each file type, loaded on first use
+    patterns: RefCell<HashMap<String, Rc<Vec<grammar::Pattern>>>>,
+    variables: Variables,
+    tracing: bool,
+    collect_errors: bool,
+    last_trace: RefCell<Trace>,
+    last_diagnostics: RefCell<Vec<Diagnostic>>,
+    /// Where the resources of the last imported document come from, for conversion errors
+    last_spans: RefCell<ValueSpans>,
+    resource_cache: Rc<RefCell<ResourceCache>>,
+    stats: RefCell<ImportStats>,
+    last_timings: RefCell<StageTimings>,
+    /// The last value imported from each document, for `diff_doke`
+    history: RefCell<ImportHistory>,
+    watcher: Watcher,
+    locks: ImportLocks,
+    hooks: CallableHooks,
+    base: Base<Node>,
 }
 
 #[godot_api]
@@ -28,21 +135,76 @@ impl DokeImporter {
     #[func]
     ///Loads parsers for a filetype
     fn load_parser_for_filetype(&mut self, file_type: String, config_path: String) -> i64 {
-        return self.load_file_builder(file_type.clone(), config_path.clone())
-            + self.load_sentence_parser(file_type, config_path);
+        // The config first, the pipe takes its plugins from it
+        self.load_import_config(file_type.clone(), config_path.clone())
+            + self.load_file_builder(file_type.clone(), config_path.clone())
+            + self.load_sentence_parser(file_type, config_path)
     }
+    #[func]
+    /// Loads parsers for a filetype from a Dictionary shaped like a config file,
+    /// so editor tools can build or tweak configs without writing them to disk.
+    /// Relative paths (parser globs, documents...) start from `base_dir` if the
+    /// Dictionary has one, from the project root otherwise.
+    fn load_parser_for_filetype_from_dict(&mut self, file_type: String, config: Dictionary) -> i64 {
+        let yaml = match config::variant_to_yaml(&config.to_variant()) {
+            Ok(yaml) => yaml,
+            Err(e) => {
+                push_error(&[Variant::from(e.to_string())]);
+                return 1;
+            }
+        };
+        let base_dir = match yaml["base_dir"].as_str() {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(
+                ProjectSettings::singleton()
+                    .globalize_path("res://")
+                    .to_string(),
+            ),
+        };
+        let rules = config::emit_yaml(&yaml);
+
+        self.insert_import_config(file_type.clone(), ImportConfig::from_yaml(&yaml, &base_dir))
+            + self.insert_file_builder(
+                file_type.clone(),
+                config::builder_config(&yaml).and_then(ResourceBuilder::from_config),
+            )
+            + self.insert_sentence_parser(
+                file_type,
+                TypedSentencesParser::from_config(&rules, &base_dir),
+            )
+    }
+
     // Load a TypedSentencesParser and add it to the parser map
     fn load_sentence_parser(&mut self, file_type: String, config_path: String) -> i64 {
-        let typed_parser = TypedSentencesParser::from_config_file(&Path::new(&config_path));
+        let typed_parser = TypedSentencesParser::from_config_file(Path::new(&config_path));
+        self.insert_sentence_parser(file_type, typed_parser)
+    }
+
+    // doke doesn't export TypedSentencesError, hence the generic error
+    fn insert_sentence_parser<E: std::fmt::Display>(
+        &mut self,
+        file_type: String,
+        typed_parser: Result<TypedSentencesParser, E>,
+    ) -> i64 {
         match typed_parser {
-            Ok(parser) => {
-                let pipe = DokePipe::new()
-                    .add(parsers::FrontmatterTemplateParser)
-                    .add(parser)
-                    .add(parsers::DebugPrinter);
-                self.parsers.insert(file_type, pipe.into());
-                0
-            }
+            Ok(parser) => match self.build_pipe(&file_type, parser) {
+                Ok((pipe, versions)) => {
+                    // Documents imported by other parser versions are out of date
+                    let previous = self
+                        .parser_versions
+                        .insert(file_type.clone(), versions.clone());
+                    if previous.is_some_and(|p| p != versions) {
+                        self.watcher.invalidate(&file_type);
+                    }
+                    self.parsers.insert(file_type, pipe.into());
+                    self.locks.invalidate();
+                    0
+                }
+                Err(e) => {
+                    push_error(&[Variant::from(format!("{} : {}", file_type, e))]);
+                    1
+                }
+            },
             Err(e) => {
                 push_error(&[Variant::from(e.to_string())]);
                 1
@@ -50,12 +212,257 @@ impl DokeImporter {
         }
     }
 
+    fn parser_versions(&self, file_type: &str) -> &str {
+        self.parser_versions
+            .get(file_type)
+            .map_or("", String::as_str)
+    }
+
+    /// Puts the stages of the file type's config (the default ones without a config) in a pipe.
+    /// Also returns the versions of its plugin parsers, see `RegisteredParser::versions`.
+    fn build_pipe(
+        &self,
+        file_type: &str,
+        parser: TypedSentencesParser,
+    ) -> Result<(DokePipe, String), String> {
+        let default = ImportConfig::default();
+        let config = self.configs.get(file_type).map_or(&default, |c| &**c);
+        let stages = config.stages();
+        let mut sentences = Some(parser);
+        let mut pipe = DokePipe::new();
+        let mut versions = Vec::new();
+        for (name, options) in stages {
+            if config::is_builtin_stage(&name) && options != GodotValue::Nil {
+                return Err(format!("the built-in stage '{}' takes no options", name));
+            }
+            pipe = match name.as_str() {
+                config::FRONTMATTER_TEMPLATES_STAGE => pipe.add(parsers::FrontmatterTemplateParser),
+                config::MATCHING_STAGE => pipe.add(MatchingParser {
+                    matching: config.matching.clone(),
+                    synonyms: config.synonyms.clone(),
+                    patterns: grammar::load_patterns(config).map_err(|e| e.to_string())?,
+                }),
+                config::SENTENCES_STAGE => match sentences.take() {
+                    Some(parser) => pipe.add(parser),
+                    None => return Err("the 'sentences' stage can only be used once".into()),
+                },
+                config::VALUE_TYPES_STAGE => pipe.add(ValueTypeParser(config.value_types.clone())),
+                config::DEBUG_STAGE => pipe.add(parsers::DebugPrinter),
+                _ => match parser_api::shared().get(&name, &options) {
+                    Ok(Some(plugin)) => {
+                        versions.push(format!("{}:{}", name, plugin.versions().join("|")));
+                        pipe.add(plugin)
+                    }
+                    Ok(None) => return Err(format!("no parser plugin named '{}' is loaded", name)),
+                    Err(e) => return Err(e.to_string()),
+                },
+            };
+        }
+        Ok((pipe, versions.join(",")))
+    }
+
     // Load a ResourceBuilder from the same config file
     fn load_file_builder(&mut self, file_type: String, config_path: String) -> i64 {
-        let builder = ResourceBuilder::from_file(&Path::new(&config_path));
+        let builder = ResourceBuilder::from_file(Path::new(&config_path));
+        self.insert_file_builder(file_type, builder)
+    }
+
+    fn insert_file_builder(
+        &mut self,
+        file_type: String,
+        builder: Result<ResourceBuilder, BuilderError>,
+    ) -> i64 {
         match builder {
             Ok(builder) => {
                 self.builders.insert(file_type, builder.into());
+                self.locks.invalidate();
+                0
+            }
+            Err(e) => {
+                push_error(&[Variant::from(e.to_string())]);
+                1
+            }
+        }
+    }
+
+    // Load the importer-side settings (coercion...) from the same config file
+    fn load_import_config(&mut self, file_type: String, config_path: String) -> i64 {
+        let config = ImportConfig::from_file(Path::new(&config_path));
+        self.insert_import_config(file_type, config)
+    }
+
+    fn insert_import_config(
+        &mut self,
+        file_type: String,
+        config: Result<ImportConfig, ConfigError>,
+    ) -> i64 {
+        match config {
+            Ok(config) => {
+                self.patterns.borrow_mut().remove(&file_type);
+                // Resources shared under the old config may not be built the same anymore
+                self.resource_cache
+                    .borrow_mut()
+                    .retain(|(scope, _), _| *scope != file_type);
+                self.configs.insert(file_type, config.into());
+                self.locks.invalidate();
+                0
+            }
+            Err(e) => {
+                push_error(&[Variant::from(e.to_string())]);
+                1
+            }
+        }
+    }
+
+    #[func]
+    /// Same as `DokeRegistry.load_parser_plugins`, the parsers are shared by every importer.
+    fn load_parser_plugins(&self, dir: String) -> Dictionary {
+        registry::load_parser_plugins(&dir)
+    }
+
+    #[func]
+    /// Same as `DokeRegistry.register_gdscript_parser`, the parsers are shared by every importer.
+    fn register_gdscript_parser(&self, name: String, callable: Callable, priority: i32) -> i64 {
+        registry::register_gdscript_parser(&name, callable, priority)
+    }
+
+    #[func]
+    /// Same as `DokeRegistry.load_wasm_parser`, the parsers are shared by every importer.
+    fn load_wasm_parser(&self, name: String, path: String, priority: i32) -> i64 {
+        registry::load_wasm_parser(&name, &path, priority)
+    }
+
+    #[func]
+    /// Adds a GDScript pre-parse hook, `func(file_type, path, content) -> String`, rewriting
+    /// the Doké section of the documents before they are read. Rust hooks run first.
+    fn add_pre_parse_hook(&mut self, callable: Callable) {
+        self.hooks.pre_parse.push(callable);
+    }
+
+    #[func]
+    /// Adds a GDScript post-parse hook, `func(file_type, path, value, frontmatter) -> Variant`,
+    /// returning the document's new value, or null to keep it. Resources are
+    /// `{type = ..., fields = {...}}` Dictionaries. It runs before the `required` fields are checked.
+    fn add_post_parse_hook(&mut self, callable: Callable) {
+        self.hooks.post_parse.push(callable);
+    }
+
+    #[func]
+    /// For GDScript parsers : reads a file next to the document being parsed, or anywhere
+    /// in the project with a `res://` path. Files out of the project are refused.
+    fn read_sibling_file(&self, rel_path: String) -> GString {
+        match parser_context::with_current(|c| c.read_sibling_file(&rel_path)) {
+            Ok(text) => text.into(),
+            Err(e) => {
+                push_error(&[Variant::from(e.to_string())]);
+                GString::new()
+            }
+        }
+    }
+
+    #[func]
+    /// For GDScript parsers : the absolute path of a file, relative paths starting from
+    /// the document being parsed. Empty for paths out of the project.
+    fn resolve_res_path(&self, path: String) -> GString {
+        match parser_context::with_current(|c| c.resolve_res_path(&path)) {
+            Ok(path) => path.to_string_lossy().as_ref().into(),
+            Err(e) => {
+                push_error(&[Variant::from(e.to_string())]);
+                GString::new()
+            }
+        }
+    }
+
+    #[func]
+    /// Same as `DokeRegistry.replace_gdscript_parser`, the parsers are shared by every importer.
+    fn replace_gdscript_parser(&self, name: String, callable: Callable, priority: i32) {
+        registry::replace_gdscript_parser(&name, callable, priority)
+    }
+
+    #[func]
+    /// Same as `DokeRegistry.unregister_parser`, the parsers are shared by every importer.
+    fn unregister_parser(&self, name: String) -> bool {
+        parser_api::shared_mut().unregister(&name)
+    }
+
+    #[func]
+    /// The file types with a pipe loaded, for editor dropdowns.
+    fn get_supported_types(&self) -> PackedStringArray {
+        let mut types: Vec<&String> = self.parsers.keys().collect();
+        types.sort();
+        types.into_iter().map(GString::from).collect()
+    }
+
+    #[func]
+    /// Same as `DokeRegistry.get_parser_versions`, the parsers are shared by every importer.
+    fn get_parser_versions(&self) -> Dictionary {
+        registry::parser_versions()
+    }
+
+    #[func]
+    /// Whether a resource imported earlier is out of date : its document, the variables or a
+    /// document it extends changed, or the parsers of `file_type` aren't the versions that built it. Resources without
+    /// `doke_source` metadata always are.
+    fn needs_reimport(&self, file_type: String, resource: Gd<Resource>) -> bool {
+        if !resource.has_meta(source::SOURCE_PATH_META) {
+            return true;
+        }
+        let md_path = resource.get_meta(source::SOURCE_PATH_META).to_string();
+        !self.source(&file_type, &md_path).is_stamp_of(&resource)
+    }
+
+    #[func]
+    /// The pipe of a file type : `{file_type, stages}`, each stage being
+    /// `{name, builtin, options, parsers}`, with `parsers` listing the registered parsers of
+    /// plugin stages like `get_parser_versions` plus their `default_options`.
+    fn describe_parser(&self, file_type: String) -> Dictionary {
+        let Some(config) = self.configs.get(&file_type) else {
+            push_error(&[Variant::from(format!(
+                "No file type '{}' is loaded",
+                file_type
+            ))]);
+            return Dictionary::new();
+        };
+        let stages: Array<Dictionary> = config
+            .stages()
+            .into_iter()
+            .map(|(name, options)| {
+                let parsers: Array<Dictionary> = parser_api::shared()
+                    .describe(&name)
+                    .into_iter()
+                    .map(|info| {
+                        let mut d = Dictionary::new();
+                        d.set("source", info.source);
+                        d.set("priority", info.priority);
+                        d.set("version", info.version);
+                        d.set(
+                            "default_options",
+                            middleware::to_variant(&info.default_options),
+                        );
+                        d
+                    })
+                    .collect();
+                let mut stage = Dictionary::new();
+                stage.set("builtin", config::is_builtin_stage(&name));
+                stage.set("name", name);
+                stage.set("options", middleware::to_variant(&options));
+                stage.set("parsers", parsers);
+                stage
+            })
+            .collect();
+        let mut description = Dictionary::new();
+        description.set("file_type", file_type);
+        description.set("stages", stages);
+        description
+    }
+
+    #[func]
+    /// Loads the project-wide `{{variables}}` from a yaml file, replacing the previous ones.
+    fn load_variables(&mut self, path: String) -> i64 {
+        match Variables::from_file(Path::new(&path)) {
+            Ok(vars) => {
+                self.variables = vars;
+                self.locks.invalidate();
                 0
             }
             Err(e) => {
@@ -65,11 +472,122 @@ impl DokeImporter {
         }
     }
 
+    #[func]
+    /// Reads only the frontmatter of a document : no markdown parsing and no resource building,
+    /// for fast scanning of large vaults. `{{variables}}` are substituted, file-type rules aren't applied.
+    fn get_doke_frontmatter(&self, md_path: String) -> Dictionary {
+        let read = || -> Result<Dictionary, ImportError> {
+            let header = frontmatter::read_header(Path::new(&md_path))?;
+            let header = self
+                .variables
+                .interpolate(&header)
+                .map_err(FrontmatterError::from)?;
+            import::frontmatter_to_dictionary(&frontmatter::read(&header)?, &Default::default())
+        };
+        read().unwrap_or_else(|e| {
+            push_error(&[Variant::from(format!("{} : {}", md_path, e))]);
+            Dictionary::new()
+        })
+    }
+
+    #[func]
+    /// The wiki part of a document (after its doke section) as BBCode for a RichTextLabel,
+    /// `[[wiki links]]` being `[url]`s to their link key. `{{variables}}` are substituted.
+    fn get_doke_bbcode(&self, md_path: String) -> GString {
+        let read = || -> Result<String, ImportError> {
+            let wiki = frontmatter::read_wiki_section(Path::new(&md_path))?;
+            let wiki = self
+                .variables
+                .interpolate(&wiki)
+                .map_err(FrontmatterError::from)?;
+            Ok(bbcode::to_bbcode(&wiki))
+        };
+        match read() {
+            Ok(bbcode) => bbcode.into(),
+            Err(e) => {
+                push_error(&[Variant::from(format!("{} : {}", md_path, e))]);
+                GString::new()
+            }
+        }
+    }
+
+    #[func]
+    /// A summary of a document for list views, read without parsing its statements or building
+    /// it : `title` (the `title` or `name` frontmatter key, or the file name), `first_paragraph`
+    /// of its wiki part as plain text, `tags`, `links` (its `[[wiki links]]`) and
+    /// `linked_documents` (the documents they point to). `{{variables}}` are substituted.
+    fn get_doke_preview(&self, md_path: String) -> Dictionary {
+        let read = || -> Result<Dictionary, ImportError> {
+            let text = std::fs::read_to_string(&md_path).in_file(&md_path)?;
+            let text = self
+                .variables
+                .interpolate(&text)
+                .map_err(FrontmatterError::from)?;
+            let fm = frontmatter::read(&text)?;
+            let links = index::extract_wiki_links(&text);
+            let linked: HashSet<&String> = links.iter().collect();
+            let mut preview = Dictionary::new();
+            preview.set("title", index::document_title(&fm, Path::new(&md_path)));
+            preview.set(
+                "first_paragraph",
+                coverage::first_paragraph(frontmatter::wiki_section(&text)).unwrap_or_default(),
+            );
+            let tags: PackedStringArray = index::extract_tags(&fm, frontmatter::split(&text).1)
+                .iter()
+                .map(GString::from)
+                .collect();
+            preview.set("tags", tags);
+            preview.set("links", links.len() as i64);
+            preview.set("linked_documents", linked.len() as i64);
+            Ok(preview)
+        };
+        read().unwrap_or_else(|e| {
+            push_error(&[Variant::from(format!("{} : {}", md_path, e))]);
+            Dictionary::new()
+        })
+    }
+
+    #[func]
+    /// The headings of a document's doke section, in order : `title`, `level`,
+    /// `index` (from 0), `number` (`1.2.3`) and `line` in the document.
+    fn get_doke_outline(&self, md_path: String) -> Array<Dictionary> {
+        let read = || -> Result<Array<Dictionary>, ImportError> {
+            let raw = frontmatter::read_doke_section(Path::new(&md_path))?;
+            let input = self
+                .variables
+                .interpolate(&raw)
+                .map_err(FrontmatterError::from)?;
+            let body = frontmatter::body(&input).0;
+            let first_line = frontmatter::body(&raw).1;
+            let nodes = DokePipe::new().run_markdown(&input).nodes;
+            let headings = sections::number_headings(&nodes, body);
+            Ok(nodes
+                .iter()
+                .zip(headings)
+                .filter_map(|(node, heading)| {
+                    let heading = heading?;
+                    let mut d = Dictionary::new();
+                    d.set("title", heading.title.as_str());
+                    d.set("level", heading.level as i64);
+                    d.set("index", heading.index as i64);
+                    d.set("number", heading.number.as_str());
+                    let line = body.get(..node.span.start)?.matches('\n').count();
+                    d.set("line", (first_line + line) as i64);
+                    Some(d)
+                })
+                .collect())
+        };
+        read().unwrap_or_else(|e| {
+            push_error(&[Variant::from(format!("{} : {}", md_path, e))]);
+            Array::new()
+        })
+    }
+
     #[func]
     fn import_doke(&self, file_type: String, md_path: String) -> Option<Gd<Resource>> {
-        match self.__import_doke(file_type, md_path) {
+        match self.__import_doke(file_type.clone(), md_path.clone()) {
             Ok(v) => Some(v),
-            Err(e) => {push_error(&[Variant::from(e.to_string())]); None},
+            Err(e) => {self.push_import_error(&file_type, &md_path, &e); None},
         }
     }
 
@@ -78,53 +596,1459 @@ impl DokeImporter {
         file_type: String,
         md_path: String,
     ) -> Result<Gd<Resource>, ImportError> {
-        match self.import_doke_as_gd_value(file_type, md_path) {
-            Ok(value) => {
-                let res = import::godot_value_to_variant(value)?.try_to::<Gd<Resource>>();
-                Ok(res?)
+        let key = format!("{}:{}", file_type, md_path);
+        let source = self.source(&file_type, &md_path);
+        let Some(_lock) = self.locks.lock(&key) else {
+            return Err(ImportError::AlreadyImporting(md_path));
+        };
+        // The watcher, a batch job or a manual call may have imported it this frame
+        if let Some(res) = self.locks.reusable(&key, source.hash()) {
+            return Ok(res);
+        }
+        let (value, frontmatter) =
+            self.import_doke_as_gd_value(file_type.clone(), md_path.clone())?;
+        let document = (value.clone(), frontmatter.clone());
+        let conversion = self.document_conversion(&file_type);
+        let mut res = import::import_top_level_resource(value, frontmatter, None, &conversion)?;
+        self.last_timings.borrow_mut().lap("instantiate");
+        source.stamp(&mut res);
+        self.locks.finish(&key, source.hash(), &res);
+        // Only real imports are changes, not the parses of exports and checks
+        self.history.borrow_mut().record(&md_path, document);
+        Ok(res)
+    }
+
+    #[func]
+    /// Imports a document into the resource saved at `save_path` and saves it there.
+    /// The existing resource is updated in place and keeps its UID, so scenes referencing it
+    /// don't break when the document is edited. Returns 0 on success, -1 on failure.
+    fn import_doke_to(&self, file_type: String, md_path: String, save_path: String) -> i64 {
+        let source = self.source(&file_type, &md_path);
+        let import = || -> Result<(), ImportError> {
+            let (value, frontmatter) =
+                self.import_doke_as_gd_value(file_type.clone(), md_path.clone())?;
+            let conversion = self.document_conversion(&file_type);
+            let path = ResourceLoader::singleton()
+                .exists(&save_path)
+                .then(|| save_path.clone());
+            let mut res = import::import_top_level_resource(value, frontmatter, path, &conversion)?;
+            self.last_timings.borrow_mut().lap("instantiate");
+            source.stamp(&mut res);
+            import::save_resource(&mut res, &save_path)?;
+            self.last_timings.borrow_mut().lap("save");
+            Ok(())
+        };
+        match import() {
+            Ok(()) => 0,
+            Err(e) => {
+                self.push_import_error(&file_type, &md_path, &e);
+                -1
+            }
+        }
+    }
+
+    #[func]
+    /// Imports a document and the other resources it declares in the `outputs:` of its config.
+    /// Returns a Dictionary of output name -> Resource, with the document's own resource as `main`.
+    fn import_doke_outputs(&self, file_type: String, md_path: String) -> Dictionary {
+        let mut resources = Dictionary::new();
+        let mut outputs = Vec::new();
+        let import = || -> Result<(), ImportError> {
+            let source = self.source(&file_type, &md_path);
+            let (value, frontmatter) = self.import_doke_with_outputs(
+                file_type.clone(),
+                md_path.clone(),
+                Some(&mut outputs),
+            )?;
+            let conversion = self.document_conversion(&file_type);
+            for (name, output) in outputs {
+                let mut res = import::import_top_level_resource(
+                    output,
+                    frontmatter.clone(),
+                    None,
+                    &conversion,
+                )?;
+                source.stamp(&mut res);
+                resources.set(name, res);
+            }
+            let mut res = import::import_top_level_resource(value, frontmatter, None, &conversion)?;
+            self.last_timings.borrow_mut().lap("instantiate");
+            source.stamp(&mut res);
+            resources.set(outputs::MAIN_OUTPUT, res);
+            Ok(())
+        };
+        if let Err(e) = import() {
+            self.push_import_error(&file_type, &md_path, &e);
+            return Dictionary::new();
+        }
+        resources
+    }
+
+    #[func]
+    /// Iterates the documents of `file_type` (see `documents:` in the config),
+    /// reading each only when the iterator is asked to.
+    fn iter_documents(&self, file_type: String) -> Option<Gd<DokeVaultIterator>> {
+        let paths = self.configs.get(&file_type).map(|c| c.document_paths());
+        match paths {
+            Some(Ok(paths)) => {
+                let paths = paths
+                    .iter()
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .collect();
+                Some(DokeVaultIterator::create(self.to_gd(), file_type, paths))
+            }
+            Some(Err(e)) => {
+                push_error(&[Variant::from(e.to_string())]);
+                None
+            }
+            None => {
+                push_error(&[Variant::from(ImportError::MissingParserError().to_string())]);
+                None
+            }
+        }
+    }
+
+    #[func]
+    /// Writes a CSV of `fields` for every document of `file_type` (see `documents:` in the config).
+    /// Fields are read from the built resource, then from the frontmatter, and `a.b` reads nested values.
+    /// Returns the number of documents that failed to parse, or -1 if nothing was written.
+    fn export_csv(&self, file_type: String, fields: PackedStringArray, out_path: String) -> i64 {
+        let Some(config) = self.configs.get(&file_type) else {
+            push_error(&[Variant::from(ImportError::MissingParserError().to_string())]);
+            return -1;
+        };
+        let paths = match config.document_paths() {
+            Ok(paths) => paths,
+            Err(e) => {
+                push_error(&[Variant::from(e.to_string())]);
+                return -1;
+            }
+        };
+        let fields: Vec<String> = fields.as_slice().iter().map(GString::to_string).collect();
+
+        let mut out = String::new();
+        let mut header = vec![spreadsheet::PATH_COLUMN.to_string()];
+        header.extend(fields.iter().cloned());
+        spreadsheet::write_row(&mut out, &header);
+
+        let mut failed = 0;
+        for path in paths {
+            let md_path = path.to_string_lossy().into_owned();
+            match self.import_doke_as_gd_value(file_type.clone(), md_path.clone()) {
+                Ok((value, frontmatter)) => {
+                    let mut row = vec![md_path];
+                    for field in &fields {
+                        row.push(spreadsheet::cell(spreadsheet::lookup(
+                            &value,
+                            &frontmatter,
+                            field,
+                        )));
+                    }
+                    spreadsheet::write_row(&mut out, &row);
+                }
+                Err(e) => {
+                    self.push_import_error(&file_type, &md_path, &e);
+                    failed += 1;
+                }
+            }
+        }
+        match std::fs::write(&out_path, out) {
+            Ok(()) => failed,
+            Err(e) => {
+                push_error(&[Variant::from(format!("Can't write {} : {}", out_path, e))]);
+                -1
+            }
+        }
+    }
+
+    #[func]
+    /// Collects the translatable strings of every document of `file_type` (see `localization:`
+    /// in the config) under stable keys, and writes them to `out_path` : a gettext template
+    /// for `.pot`, the source locale's `.po`, or a Translation resource (`.translation`,
+    /// `.tres`, `.res`). Returns the number of strings, or -1 if nothing was written.
+    fn extract_translations(&self, file_type: String, out_path: String) -> i64 {
+        let Some(config) = self.configs.get(&file_type) else {
+            push_error(&[Variant::from(ImportError::MissingParserError().to_string())]);
+            return -1;
+        };
+        if config.localization.is_empty() {
+            push_warning(&[Variant::from(format!(
+                "{} : 'localization' doesn't list anything to extract",
+                file_type
+            ))]);
+        }
+        let paths = match config.document_paths() {
+            Ok(paths) => paths,
+            Err(e) => {
+                push_error(&[Variant::from(e.to_string())]);
+                return -1;
+            }
+        };
+        let settings = ProjectSettings::singleton();
+        let mut entries: Vec<localization::Entry> = Vec::new();
+        for path in paths {
+            let md_path = path.to_string_lossy().into_owned();
+            let extracted = self
+                .import_doke_as_gd_value(file_type.clone(), md_path.clone())
+                .and_then(|(value, frontmatter)| {
+                    let wiki = frontmatter::read_wiki_section(&path)?;
+                    let source = settings.localize_path(&md_path).to_string();
+                    Ok(config.localization.extract(
+                        &index::document_name(&path),
+                        &source,
+                        &value,
+                        &frontmatter,
+                        &wiki,
+                    ))
+                });
+            match extracted {
+                Ok(extracted) => {
+                    for entry in extracted {
+                        if entries.iter().any(|e| e.key == entry.key) {
+                            push_warning(&[Variant::from(format!(
+                                "{} : translation key '{}' is used twice, the first string is kept",
+                                md_path, entry.key
+                            ))]);
+                            continue;
+                        }
+                        entries.push(entry);
+                    }
+                }
+                Err(e) => self.push_import_error(&file_type, &md_path, &e),
+            }
+        }
+        let locale = &config.localization.locale;
+        let written = if out_path.ends_with(".pot") || out_path.ends_with(".po") {
+            let po_locale = out_path.ends_with(".po").then_some(locale.as_str());
+            let path = settings.globalize_path(&out_path).to_string();
+            std::fs::write(&path, localization::to_gettext(&entries, po_locale))
+                .map_err(|e| format!("Can't write {} : {}", path, e))
+        } else {
+            let mut translation = Translation::new_gd();
+            translation.set_locale(locale);
+            for entry in &entries {
+                translation.add_message(&entry.key, &entry.text);
+            }
+            match ResourceSaver::singleton()
+                .save_ex(&translation)
+                .path(&out_path)
+                .done()
+            {
+                godot::global::Error::OK => Ok(()),
+                error => Err(format!("Can't save {} : {:?}", out_path, error)),
+            }
+        };
+        match written {
+            Ok(()) => entries.len() as i64,
+            Err(e) => {
+                push_error(&[Variant::from(e)]);
+                -1
+            }
+        }
+    }
+
+    #[func]
+    /// Writes the values of a CSV (like the one `export_csv` makes) back into the documents of `file_type`.
+    /// Rows are matched by their `id` column if there is one, by `path` otherwise.
+    /// Returns one Dictionary per differing value : `path`, `field`, `old`, `new` and `status`,
+    /// which is `applied`, `dry_run`, `templated` (comes from a `{{variable}}`, left as is),
+    /// `not_found` (couldn't be located in the document) or `unmatched` (row without a document).
+    fn apply_csv(&self, file_type: String, csv_path: String, dry_run: bool) -> Array<Dictionary> {
+        let mut report = Array::new();
+        let Some(config) = self.configs.get(&file_type) else {
+            push_error(&[Variant::from(ImportError::MissingParserError().to_string())]);
+            return report;
+        };
+        let (paths, csv) = match (config.document_paths(), std::fs::read_to_string(&csv_path)) {
+            (Ok(paths), Ok(csv)) => (paths, csv),
+            (Err(e), _) => {
+                push_error(&[Variant::from(e.to_string())]);
+                return report;
+            }
+            (_, Err(e)) => {
+                push_error(&[Variant::from(format!("Can't read {} : {}", csv_path, e))]);
+                return report;
+            }
+        };
+        let mut rows = spreadsheet::parse(&csv).into_iter();
+        let header = rows.next().unwrap_or_default();
+        let key_column = if header.iter().any(|h| h == "id") {
+            "id"
+        } else {
+            spreadsheet::PATH_COLUMN
+        };
+        let Some(key_index) = header.iter().position(|h| h == key_column) else {
+            push_error(&[Variant::from(format!(
+                "{} has no id or path column",
+                csv_path
+            ))]);
+            return report;
+        };
+        let mut rows: HashMap<String, Vec<String>> = rows
+            .filter_map(|row| Some((row.get(key_index)?.clone(), row)))
+            .collect();
+
+        let entry = |path: &str, field: &str, old: &str, new: &str, status: &str| {
+            let mut d = Dictionary::new();
+            d.set("path", path);
+            d.set("field", field);
+            d.set("old", old);
+            d.set("new", new);
+            d.set("status", status);
+            d
+        };
+        for path in paths {
+            let md_path = path.to_string_lossy().into_owned();
+            let (value, frontmatter) =
+                match self.import_doke_as_gd_value(file_type.clone(), md_path.clone()) {
+                    Ok(parsed) => parsed,
+                    Err(e) => {
+                        push_error(&[Variant::from(format!("{} : {}", md_path, e))]);
+                        continue;
+                    }
+                };
+            let key = if key_column == "id" {
+                spreadsheet::cell(spreadsheet::lookup(&value, &frontmatter, "id"))
+            } else {
+                md_path.clone()
+            };
+            let Some(row) = rows.remove(&key) else {
+                continue;
+            };
+            let mut text = match std::fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) => {
+                    push_error(&[Variant::from(format!("Can't read {} : {}", md_path, e))]);
+                    continue;
+                }
+            };
+            let skip = ["id", spreadsheet::PATH_COLUMN];
+            let changes =
+                spreadsheet::apply_row(&mut text, &value, &frontmatter, &header, &row, &skip);
+            let written = changes
+                .iter()
+                .any(|c| c.result == spreadsheet::WriteBack::Written);
+            if written
+                && !dry_run
+                && let Err(e) = std::fs::write(&path, &text)
+            {
+                push_error(&[Variant::from(format!("Can't write {} : {}", md_path, e))]);
+                continue;
+            }
+            for c in changes {
+                let status = match c.result {
+                    spreadsheet::WriteBack::Written if dry_run => "dry_run",
+                    spreadsheet::WriteBack::Written => "applied",
+                    spreadsheet::WriteBack::Templated => "templated",
+                    spreadsheet::WriteBack::NotFound => "not_found",
+                };
+                report.push(&entry(&md_path, &c.field, &c.old, &c.new, status));
+            }
+        }
+        for key in rows.keys() {
+            report.push(&entry(key, key_column, "", "", "unmatched"));
+        }
+        report
+    }
+
+    #[func]
+    /// Checks every document of `file_type` against the `budgets:` of its config.
+    /// Each exceeded budget, and each document that fails to import, is pushed as a warning
+    /// and returned, so a headless CI script can exit with an error when the array isn't empty.
+    fn check_budgets(&self, file_type: String) -> PackedStringArray {
+        let Some(config) = self.configs.get(&file_type) else {
+            push_error(&[Variant::from(ImportError::MissingParserError().to_string())]);
+            return PackedStringArray::new();
+        };
+        if config.budgets.is_empty() {
+            push_error(&[Variant::from(format!(
+                "No budgets in the config of {}",
+                file_type
+            ))]);
+            return PackedStringArray::new();
+        }
+        let paths = match config.document_paths() {
+            Ok(paths) => paths,
+            Err(e) => {
+                push_error(&[Variant::from(e.to_string())]);
+                return PackedStringArray::new();
+            }
+        };
+        let mut documents = Vec::new();
+        let mut diagnostics = Vec::new();
+        for path in paths {
+            let md_path = path.to_string_lossy().into_owned();
+            match self.import_doke_as_gd_value(file_type.clone(), md_path.clone()) {
+                Ok((value, frontmatter)) => documents.push((path, value, frontmatter)),
+                Err(e) => diagnostics.push(format!("{} : {}", md_path, e)),
+            }
+        }
+        diagnostics.extend(config.budgets.check(&documents));
+        for d in &diagnostics {
+            push_warning(&[Variant::from(d.as_str())]);
+        }
+        diagnostics.iter().map(GString::from).collect()
+    }
+
+    /// The sentence patterns of `file_type`, loaded on first use.
+    fn cached_patterns(
+        &self,
+        file_type: &str,
+        config: &ImportConfig,
+    ) -> Result<Rc<Vec<grammar::Pattern>>, ConfigError> {
+        if let Some(patterns) = self.patterns.borrow().get(file_type) {
+            return Ok(patterns.clone());
+        }
+        let patterns = Rc::new(grammar::load_patterns(config)?);
+        self.patterns
+            .borrow_mut()
+            .insert(file_type.to_string(), patterns.clone());
+        Ok(patterns)
+    }
+
+    #[func]
+    /// Indexes every document of the loaded file types (see `documents:` in their configs)
+    /// and saves the DokeIndex to `out_path`, for games to enumerate their content at
+    /// runtime. Reads frontmatters and links, without building the documents, so call it
+    /// at the end of a batch import. Returns the number of documents indexed, or -1 if
+    /// nothing was saved.
+    fn save_doke_index(&self, out_path: String) -> i64 {
+        let settings = ProjectSettings::singleton();
+        let mut file_types: Vec<_> = self.configs.iter().collect();
+        file_types.sort_by_key(|(name, _)| name.as_str());
+        let mut documents = Array::<Dictionary>::new();
+        let mut terms = search::Terms::new();
+        for (file_type, config) in file_types {
+            let paths = match config.document_paths() {
+                Ok(paths) => paths,
+                Err(e) => {
+                    push_error(&[Variant::from(format!("{} : {}", file_type, e))]);
+                    continue;
+                }
+            };
+            for path in paths {
+                let md_path = path.to_string_lossy().into_owned();
+                let mut read = || -> Result<Dictionary, ImportError> {
+                    let text = std::fs::read_to_string(&path).in_file(&path)?;
+                    let fm = frontmatter::read(&text)?;
+                    let id = match fm.get("id") {
+                        Some(GodotValue::String(id)) => id.clone(),
+                        Some(GodotValue::Int(id)) => id.to_string(),
+                        _ => index::document_name(&path),
+                    };
+                    let tags: PackedStringArray =
+                        index::extract_tags(&fm, frontmatter::split(&text).1)
+                            .iter()
+                            .map(GString::from)
+                            .collect();
+                    let links: PackedStringArray = index::extract_wiki_links(&text)
+                        .iter()
+                        .map(GString::from)
+                        .collect();
+                    let mut d = Dictionary::new();
+                    d.set("id", id);
+                    d.set("path", settings.localize_path(&md_path));
+                    d.set("type", file_type.as_str());
+                    d.set("tags", tags);
+                    d.set("links", links);
+                    d.set("hash", FileAccess::get_sha256(&md_path));
+                    d.set(
+                        "frontmatter",
+                        import::frontmatter_to_dictionary(&fm, &Default::default())?,
+                    );
+                    search::add_document(&mut terms, documents.len() as i64, &text);
+                    Ok(d)
+                };
+                match read() {
+                    Ok(d) => documents.push(&d),
+                    Err(e) => push_error(&[Variant::from(format!("{} : {}", md_path, e))]),
+                }
+            }
+        }
+        let count = documents.len() as i64;
+        let index = DokeIndex::create(documents, search::to_dictionary(&terms));
+        match ResourceSaver::singleton()
+            .save_ex(&index)
+            .path(&out_path)
+            .done()
+        {
+            godot::global::Error::OK => count,
+            error => {
+                push_error(&[Variant::from(format!(
+                    "Can't save {} : {:?}",
+                    out_path, error
+                ))]);
+                -1
+            }
+        }
+    }
+
+    #[func]
+    /// What an editor plugin or language server needs to complete documents of the loaded
+    /// file types : `documents`, every document as `{name, path, type}`, `link_targets`,
+    /// file type -> names of its documents, for `[[` completion, and `templates`,
+    /// file type -> its sentence patterns as `{type, section, pattern}`. Documents come
+    /// from the `documents:` of each file type.
+    fn get_completion_data(&self) -> Dictionary {
+        let settings = ProjectSettings::singleton();
+        let mut file_types: Vec<_> = self.configs.iter().collect();
+        file_types.sort_by_key(|(name, _)| name.as_str());
+        let mut documents = Array::<Dictionary>::new();
+        let mut link_targets = Dictionary::new();
+        let mut templates = Dictionary::new();
+        for (file_type, config) in file_types {
+            let paths = match config.document_paths() {
+                Ok(paths) => paths,
+                Err(e) => {
+                    push_warning(&[Variant::from(format!("{} : {}", file_type, e))]);
+                    Vec::new()
+                }
+            };
+            let mut names = PackedStringArray::new();
+            for path in &paths {
+                let name = index::document_name(path);
+                let mut d = Dictionary::new();
+                d.set("name", name.as_str());
+                d.set(
+                    "path",
+                    settings.localize_path(path.to_string_lossy().as_ref()),
+                );
+                d.set("type", file_type.as_str());
+                documents.push(&d);
+                names.push(name.as_str());
+            }
+            link_targets.set(file_type.as_str(), names);
+            let patterns: Array<Dictionary> = match self.cached_patterns(file_type, config) {
+                Ok(patterns) => patterns
+                    .iter()
+                    .map(|p| {
+                        let mut d = Dictionary::new();
+                        d.set("type", p.target.as_str());
+                        d.set("section", p.section.as_str());
+                        d.set("pattern", p.pattern.as_str());
+                        d
+                    })
+                    .collect(),
+                Err(e) => {
+                    push_warning(&[Variant::from(format!("{} : {}", file_type, e))]);
+                    Array::new()
+                }
+            };
+            templates.set(file_type.as_str(), patterns);
+        }
+        let mut data = Dictionary::new();
+        data.set("documents", documents);
+        data.set("link_targets", link_targets);
+        data.set("templates", templates);
+        data
+    }
+
+    #[func]
+    /// Explains why a statement doesn't match the sentence patterns of `file_type`.
+    /// Returns one Dictionary per pattern, closest first : `type`, `section`, `pattern`,
+    /// `matched`, `matched_text` (the part of the line the pattern accepted),
+    /// `remaining` (where it diverged) and `expected` (what the pattern wanted there).
+    /// Parent/children restrictions of the rules aren't taken into account.
+    fn explain_no_match(&self, file_type: String, line: String) -> Array<Dictionary> {
+        let Some(config) = self.configs.get(&file_type) else {
+            push_error(&[Variant::from(ImportError::MissingParserError().to_string())]);
+            return Array::new();
+        };
+        let patterns = match self.cached_patterns(&file_type, config) {
+            Ok(patterns) => patterns,
+            Err(e) => {
+                push_error(&[Variant::from(e.to_string())]);
+                return Array::new();
+            }
+        };
+        let statement = grammar::clean_statement(&line);
+        let mut explanations: Vec<_> = patterns.iter().map(|p| p.explain(statement)).collect();
+        explanations.sort_by_key(|e| {
+            (
+                std::cmp::Reverse(e.matched),
+                std::cmp::Reverse(e.matched_len),
+            )
+        });
+        explanations
+            .iter()
+            .map(|e| {
+                let mut d = Dictionary::new();
+                d.set("type", e.pattern.target.as_str());
+                d.set("section", e.pattern.section.as_str());
+                d.set("pattern", e.pattern.pattern.as_str());
+                d.set("matched", e.matched);
+                d.set("matched_text", &statement[..e.matched_len]);
+                d.set("remaining", &statement[e.matched_len..]);
+                d.set("expected", e.expected.unwrap_or_default());
+                d
+            })
+            .collect()
+    }
+
+    /// Emitted by `poll_watched` for each re-imported document,
+    /// with its resource (null on failure) and the error (empty on success).
+    #[signal]
+    fn document_reimported(file_type: GString, path: GString, resource: Variant, error: GString);
+
+    #[func]
+    /// Watches the documents of `file_type` (see `documents:` in the config), for `poll_watched`.
+    fn watch_file_type(&mut self, file_type: String) -> i64 {
+        let paths = self.configs.get(&file_type).map(|c| c.document_paths());
+        match paths {
+            Some(Ok(paths)) => {
+                self.watcher.watch(&file_type, paths);
+                0
+            }
+            Some(Err(e)) => {
+                push_error(&[Variant::from(e.to_string())]);
+                1
+            }
+            None => {
+                push_error(&[Variant::from(ImportError::MissingParserError().to_string())]);
+                1
+            }
+        }
+    }
+
+    #[func]
+    fn unwatch_file_type(&mut self, file_type: String) -> bool {
+        self.watcher.unwatch(&file_type)
+    }
+
+    #[func]
+    /// Opens the document of the last failed watched re-import, see `watch::open_at`.
+    /// Returns false if no re-import failed or it can't be opened.
+    fn jump_to_watch_error(&self) -> bool {
+        match &self.watcher.last_error {
+            Some((path, line)) => watch::open_at(path, *line),
+            None => false,
+        }
+    }
+
+    #[func]
+    /// Re-imports the watched documents that are new or were modified since the last poll,
+    /// emitting `document_reimported` for each and showing a toast in the editor.
+    /// Cheap enough to call from a Timer. Returns the re-imported paths.
+    fn poll_watched(&mut self) -> PackedStringArray {
+        let mut reimported = PackedStringArray::new();
+        for file_type in self.watcher.file_types() {
+            let paths = match self.configs.get(&file_type).map(|c| c.document_paths()) {
+                Some(Ok(paths)) => paths,
+                Some(Err(e)) => {
+                    push_error(&[Variant::from(e.to_string())]);
+                    continue;
+                }
+                None => continue,
+            };
+            for path in self.watcher.changed(&file_type, paths) {
+                let md_path = path.to_string_lossy().into_owned();
+                let (resource, error) = match self.__import_doke(file_type.clone(), md_path.clone())
+                {
+                    Ok(res) => {
+                        if self
+                            .watcher
+                            .last_error
+                            .as_ref()
+                            .is_some_and(|(path, _)| *path == md_path)
+                        {
+                            self.watcher.last_error = None;
+                        }
+                        watch::toast(&md_path, None);
+                        (res.to_variant(), String::new())
+                    }
+                    Err(e) => {
+                        self.push_import_error(&file_type, &md_path, &e);
+                        let line = Diagnostic::from_import_error(&md_path, &e)
+                            .iter()
+                            .find_map(|d| d.span.as_ref().map(|s| s.start_line))
+                            .unwrap_or(1);
+                        self.watcher.last_error = Some((md_path.clone(), line));
+                        let error = e.to_string();
+                        watch::toast(&md_path, Some((error.as_str(), line)));
+                        (Variant::nil(), error)
+                    }
+                };
+                self.base_mut().emit_signal(
+                    "document_reimported",
+                    &[
+                        file_type.to_variant(),
+                        md_path.to_variant(),
+                        resource,
+                        error.to_variant(),
+                    ],
+                );
+                reimported.push(md_path.as_str());
+            }
+        }
+        reimported
+    }
+
+    #[func]
+    /// What changed in a document since it was last imported : parses and builds it again
+    /// and returns its changed values, path (`stats.damage`, `abilities.0`, `frontmatter.tags`)
+    /// -> `{change, old, new}`, `change` being `added`, `removed` or `changed`.
+    /// Empty the first time a document is imported.
+    fn diff_doke(&self, file_type: String, md_path: String) -> Dictionary {
+        match self.import_doke_as_gd_value(file_type.clone(), md_path.clone()) {
+            Ok(_) => self.get_doke_changes(md_path),
+            Err(e) => {
+                self.push_import_error(&file_type, &md_path, &e);
+                Dictionary::new()
+            }
+        }
+    }
+
+    #[func]
+    /// The changes of the last import of a document from the one before, like `diff_doke`
+    /// returns them, for `document_reimported` listeners.
+    fn get_doke_changes(&self, md_path: String) -> Dictionary {
+        diff::to_dictionary(self.history.borrow().changes(&md_path))
+    }
+
+    #[func]
+    /// Forgets the nested resources shared by `dedup: true` file types,
+    /// so the next imports build them fresh, e.g. after editing the documents.
+    fn clear_resource_cache(&self) {
+        self.resource_cache.borrow_mut().clear();
+    }
+
+    #[func]
+    /// Counts and durations of the imports since the stats were last saved :
+    /// file type -> Dictionary of `documents`, `errors`, `total_ms` and `average_ms`.
+    fn get_import_stats(&self) -> Dictionary {
+        self.stats.borrow().to_dictionary()
+    }
+
+    #[func]
+    /// How long each stage of the last import took, to find what makes imports slow :
+    /// `file_type`, `path`, `total_ms` and `stages`, a Dictionary of stage -> ms in order
+    /// (`read`, `frontmatter`, `parse`, `checks`, `validate`, `build`, `instantiate`, `save`).
+    /// A failed import only has the stages before the one that failed.
+    fn get_last_import_stats(&self) -> Dictionary {
+        self.last_timings.borrow().to_dictionary()
+    }
+
+    #[func]
+    /// Appends the stats of the imports since the last save to `.godot/doke_stats/history.csv`,
+    /// one row per file type, and starts counting again. Call it at the end of a batch import.
+    fn save_import_stats(&self) -> i64 {
+        match self.stats.borrow_mut().save(&stats::history_path()) {
+            Ok(()) => 0,
+            Err(e) => {
+                push_error(&[Variant::from(format!("Can't save import stats : {}", e))]);
+                1
+            }
+        }
+    }
+
+    #[func]
+    /// The saved stats of `file_type` (every type if empty), oldest first, to follow trends :
+    /// Dictionaries like `get_import_stats` ones, with their `timestamp` and `file_type`.
+    fn get_import_stats_history(&self, file_type: String) -> Array<Dictionary> {
+        stats::read_history(&stats::history_path(), &file_type).unwrap_or_else(|e| {
+            push_error(&[Variant::from(format!("Can't read import stats : {}", e))]);
+            Array::new()
+        })
+    }
+
+    #[func]
+    /// Reports every rejected or unmatched statement of a document at once, with its lines,
+    /// instead of stopping at the first one. Slower, meant for authors fixing a document.
+    fn set_collect_all_errors(&mut self, enabled: bool) {
+        self.collect_errors = enabled;
+    }
+
+    #[func]
+    /// The problems of the last import, empty if it succeeded.
+    fn get_last_diagnostics(&self) -> Array<Gd<DokeDiagnostic>> {
+        diagnostic::to_array(&self.last_diagnostics.borrow())
+    }
+
+    #[func]
+    /// Parses and builds a document without making its resource, and returns its problems
+    /// instead of pushing them. Empty when the document imports.
+    fn validate_document(&self, file_type: String, md_path: String) -> Array<Gd<DokeDiagnostic>> {
+        let _ = self.import_doke_as_gd_value(file_type, md_path);
+        self.get_last_diagnostics()
+    }
+
+    #[func]
+    /// Writes the parsed structure of a document to `out_path` as JSON, for pipelines outside
+    /// Godot : `path`, `file_type`, `frontmatter`, the `nodes` after the parsers ran (with their
+    /// `statement`, place, `state`, value, `constituents` and `children`) and the `links`,
+    /// `{target, path}`, resolved among the `documents:` of the file type, or the document's
+    /// folder without them. Returns 0 on success, -1 on failure.
+    fn export_doke_json(&self, file_type: String, md_path: String, out_path: String) -> i64 {
+        let export = || -> Result<String, ImportError> {
+            let Some(parser) = self.parsers.get(&file_type) else {
+                return Err(ImportError::MissingParserError());
+            };
+            let mut trace = Trace::new(false);
+            let (raw_source, input) = self.prepare_document(
+                &file_type,
+                &md_path,
+                &mut trace,
+                &mut StageTimings::default(),
+            )?;
+            let first_line = frontmatter::body(&raw_source).1;
+            let (doc, _) =
+                self.parse_prepared(parser, &file_type, &md_path, &input, first_line, &mut trace)?;
+            let body = frontmatter::body(&input).0;
+            let candidates = match self.configs.get(&file_type).map(|c| c.document_paths()) {
+                Some(Ok(paths)) => paths,
+                _ => export::sibling_documents(&md_path),
+            };
+            Ok(export::to_json(
+                &md_path,
+                &file_type,
+                &doc,
+                body,
+                first_line,
+                &candidates,
+            ))
+        };
+        let json = match export() {
+            Ok(json) => json,
+            Err(e) => {
+                self.push_import_error(&file_type, &md_path, &e);
+                return -1;
+            }
+        };
+        let out_path = ProjectSettings::singleton()
+            .globalize_path(&out_path)
+            .to_string();
+        match std::fs::write(&out_path, json) {
+            Ok(()) => 0,
+            Err(e) => {
+                push_error(&[Variant::from(format!("Can't write {} : {}", out_path, e))]);
+                -1
+            }
+        }
+    }
+
+    /// The markdown of a document of `file_type` with `frontmatter` and the statements of `value`.
+    fn write_document(
+        &self,
+        file_type: &str,
+        frontmatter: &Dictionary,
+        value: &Variant,
+    ) -> Result<String, String> {
+        let Some(config) = self.configs.get(file_type) else {
+            return Err(ImportError::MissingParserError().to_string());
+        };
+        let patterns = self
+            .cached_patterns(file_type, config)
+            .map_err(|e| e.to_string())?;
+        let writer = writer::Writer {
+            patterns: &patterns,
+            naming: &config.naming,
+        };
+        let write = || -> Result<String, writer::WriteError> {
+            let value = writer::value_of(value)?;
+            let statements = writer.statements(&config.children, &value)?;
+            Ok(writer::document(
+                &writer::frontmatter(frontmatter)?,
+                &statements,
+            ))
+        };
+        write().map_err(|e| e.to_string())
+    }
+
+    #[func]
+    /// Writes `value` back as a document of `file_type` : `frontmatter`, then a statement for
+    /// each value of the root's `children:` fields, written with the sentence patterns.
+    /// `value` is a built resource or its plain form (`{type, fields}`). Returns the
+    /// markdown, empty on failure.
+    fn write_doke(&self, file_type: String, frontmatter: Dictionary, value: Variant) -> GString {
+        match self.write_document(&file_type, &frontmatter, &value) {
+            Ok(markdown) => markdown.into(),
+            Err(e) => {
+                push_error(&[Variant::from(e)]);
+                GString::new()
+            }
+        }
+    }
+
+    #[func]
+    /// Like `write_doke`, replacing the frontmatter and statements of `md_path` and keeping
+    /// what follows its doke section. Returns 0 on success, -1 on failure.
+    fn write_doke_file(
+        &self,
+        file_type: String,
+        md_path: String,
+        frontmatter: Dictionary,
+        value: Variant,
+    ) -> i64 {
+        let path = ProjectSettings::singleton()
+            .globalize_path(&md_path)
+            .to_string();
+        let write = || -> Result<(), String> {
+            let document = self.write_document(&file_type, &frontmatter, &value)?;
+            let text = match std::fs::read_to_string(&path) {
+                Ok(text) => writer::replace_document(&text, &document),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => document,
+                Err(e) => return Err(format!("Can't read {} : {}", path, e)),
+            };
+            std::fs::write(&path, text).map_err(|e| format!("Can't write {} : {}", path, e))
+        };
+        match write() {
+            Ok(()) => 0,
+            Err(e) => {
+                push_error(&[Variant::from(e)]);
+                -1
             }
-            Err(_) => todo!(),
         }
     }
 
+    #[func]
+    /// Writes a document as a standalone HTML page to `out_path` : its frontmatter, its statements
+    /// as nested lists classed by state (`doke-resolved`, `doke-error`...) and its wiki part.
+    /// `[[wiki links]]` point to `html_links:` of the file type (`{name}.html` by default).
+    /// Returns 0 on success, -1 on failure.
+    fn export_doke_html(&self, file_type: String, md_path: String, out_path: String) -> i64 {
+        let export = || -> Result<String, ImportError> {
+            let Some(parser) = self.parsers.get(&file_type) else {
+                return Err(ImportError::MissingParserError());
+            };
+            let mut trace = Trace::new(false);
+            let (raw_source, input) = self.prepare_document(
+                &file_type,
+                &md_path,
+                &mut trace,
+                &mut StageTimings::default(),
+            )?;
+            let first_line = frontmatter::body(&raw_source).1;
+            let (doc, _) =
+                self.parse_prepared(parser, &file_type, &md_path, &input, first_line, &mut trace)?;
+            let wiki = frontmatter::read_wiki_section(Path::new(&md_path))?;
+            let title = index::document_title(&doc.frontmatter, Path::new(&md_path));
+            let links = self
+                .configs
+                .get(&file_type)
+                .and_then(|c| c.html_links.as_deref())
+                .unwrap_or(html::DEFAULT_LINK_URL);
+            Ok(html::to_html(&title, &doc, &wiki, links))
+        };
+        let page = match export() {
+            Ok(page) => page,
+            Err(e) => {
+                self.push_import_error(&file_type, &md_path, &e);
+                return -1;
+            }
+        };
+        let out_path = ProjectSettings::singleton()
+            .globalize_path(&out_path)
+            .to_string();
+        match std::fs::write(&out_path, page) {
+            Ok(()) => 0,
+            Err(e) => {
+                push_error(&[Variant::from(format!("Can't write {} : {}", out_path, e))]);
+                -1
+            }
+        }
+    }
+
+    #[func]
+    /// Imports every document of `file_type` (see `documents:` in the config) and writes their
+    /// diagnostics to `out_path` as JSON : `{documents, errors, warnings, diagnostics}`, each
+    /// diagnostic having the fields of a DokeDiagnostic. Returns the number of errors, or -1
+    /// if nothing was written.
+    fn export_diagnostics_json(&self, file_type: String, out_path: String) -> i64 {
+        let Some(config) = self.configs.get(&file_type) else {
+            push_error(&[Variant::from(ImportError::MissingParserError().to_string())]);
+            return -1;
+        };
+        let paths = match config.document_paths() {
+            Ok(paths) => paths,
+            Err(e) => {
+                push_error(&[Variant::from(e.to_string())]);
+                return -1;
+            }
+        };
+        let mut diagnostics = Vec::new();
+        for path in &paths {
+            let md_path = path.to_string_lossy().into_owned();
+            let _ = self.import_doke_as_gd_value(file_type.clone(), md_path);
+            diagnostics.extend(self.last_diagnostics.borrow().iter().cloned());
+        }
+        let errors = diagnostics
+            .iter()
+            .filter(|d| d.severity == diagnostic::Severity::Error)
+            .count();
+        let out_path = ProjectSettings::singleton()
+            .globalize_path(&out_path)
+            .to_string();
+        match std::fs::write(&out_path, diagnostic::to_json(paths.len(), &diagnostics)) {
+            Ok(()) => errors as i64,
+            Err(e) => {
+                push_error(&[Variant::from(format!("Can't write {} : {}", out_path, e))]);
+                -1
+            }
+        }
+    }
+
+    #[func]
+    /// Enables recording every stage of the next imports, see `get_last_trace`.
+    fn set_trace_enabled(&mut self, enabled: bool) {
+        self.tracing = enabled;
+    }
+
+    #[func]
+    /// The stages of the last import when tracing is enabled, in the order of
+    /// `get_trace_stages`. Each is a Dictionary with the `stage` name and the `text`
+    /// of the document at that point.
+    fn get_last_trace(&self) -> Array<Dictionary> {
+        self.last_trace.borrow().to_array()
+    }
+
+    #[func]
+    /// The names of the stages `get_last_trace` can have, in order.
+    fn get_trace_stages(&self) -> PackedStringArray {
+        trace::STAGES.iter().map(|s| GString::from(*s)).collect()
+    }
+
+    /// Parses and builds a document, returning the built value and its frontmatter.
     fn import_doke_as_gd_value(
         &self,
         file_type: String,
         md_path: String,
-    ) -> Result<GodotValue, ImportError> {
+    ) -> Result<(GodotValue, HashMap<String, GodotValue>), ImportError> {
+        self.import_doke_with_outputs(file_type, md_path, None)
+    }
+
+    /// Same as import_doke_as_gd_value, also building the `outputs:` of the config into `outputs`.
+    fn import_doke_with_outputs(
+        &self,
+        file_type: String,
+        md_path: String,
+        outputs: Option<&mut Vec<(String, GodotValue)>>,
+    ) -> Result<(GodotValue, HashMap<String, GodotValue>), ImportError> {
+        let mut trace = Trace::new(self.tracing);
+        let start = Instant::now();
+        let mut timings = StageTimings::start(&file_type, &md_path);
+        let mut warnings = Vec::new();
+        let result = self.import_doke_traced(
+            file_type.clone(),
+            md_path.clone(),
+            &mut trace,
+            &mut timings,
+            &mut warnings,
+            outputs,
+        );
+        if let Err(e) = &result {
+            trace.record("error", || e.to_string());
+        }
+        self.reword(&file_type, &mut warnings);
+        if !warnings.is_empty() {
+            let source = std::fs::read_to_string(&md_path).ok();
+            for warning in &warnings {
+                let rendered = diagnostic::render(warning, source.as_deref());
+                push_warning(&[Variant::from(rendered)]);
+            }
+        }
+        self.stats
+            .borrow_mut()
+            .record(&file_type, start.elapsed(), result.is_ok());
+        if self.tracing {
+            self.last_trace.replace(trace);
+        }
+        self.last_timings.replace(timings);
+        if let Err(e) = &result {
+            let mut errors = Diagnostic::from_import_error(&md_path, e);
+            self.reword(&file_type, &mut errors);
+            warnings.extend(errors);
+        }
+        self.last_diagnostics.replace(warnings);
+        result
+    }
+
+    /// Pushes an import error with the lines of the document it is about.
+    fn push_import_error(&self, file_type: &str, md_path: &str, error: &ImportError) {
+        let mut diagnostics = Diagnostic::from_import_error(md_path, error);
+        self.reword(file_type, &mut diagnostics);
+        let source = std::fs::read_to_string(md_path).ok();
+        for d in &diagnostics {
+            push_error(&[Variant::from(diagnostic::render(d, source.as_deref()))]);
+        }
+    }
+
+    /// Words the diagnostics with the `messages:` of `file_type`, in the editor's locale.
+    fn reword(&self, file_type: &str, diagnostics: &mut [Diagnostic]) {
+        let Some(config) = self.configs.get(file_type) else {
+            return;
+        };
+        if config.messages.is_empty() || diagnostics.is_empty() {
+            return;
+        }
+        let locale = TranslationServer::singleton().get_locale().to_string();
+        for d in diagnostics {
+            config.messages.apply(d, &locale);
+        }
+    }
+
+    /// Same as `conversion`, right after importing a document, with where its resources
+    /// come from so conversion errors have a line.
+    fn document_conversion(&self, file_type: &str) -> Conversion {
+        let mut conversion = self.conversion(file_type);
+        conversion.spans = self.last_spans.take();
+        conversion
+    }
+
+    /// How the values of `file_type` become Godot objects.
+    fn conversion(&self, file_type: &str) -> Conversion {
+        let Some(config) = self.configs.get(file_type) else {
+            return Conversion::default();
+        };
+        let mut conversion = config.conversion();
+        if config.dedup {
+            conversion.resource_cache = Some(self.resource_cache.clone());
+            conversion.cache_scope = file_type.to_string();
+        }
+        conversion
+    }
+
+    /// The project's `{{variables}}` with those of the file type's config over them.
+    fn variables_of(&self, file_type: &str) -> Cow<'_, Variables> {
+        match self.configs.get(file_type) {
+            Some(config) if !config.variables.is_empty() => {
+                let mut variables = self.variables.clone();
+                variables.extend(&config.variables);
+                Cow::Owned(variables)
+            }
+            _ => Cow::Borrowed(&self.variables),
+        }
+    }
+
+    /// What a document is imported from : its content, the file type's parsers, and the
+    /// `{{variables}}` and extended documents it is prepared with, see [`Source::hash`].
+    fn source(&self, file_type: &str, md_path: &str) -> Source {
+        let variables = self.variables_of(file_type);
+        let prepare = |text: String| -> Result<String, FrontmatterError> {
+            Ok(variables.interpolate(&text)?)
+        };
+        let limits = self
+            .configs
+            .get(file_type)
+            .map_or(Limits::default(), |c| c.limits());
+        // A broken chain fails the import itself, with the error
+        let extended = frontmatter::read_doke_section(Path::new(md_path))
+            .ok()
+            .and_then(|raw| prepare(raw).ok())
+            .and_then(|input| {
+                let context = project_context(md_path, &limits);
+                frontmatter::extended_documents(&input, &context, &prepare).ok()
+            })
+            .unwrap_or_default();
+        Source::of(
+            md_path,
+            self.parser_versions(file_type),
+            &variables,
+            &extended,
+        )
+    }
+
+    /// Reads a document and prepares it for the parsers : pre-parse hooks, `{{variables}}`,
+    /// `extends` and frontmatter rules. Returns the text as the hooks left it, which spans
+    /// point into, and the prepared text.
+    fn prepare_document(
+        &self,
+        file_type: &str,
+        md_path: &str,
+        trace: &mut Trace,
+        timings: &mut StageTimings,
+    ) -> Result<(String, String), ImportError> {
         // Only process .md files
         if !md_path.ends_with(".md") {
             return Err(ImportError::InvalidExtension(md_path.to_string()));
         }
 
-        let mut input = String::new();
-        // Open the file
-        let file = std::fs::File::open(&md_path)?;
-        let reader = std::io::BufReader::new(file);
+        let config = self.configs.get(file_type);
+        let variables = self.variables_of(file_type);
+        let prepare = |text: String| -> Result<String, FrontmatterError> {
+            Ok(variables.interpolate(&text)?)
+        };
 
-        let mut separator_count = 0;
+        let limits = config.map_or(Limits::default(), |c| c.limits());
+        let max_file_size = limits.max_file_size;
+        let size = std::fs::metadata(md_path).in_file(md_path)?.len();
+        if size > max_file_size as u64 {
+            return Err(ImportError::FileTooLarge(size, max_file_size));
+        }
+        let raw = frontmatter::read_doke_section(Path::new(md_path))?;
+        timings.lap("read");
+        trace.record("raw", || raw.clone());
+        let raw = self.hooks.pre_parse(file_type, md_path, raw)?;
+        trace.record("pre_parse", || raw.clone());
+        let raw_source = raw.clone();
+        let mut input = prepare(raw)?;
+        trace.record("variables", || input.clone());
+        // Extended documents are read by path, which safe mode doesn't allow
+        if config.is_some_and(|c| c.safe_mode.is_some())
+            && let Some(target) = frontmatter::extends_target(&input)?
+        {
+            return Err(SafeModeError::PathLoading(target).into());
+        }
+        let inherit_body = config.is_some_and(|c| c.inherit_body);
+        let context = project_context(md_path, &limits);
+        input = frontmatter::resolve_extends(&input, &context, inherit_body, &prepare)?;
+        trace.record("extends", || input.clone());
 
-        for line in reader.lines() {
-            let line = line?;
-            if line.trim() == "---" {
-                separator_count += 1;
-                if separator_count == 3 {
-                    break; // stop reading after the third "---"
-                }
-            }
-            input.push_str(&line);
-            input.push('\n');
+        if let Some(config) = config
+            && config.has_frontmatter_rules()
+        {
+            input = frontmatter::rewrite(&input, |fm| config.apply_frontmatter_rules(fm))?;
+            trace.record("frontmatter_rules", || input.clone());
         }
+        timings.lap("frontmatter");
+        Ok((raw_source, input))
+    }
+
+    /// Runs the pipe of a file type over a prepared document, once its markdown
+    /// is known to be within the `limits`. Returns the document and the mdast of its body.
+    /// `first_line` is the line the body starts at in the source, see [`Self::prepare_document`].
+    fn parse_prepared(
+        &self,
+        parser: &DokePipe,
+        file_type: &str,
+        md_path: &str,
+        input: &str,
+        first_line: usize,
+        trace: &mut Trace,
+    ) -> Result<(DokeDocument, Option<markdown::mdast::Node>), ImportError> {
+        let limits = self
+            .configs
+            .get(file_type)
+            .map_or(Limits::default(), |c| c.limits());
+        let body = frontmatter::body(input).0;
+        let mdast = coverage::parse(body);
+        if let Some(root) = &mdast {
+            validation::check_markdown_size(md_path, root, first_line, &limits)?;
+        }
+        // The statements before any parser ran, only parsed when tracing
+        trace.record("nodes", || {
+            trace::format_nodes(&DokePipe::new().run_markdown(input).nodes)
+        });
+        let _context = parser_context::enter(project_context(md_path, &limits));
+        Ok((parser.run_markdown(input), mdast))
+    }
+
+    fn import_doke_traced(
+        &self,
+        file_type: String,
+        md_path: String,
+        trace: &mut Trace,
+        timings: &mut StageTimings,
+        warnings: &mut Vec<Diagnostic>,
+        outputs: Option<&mut Vec<(String, GodotValue)>>,
+    ) -> Result<(GodotValue, HashMap<String, GodotValue>), ImportError> {
+        let config = self.configs.get(&file_type);
+        let (raw_source, input) = self.prepare_document(&file_type, &md_path, trace, timings)?;
 
         // Get the parser for this file type
         if let Some(parser) = self.parsers.get(&file_type)
             && let Some(builder) = self.builders.get(&file_type)
         {
-            let parsed = parser.validate(&input)?;
-            let final_value = builder.build_file_resource(parsed)?;
-            Ok(final_value)
+            // Spans are reported on the source's lines, the rewritten frontmatter
+            // may not have as many lines as the one written
+            let first_line = frontmatter::body(&raw_source).1;
+            let (mut doc, mdast) =
+                self.parse_prepared(parser, &file_type, &md_path, &input, first_line, trace)?;
+            timings.lap("parse");
+            let body = frontmatter::body(&input).0;
+            let builder = match config.and_then(|c| c.dispatch.as_ref()) {
+                Some(dispatch) => dispatch.builder_for(&doc.frontmatter)?.unwrap_or(builder),
+                None => builder,
+            };
+            let dropped = mdast
+                .as_ref()
+                .map(|root| coverage::dropped_constructs(root, body))
+                .unwrap_or_default();
+            if !dropped.is_empty() {
+                let warning = coverage::describe(&dropped);
+                trace.record("dropped", || warning.clone());
+                warnings.push(Diagnostic::warning(
+                    &md_path,
+                    diagnostic::DROPPED_MARKDOWN,
+                    warning,
+                    None,
+                ));
+            }
+            for heading in sections::empty_headings(&doc.nodes, body) {
+                let span = span::SourceSpan::of_range(
+                    body,
+                    heading.span.start,
+                    heading.span.end,
+                    first_line,
+                );
+                let message = format!(
+                    "no statement under the heading '{}'",
+                    heading.statement.trim()
+                );
+                warnings.push(Diagnostic::warning(
+                    &md_path,
+                    diagnostic::EMPTY_HEADING,
+                    message,
+                    Some(span),
+                ));
+            }
+            if let Some(config) = config {
+                let unknown = config.unknown_frontmatter_keys(&doc.frontmatter);
+                if !unknown.is_empty() {
+                    let message = format!("unknown frontmatter keys {}", unknown.join(", "));
+                    let span = validation::frontmatter_span(&raw_source);
+                    warnings.push(Diagnostic::warning(
+                        &md_path,
+                        diagnostic::UNKNOWN_FRONTMATTER_KEYS,
+                        message,
+                        Some(span),
+                    ));
+                }
+                let mut lint_errors = Vec::new();
+                let findings = match &mdast {
+                    Some(root) => config.lint.check(root, body, first_line),
+                    None => Vec::new(),
+                };
+                for finding in findings {
+                    match finding.severity {
+                        diagnostic::Severity::Warning => warnings.push(Diagnostic::warning(
+                            &md_path,
+                            finding.code,
+                            finding.message,
+                            Some(finding.span),
+                        )),
+                        diagnostic::Severity::Error => lint_errors
+                            .push(ValidationError::Lint(md_path.clone(), Box::new(finding))),
+                    }
+                }
+                match lint_errors.len() {
+                    0 => {}
+                    1 => return Err(lint_errors.swap_remove(0).into()),
+                    _ => return Err(ValidationError::Many(lint_errors).into()),
+                }
+            }
+            timings.lap("checks");
+            let mut sections = Vec::new();
+            match config {
+                Some(config) => config.heading_numbers.apply(&mut doc.nodes, body),
+                None => HeadingNumbers::default().apply(&mut doc.nodes, body),
+            }
+            if let Some(config) = config {
+                if !config.headings.is_empty() {
+                    doc.nodes = config.headings.apply(doc.nodes, body);
+                }
+                if !config.sections.is_empty() {
+                    (doc.nodes, sections) = config.sections.split(doc.nodes, body);
+                }
+            }
+            trace.record("frontmatter", || {
+                let mut keys: Vec<_> = doc.frontmatter.iter().collect();
+                keys.sort_by_key(|(k, _)| k.as_str());
+                keys.iter()
+                    .map(|(k, v)| format!("{} : {}\n", k, v))
+                    .collect()
+            });
+            trace.record("matched", || trace::format_nodes(&doc.nodes));
+            // Gathered before validating, which resolves the nodes it goes through
+            let mut statement_errors =
+                validation::statement_errors(&md_path, &doc.nodes, body, first_line);
+            if !statement_errors.is_empty()
+                && let Some(config) = config
+                && let Ok(patterns) = self.cached_patterns(&file_type, config)
+            {
+                validation::suggest_sentences(&mut statement_errors, &patterns);
+            }
+            let parsed = match DokeValidate::validate_tree(&mut doc.nodes, &doc.frontmatter) {
+                Ok(parsed) => parsed,
+                Err(_) if self.collect_errors && statement_errors.len() > 1 => {
+                    return Err(ValidationError::Many(statement_errors).into());
+                }
+                Err(_) if !statement_errors.is_empty() => {
+                    return Err(statement_errors.swap_remove(0).into());
+                }
+                Err(e) => {
+                    return Err(
+                        match validation::locate(&md_path, e, &doc.nodes, body, first_line) {
+                            Ok(located) => located.into(),
+                            Err(e) => e.into(),
+                        },
+                    );
+                }
+            };
+            timings.lap("validate");
+            *self.last_spans.borrow_mut() = import::value_spans(&doc.nodes, body, first_line);
+            trace.record("values", || trace::format_values(&parsed));
+            // The builders of the outputs take the same statements
+            let output_values = outputs.is_some().then(|| parsed.clone());
+            let mut final_value = builder.build_file_resource(parsed)?;
+            if let Some(config) = config {
+                config
+                    .sections
+                    .build(&mut final_value, sections, &doc.frontmatter)?;
+                // The wiki part is only read for the fields built from it
+                let wiki = match config.bbcode_field.is_some() || !config.tables.is_empty() {
+                    true => {
+                        let wiki = frontmatter::read_wiki_section(Path::new(&md_path))?;
+                        self.variables_of(&file_type)
+                            .interpolate(&wiki)
+                            .map_err(FrontmatterError::from)?
+                    }
+                    false => String::new(),
+                };
+                let context = project_context(&md_path, &config.limits());
+                config.tables.apply(
+                    &mut final_value,
+                    &doc.frontmatter,
+                    &wiki,
+                    &context,
+                    config.safe_mode.is_some(),
+                )?;
+                if !config.naming.is_empty() {
+                    config.naming.apply(&mut final_value);
+                    doc.frontmatter = config.naming.apply_to_keys(doc.frontmatter);
+                }
+                config
+                    .frontmatter_resources
+                    .apply(&mut final_value, &doc.frontmatter);
+                config.apply_field_defaults(&mut final_value);
+                if let (Some(field), GodotValue::Resource { fields, .. }) =
+                    (&config.bbcode_field, &mut final_value)
+                {
+                    fields.insert(field.clone(), GodotValue::String(bbcode::to_bbcode(&wiki)));
+                }
+                config.computed.apply(&mut final_value, &doc.frontmatter)?;
+                config.conditions.apply(&mut final_value, &doc.frontmatter);
+            }
+            self.hooks
+                .post_parse(&file_type, &md_path, &mut final_value, &mut doc.frontmatter)?;
+            if let Some(config) = config {
+                validation::check_required(
+                    &md_path,
+                    &raw_source,
+                    &config.required,
+                    &final_value,
+                    &doc.frontmatter,
+                )?;
+                if let (Some(outputs), Some(values)) = (outputs, output_values) {
+                    *outputs = config
+                        .outputs
+                        .build(values, &final_value, &doc.frontmatter)?;
+                }
+            }
+            timings.lap("build");
+            trace.record("resource", || final_value.to_string());
+            Ok((final_value, doc.frontmatter))
         } else {
             Err(ImportError::MissingParserError())
         }
     }
 }
+
+/// The context of a document in the project, for reading the files it names.
+fn project_context(md_path: &str, limits: &Limits) -> ParserContext {
+    let settings = ProjectSettings::singleton();
+    ParserContext::new(
+        Path::new(&settings.globalize_path(md_path).to_string()),
+        Path::new(&settings.globalize_path("res://").to_string()),
+        limits.max_file_size,
+    )
+}
@@ -0,0 +1,269 @@
+// lint.rs
+// Style rules of a file type (`lint:` in its config), checked on each import so
+// large dokedexes stay consistent. Each rule is a warning or an error.
+
+use markdown::mdast::Node;
+use yaml_rust2::Yaml;
+
+use crate::config::ConfigError;
+use crate::diagnostic::Severity;
+use crate::span::SourceSpan;
+
+pub const MAX_HEADING_DEPTH: &str = "DOKE110";
+pub const HEADING_CASE: &str = "DOKE111";
+pub const REQUIRED_SECTIONS: &str = "DOKE112";
+pub const EMPTY_LIST_ITEM: &str = "DOKE113";
+
+#[derive(Debug, Clone, Default)]
+pub struct Lints {
+    /// `max_heading_depth:` deepest heading level allowed
+    max_heading_depth: Option<Rule<usize>>,
+    /// `heading_case:` how heading titles are capitalized
+    heading_case: Option<Rule<HeadingCase>>,
+    /// `required_sections:` headings every document has
+    required_sections: Option<Rule<Vec<String>>>,
+    /// `no_empty_list_items:` list items must have text
+    no_empty_list_items: Option<Rule<()>>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule<T> {
+    severity: Severity,
+    value: T,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HeadingCase {
+    /// Every word of more than three letters capitalized, and the first one
+    Title,
+    /// The first letter capitalized
+    Sentence,
+    Lower,
+}
+
+impl HeadingCase {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "title" => Some(Self::Title),
+            "sentence" => Some(Self::Sentence),
+            "lower" => Some(Self::Lower),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Sentence => "sentence",
+            Self::Lower => "lower",
+        }
+    }
+
+    fn matches(self, title: &str) -> bool {
+        let capitalized = |word: &str| word.chars().next().is_none_or(|c| !c.is_lowercase());
+        match self {
+            Self::Title => title
+                .split_whitespace()
+                .enumerate()
+                .all(|(i, word)| (i > 0 && word.chars().count() <= 3) || capitalized(word)),
+            Self::Sentence => title
+                .chars()
+                .find(|c| c.is_alphabetic())
+                .is_none_or(|c| !c.is_lowercase()),
+            Self::Lower => !title.chars().any(char::is_uppercase),
+        }
+    }
+}
+
+/// A rule a document breaks.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub span: SourceSpan,
+}
+
+/// A rule given as its value, or as `{<key>: value, severity: error|warning|off}`.
+/// Rules are warnings unless they say otherwise.
+fn rule<T>(
+    y: &Yaml,
+    name: &str,
+    key: &str,
+    parse: impl Fn(&Yaml) -> Option<T>,
+) -> Result<Option<Rule<T>>, ConfigError> {
+    let invalid = || ConfigError::Invalid(format!("lint.{} has an invalid value : {:?}", name, y));
+    let (severity, value) = match y {
+        Yaml::BadValue => return Ok(None),
+        Yaml::Hash(_) => {
+            let severity = match &y["severity"] {
+                Yaml::BadValue => Some(Severity::Warning),
+                Yaml::String(s) if s == "off" => None,
+                Yaml::String(s) => Some(Severity::from_name(s).ok_or_else(invalid)?),
+                _ => return Err(invalid()),
+            };
+            (severity, &y[key])
+        }
+        _ => (Some(Severity::Warning), y),
+    };
+    let Some(severity) = severity else {
+        return Ok(None);
+    };
+    let value = parse(value).ok_or_else(invalid)?;
+    Ok(Some(Rule { severity, value }))
+}
+
+impl Lints {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        if y.is_badvalue() {
+            return Ok(Self::default());
+        }
+        let no_empty_list_items = rule(
+            &y["no_empty_list_items"],
+            "no_empty_list_items",
+            "enabled",
+            |v| {
+                match v {
+                    // The severity alone turns it on
+                    Yaml::BadValue | Yaml::Boolean(true) => Some(true),
+                    Yaml::Boolean(false) => Some(false),
+                    _ => None,
+                }
+            },
+        )?;
+        Ok(Self {
+            max_heading_depth: rule(&y["max_heading_depth"], "max_heading_depth", "max", |v| {
+                v.as_i64()
+                    .filter(|&i| (1..=6).contains(&i))
+                    .map(|i| i as usize)
+            })?,
+            heading_case: rule(&y["heading_case"], "heading_case", "style", |v| {
+                v.as_str().and_then(HeadingCase::from_name)
+            })?,
+            required_sections: rule(
+                &y["required_sections"],
+                "required_sections",
+                "sections",
+                |v| {
+                    v.as_vec()?
+                        .iter()
+                        .map(|s| s.as_str().map(normalize_title))
+                        .collect()
+                },
+            )?,
+            no_empty_list_items: no_empty_list_items.filter(|r| r.value).map(|r| Rule {
+                severity: r.severity,
+                value: (),
+            }),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max_heading_depth.is_none()
+            && self.heading_case.is_none()
+            && self.required_sections.is_none()
+            && self.no_empty_list_items.is_none()
+    }
+
+    /// Checks the markdown of a document, `root` being the mdast of `body`,
+    /// which starts at `first_line` of it.
+    pub fn check(&self, root: &Node, body: &str, first_line: usize) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        if self.is_empty() {
+            return findings;
+        }
+        let mut headings = Vec::new();
+        let mut empty_items = Vec::new();
+        collect(root, &mut headings, &mut empty_items);
+        let span_of = |node: &Node| match node.position() {
+            Some(p) => SourceSpan::of_range(body, p.start.offset, p.end.offset, first_line),
+            None => SourceSpan::point(first_line, 1),
+        };
+        let mut find = |severity: Severity, code, message: String, span| {
+            findings.push(Finding {
+                code,
+                severity,
+                message,
+                span,
+            })
+        };
+        for (node, depth, title) in &headings {
+            if let Some(rule) = &self.max_heading_depth
+                && *depth > rule.value
+            {
+                find(
+                    rule.severity,
+                    MAX_HEADING_DEPTH,
+                    format!(
+                        "heading '{}' is level {}, deeper than {}",
+                        title, depth, rule.value
+                    ),
+                    span_of(node),
+                );
+            }
+            if let Some(rule) = &self.heading_case
+                && !rule.value.matches(title)
+            {
+                find(
+                    rule.severity,
+                    HEADING_CASE,
+                    format!("heading '{}' isn't in {} case", title, rule.value.name()),
+                    span_of(node),
+                );
+            }
+        }
+        if let Some(rule) = &self.required_sections {
+            for section in &rule.value {
+                if !headings
+                    .iter()
+                    .any(|(_, _, t)| normalize_title(t) == *section)
+                {
+                    find(
+                        rule.severity,
+                        REQUIRED_SECTIONS,
+                        format!("missing section '{}'", section),
+                        SourceSpan::point(first_line, 1),
+                    );
+                }
+            }
+        }
+        if let Some(rule) = &self.no_empty_list_items {
+            for item in empty_items {
+                find(
+                    rule.severity,
+                    EMPTY_LIST_ITEM,
+                    "empty list item".into(),
+                    span_of(item),
+                );
+            }
+        }
+        findings
+    }
+}
+
+/// The headings with their level and title, and the list items without text.
+fn collect<'a>(
+    node: &'a Node,
+    headings: &mut Vec<(&'a Node, usize, String)>,
+    empty_items: &mut Vec<&'a Node>,
+) {
+    match node {
+        Node::Heading(h) => {
+            let title = node.to_string();
+            headings.push((
+                node,
+                h.depth as usize,
+                title.trim().trim_end_matches(':').to_string(),
+            ));
+        }
+        Node::ListItem(_) if node.to_string().trim().is_empty() => empty_items.push(node),
+        _ => {}
+    }
+    for child in node.children().into_iter().flatten() {
+        collect(child, headings, empty_items);
+    }
+}
+
+fn normalize_title(title: &str) -> String {
+    title.trim().trim_end_matches(':').trim().to_lowercase()
+}
@@ -0,0 +1,174 @@
+// localization.rs
+// Translatable strings of documents under stable keys (`localization:` in a config),
+// written as gettext files or a Translation resource so narrative documents feed
+// the localization pipeline.
+
+use std::collections::HashMap;
+
+use doke::GodotValue;
+use markdown::ParseOptions;
+use markdown::mdast::Node;
+use yaml_rust2::Yaml;
+
+use crate::config::ConfigError;
+use crate::frontmatter::normalize_key;
+use crate::spreadsheet;
+
+#[derive(Debug, Clone, Default)]
+pub struct Localization {
+    /// `fields:` fields of the built resource, or frontmatter keys, whose strings are extracted
+    fields: Vec<String>,
+    /// `headings:` whether the headings of the wiki part are extracted
+    headings: bool,
+    /// `paragraphs:` whether the paragraphs of the wiki part are extracted
+    paragraphs: bool,
+    /// `locale:` language the documents are written in, `en` by default
+    pub locale: String,
+}
+
+/// A string to translate, `key` staying the same as long as it keeps its place.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub key: String,
+    pub text: String,
+    pub source: String,
+}
+
+impl Localization {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let mut localization = Self {
+            locale: "en".into(),
+            ..Default::default()
+        };
+        if y.is_badvalue() {
+            return Ok(localization);
+        }
+        if !y.is_hash() {
+            return Err(ConfigError::Invalid(
+                "'localization' must be a map of `fields`, `headings`, `paragraphs` and `locale`"
+                    .into(),
+            ));
+        }
+        for field in y["fields"].as_vec().into_iter().flatten() {
+            let field = field.as_str().ok_or_else(|| {
+                ConfigError::Invalid("'localization.fields' must be a list of fields".into())
+            })?;
+            localization.fields.push(field.to_string());
+        }
+        localization.headings = y["headings"].as_bool().unwrap_or(false);
+        localization.paragraphs = y["paragraphs"].as_bool().unwrap_or(false);
+        if let Some(locale) = y["locale"].as_str() {
+            localization.locale = locale.to_string();
+        }
+        Ok(localization)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && !self.headings && !self.paragraphs
+    }
+
+    /// The strings of a document named `name`. Fields are keyed `name.field` (`name.field.0`
+    /// for lists), headings `name.heading` and paragraphs `name.heading.1`, counted from 1
+    /// under their heading (`name.1` before the first one).
+    pub fn extract(
+        &self,
+        name: &str,
+        md_path: &str,
+        value: &GodotValue,
+        frontmatter: &HashMap<String, GodotValue>,
+        wiki: &str,
+    ) -> Vec<Entry> {
+        let name = normalize_key(name);
+        let mut entries = Vec::new();
+        let mut push = |key: String, text: &str| {
+            if !text.trim().is_empty() {
+                entries.push(Entry {
+                    key,
+                    text: text.trim().to_string(),
+                    source: md_path.to_string(),
+                });
+            }
+        };
+        for field in &self.fields {
+            match spreadsheet::lookup(value, frontmatter, field) {
+                Some(GodotValue::String(text)) => push(format!("{}.{}", name, field), text),
+                Some(GodotValue::Array(items)) => {
+                    for (i, item) in items.iter().enumerate() {
+                        if let GodotValue::String(text) = item {
+                            push(format!("{}.{}.{}", name, field, i), text);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !self.headings && !self.paragraphs {
+            return entries;
+        }
+        let Ok(root) = markdown::to_mdast(wiki, &ParseOptions::gfm()) else {
+            return entries;
+        };
+        let mut section = name.clone();
+        let mut paragraph = 0;
+        for node in root.children().into_iter().flatten() {
+            let source = |node: &Node| {
+                node.position()
+                    .map(|p| &wiki[p.start.offset..p.end.offset])
+                    .unwrap_or_default()
+            };
+            match node {
+                Node::Heading(_) => {
+                    let title = node.to_string();
+                    section = format!("{}.{}", name, normalize_key(title.trim_end_matches(':')));
+                    paragraph = 0;
+                    if self.headings {
+                        push(section.clone(), &title);
+                    }
+                }
+                Node::Paragraph(_) if self.paragraphs => {
+                    paragraph += 1;
+                    push(format!("{}.{}", section, paragraph), source(node));
+                }
+                _ => {}
+            }
+        }
+        entries
+    }
+}
+
+/// A gettext string, quoted.
+fn quote(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// The entries as gettext, keys being the msgids. A template (`.pot`) has the
+/// source texts as comments and empty translations, a `.po` of the source
+/// locale has them as translations.
+pub fn to_gettext(entries: &[Entry], locale: Option<&str>) -> String {
+    let mut out = String::from("msgid \"\"\nmsgstr \"\"\n");
+    if let Some(locale) = locale {
+        out.push_str(&format!("\"Language: {}\\n\"\n", locale));
+    }
+    out.push_str("\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+    for entry in entries {
+        out.push('\n');
+        if locale.is_none() {
+            for line in entry.text.lines() {
+                out.push_str(&format!("#. {}\n", line));
+            }
+        }
+        out.push_str(&format!("#: {}\n", entry.source));
+        out.push_str(&format!("msgid {}\n", quote(&entry.key)));
+        let translation = if locale.is_some() {
+            &entry.text[..]
+        } else {
+            ""
+        };
+        out.push_str(&format!("msgstr {}\n", quote(translation)));
+    }
+    out
+}
@@ -0,0 +1,92 @@
+// locks.rs
+// Keeps imports of the same document from interleaving : a script hook importing
+// the document it is being built from fails instead of recursing, and requests made
+// in the same frame (watcher, batch job, manual call) share one import.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+
+use godot::classes::{Engine, Resource};
+use godot::prelude::*;
+
+/// Results kept at most, older frames are dropped first anyway.
+const MAX_DONE: usize = 256;
+
+/// The documents being imported, and the last result of each.
+/// Like the rest of the importer, only used from the thread it was made on.
+#[derive(Default)]
+pub struct ImportLocks {
+    in_flight: RefCell<HashSet<String>>,
+    done: RefCell<HashMap<String, Imported>>,
+    /// Bumped when a config, parser or variable is (re)loaded, results of older ones aren't reused
+    generation: Cell<u64>,
+}
+
+struct Imported {
+    frame: u64,
+    hash: u64,
+    generation: u64,
+    resource: Gd<Resource>,
+}
+
+/// Releases a document's lock when its import ends, errors included.
+pub struct ImportLock<'a> {
+    locks: &'a ImportLocks,
+    key: String,
+}
+
+impl Drop for ImportLock<'_> {
+    fn drop(&mut self) {
+        self.locks.in_flight.borrow_mut().remove(&self.key);
+    }
+}
+
+impl ImportLocks {
+    /// Locks a document for import. None if it is already being imported : that import is
+    /// further down the stack (a hook or parser importing its own document), so it has
+    /// no result to share yet and waiting for one would never end.
+    pub fn lock(&self, key: &str) -> Option<ImportLock<'_>> {
+        if !self.in_flight.borrow_mut().insert(key.to_string()) {
+            return None;
+        }
+        Some(ImportLock {
+            locks: self,
+            key: key.to_string(),
+        })
+    }
+
+    /// Forgets the results imported so far, after a config, parser or variable changed.
+    pub fn invalidate(&self) {
+        self.generation.set(self.generation.get() + 1);
+        self.done.borrow_mut().clear();
+    }
+
+    /// A copy of the resource imported from the same content during this frame, if any.
+    pub fn reusable(&self, key: &str, hash: u64) -> Option<Gd<Resource>> {
+        let frame = Engine::singleton().get_process_frames();
+        let generation = self.generation.get();
+        let mut done = self.done.borrow_mut();
+        done.retain(|_, d| d.frame == frame && d.generation == generation);
+        match done.get(key) {
+            Some(d) if d.hash == hash => d.resource.duplicate_ex().subresources(true).done(),
+            _ => None,
+        }
+    }
+
+    pub fn finish(&self, key: &str, hash: u64, res: &Gd<Resource>) {
+        let frame = Engine::singleton().get_process_frames();
+        let generation = self.generation.get();
+        let mut done = self.done.borrow_mut();
+        done.retain(|_, d| d.frame == frame && d.generation == generation);
+        if done.len() >= MAX_DONE && !done.contains_key(key) {
+            return;
+        }
+        let imported = Imported {
+            frame,
+            hash,
+            generation,
+            resource: res.clone(),
+        };
+        done.insert(key.to_string(), imported);
+    }
+}
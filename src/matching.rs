@@ -0,0 +1,299 @@
+// matching.rs
+// Statements brought to the exact writing of the sentence they match (`matching:` in
+// a config), as designers don't write with the case and punctuation doke expects,
+// and other wordings of a sentence brought to it (`synonyms:`).
+
+use std::collections::HashMap;
+
+use doke::GodotValue;
+use doke::semantic::{DokeNode, DokeNodeState, DokeParser};
+use regex::{Regex, RegexBuilder};
+use yaml_rust2::Yaml;
+
+use crate::config::ConfigError;
+use crate::grammar::{self, PARAM_RE, Pattern};
+use crate::value_types;
+
+/// How far nested sentence parameters are followed.
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Matching {
+    /// `ignore_case:` words of the sentences match whatever their case
+    ignore_case: bool,
+    /// `trailing_punctuation:` ending `.`, `!`, `?`, `;`, `,` and `…` are dropped
+    trailing_punctuation: bool,
+    /// `whitespace:` runs of spaces, tabs and line breaks count as one space
+    whitespace: bool,
+}
+
+impl Matching {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        if y.is_badvalue() {
+            return Ok(Self::default());
+        }
+        let option = |key: &str| match &y[key] {
+            Yaml::BadValue => Ok(false),
+            Yaml::Boolean(b) => Ok(*b),
+            _ => Err(ConfigError::Invalid(format!(
+                "matching.{} must be true or false",
+                key
+            ))),
+        };
+        if !y.is_hash() {
+            return Err(ConfigError::Invalid(
+                "'matching' must be a map of `ignore_case`, `trailing_punctuation` and `whitespace`"
+                    .into(),
+            ));
+        }
+        Ok(Self {
+            ignore_case: option("ignore_case")?,
+            trailing_punctuation: option("trailing_punctuation")?,
+            whitespace: option("whitespace")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn ignore_case(&self) -> bool {
+        self.ignore_case
+    }
+}
+
+/// `synonyms:` sentence -> other wordings of it, written like sentence patterns.
+#[derive(Debug, Clone, Default)]
+pub struct Synonyms {
+    aliases: Vec<Alias>,
+}
+
+/// A wording matched like doke matches a pattern, and the sentence it stands for.
+#[derive(Debug, Clone)]
+struct Alias {
+    regex: Regex,
+    /// The name of each capture of `regex`
+    parameters: Vec<String>,
+    sentence: String,
+}
+
+fn parameter_name(param: &regex::Captures) -> String {
+    param[1].trim().trim_end_matches(":?").trim().to_string()
+}
+
+impl Alias {
+    fn new(sentence: &str, alias: &str, ignore_case: bool) -> Result<Self, String> {
+        let mut pattern = String::from("^");
+        let mut parameters = Vec::new();
+        let mut last = 0;
+        for param in PARAM_RE.captures_iter(alias) {
+            let whole = param.get(0).expect("whole match");
+            pattern.push_str(&literal_regex(&alias[last..whole.start()]));
+            last = whole.end();
+            let ty = param.get(2).map_or("string", |t| t.as_str().trim());
+            pattern.push_str(&format!("({})", grammar::parameter_regex(ty)));
+            parameters.push(parameter_name(&param));
+        }
+        pattern.push_str(&literal_regex(&alias[last..]));
+        pattern.push('$');
+        for param in PARAM_RE.captures_iter(sentence) {
+            let name = parameter_name(&param);
+            if !parameters.contains(&name) {
+                return Err(format!("'{}' doesn't have the parameter '{}'", alias, name));
+            }
+        }
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| format!("'{}' : {}", alias, e))?;
+        Ok(Self {
+            regex,
+            parameters,
+            sentence: sentence.to_string(),
+        })
+    }
+
+    /// The sentence with the parameters of the statement, if it is written this way.
+    fn rewrite(&self, statement: &str) -> Option<String> {
+        let caps = self.regex.captures(grammar::clean_statement(statement))?;
+        let values: HashMap<&str, &str> = self
+            .parameters
+            .iter()
+            .zip(caps.iter().skip(1))
+            .filter_map(|(name, value)| Some((name.as_str(), value?.as_str())))
+            .collect();
+        Some(
+            PARAM_RE
+                .replace_all(&self.sentence, |param: &regex::Captures| {
+                    values
+                        .get(parameter_name(param).as_str())
+                        .copied()
+                        .unwrap_or_default()
+                        .to_string()
+                })
+                .into_owned(),
+        )
+    }
+}
+
+/// Literal text of a pattern, its runs of whitespace matching any whitespace like in doke.
+fn literal_regex(text: &str) -> String {
+    let mut regex = String::new();
+    let mut in_space = false;
+    for c in text.chars() {
+        if !c.is_whitespace() {
+            regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+        } else if !in_space {
+            regex.push_str(r"\s+");
+        }
+        in_space = c.is_whitespace();
+    }
+    regex
+}
+
+impl Synonyms {
+    /// `ignore_case` is the file type's `matching.ignore_case`, for the wordings too.
+    pub fn from_yaml(y: &Yaml, ignore_case: bool) -> Result<Self, ConfigError> {
+        let mut synonyms = Self::default();
+        let Some(hash) = y.as_hash() else {
+            return Ok(synonyms);
+        };
+        let invalid = |e: String| ConfigError::Invalid(format!("synonyms : {}", e));
+        for (sentence, aliases) in hash {
+            let sentence = sentence
+                .as_str()
+                .ok_or_else(|| invalid("keys must be sentences".into()))?;
+            let aliases = match aliases {
+                Yaml::String(alias) => vec![alias.as_str()],
+                Yaml::Array(list) => list
+                    .iter()
+                    .map(Yaml::as_str)
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| invalid(format!("'{}' must list sentences", sentence)))?,
+                _ => return Err(invalid(format!("'{}' must list sentences", sentence))),
+            };
+            for alias in aliases {
+                synonyms
+                    .aliases
+                    .push(Alias::new(sentence, alias, ignore_case).map_err(invalid)?);
+            }
+        }
+        Ok(synonyms)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+
+    fn rewrite(&self, statement: &str) -> Option<String> {
+        self.aliases.iter().find_map(|a| a.rewrite(statement))
+    }
+}
+
+/// The `tolerant_matching` stage, before the sentences : rewrites the statements
+/// of the document so they match a sentence pattern as written.
+#[derive(Debug)]
+pub struct MatchingParser {
+    pub matching: Matching,
+    pub synonyms: Synonyms,
+    pub patterns: Vec<Pattern>,
+}
+
+impl MatchingParser {
+    fn process_node(&self, node: &mut DokeNode) {
+        if matches!(node.state, DokeNodeState::Unresolved) {
+            node.statement = self.normalize(&node.statement);
+        }
+        for child in &mut node.children {
+            self.process_node(child);
+        }
+    }
+
+    fn normalize(&self, statement: &str) -> String {
+        let mut text = statement.to_string();
+        if self.matching.whitespace {
+            text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+        if self.matching.trailing_punctuation {
+            text = text
+                .trim_end_matches(|c: char| c.is_whitespace() || ".!?;,…".contains(c))
+                .to_string();
+        }
+        if let Some(sentence) = self.synonyms.rewrite(&text) {
+            text = sentence;
+        }
+        if self.matching.ignore_case {
+            text = self.recase(&text, None, 0);
+        }
+        text
+    }
+
+    /// The statement written like the pattern it matches ignoring case, its parameters
+    /// as they are, or recased too when they are nested sentences. `within` restricts the
+    /// patterns to a parameter's (rule, section), like doke does for nested sentences.
+    fn recase(&self, statement: &str, within: Option<(&str, &str)>, depth: usize) -> String {
+        if depth > MAX_DEPTH {
+            return statement.to_string();
+        }
+        let cleaned = grammar::clean_statement(statement);
+        let candidates: Vec<&Pattern> = match within {
+            None => self.patterns.iter().collect(),
+            Some((target, section)) => {
+                let in_section: Vec<_> = self
+                    .patterns
+                    .iter()
+                    .filter(|p| p.target == target && p.section == section)
+                    .collect();
+                if in_section.is_empty() {
+                    self.patterns
+                        .iter()
+                        .filter(|p| p.target == target)
+                        .collect()
+                } else {
+                    in_section
+                }
+            }
+        };
+        // Exact matches first, then the most specific pattern, like doke picks them
+        let best = candidates
+            .into_iter()
+            .filter_map(|p| Some((p, p.caseless()?.captures(cleaned)?)))
+            .max_by_key(|(p, _)| (p.is_match(cleaned), p.literal_len()));
+        let Some((pattern, caps)) = best else {
+            return statement.to_string();
+        };
+        let mut out = String::new();
+        let mut last = 0;
+        for (i, param) in PARAM_RE.captures_iter(&pattern.pattern).enumerate() {
+            let whole = param.get(0).expect("whole match");
+            out.push_str(&pattern.pattern[last..whole.start()]);
+            last = whole.end();
+            let Some(value) = caps.get(i + 1) else {
+                continue;
+            };
+            // Optional parameters take the whitespace before them
+            if param[1].trim().ends_with(":?") {
+                out.push(' ');
+            }
+            let ty = param.get(2).map_or("string", |t| t.as_str().trim());
+            let basic = ["int", "float", "bool", "string"].contains(&ty.to_lowercase().as_str());
+            if basic || value_types::is_value_type(ty) {
+                out.push_str(value.as_str());
+            } else {
+                out.push_str(&self.recase(
+                    value.as_str().trim(),
+                    Some((&pattern.target, ty)),
+                    depth + 1,
+                ));
+            }
+        }
+        out.push_str(&pattern.pattern[last..]);
+        out
+    }
+}
+
+impl DokeParser for MatchingParser {
+    fn process(&self, node: &mut DokeNode, _frontmatter: &HashMap<String, GodotValue>) {
+        self.process_node(node);
+    }
+}
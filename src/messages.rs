@@ -0,0 +1,165 @@
+// messages.rs
+// Project wording of diagnostics (`messages:` in a config), so in-house tools
+// can show designers their own sentences, or translated ones, instead of ours.
+
+use std::collections::HashMap;
+
+use yaml_rust2::Yaml;
+
+use crate::config::ConfigError;
+use crate::diagnostic::Diagnostic;
+use crate::span::SourceSpan;
+
+#[derive(Debug, Clone)]
+struct Template {
+    message: Option<String>,
+    hint: Option<String>,
+}
+
+impl Template {
+    /// `"text"` or `{message: "text", hint: "text"}`
+    fn from_yaml(code: &str, y: &Yaml) -> Result<Self, ConfigError> {
+        let text = |v: &Yaml| match v {
+            Yaml::BadValue => Ok(None),
+            Yaml::String(s) => Ok(Some(s.clone())),
+            _ => Err(ConfigError::Invalid(format!(
+                "messages.{} must be a string or {{message, hint}} : {:?}",
+                code, y
+            ))),
+        };
+        match y {
+            Yaml::Hash(_) => Ok(Self {
+                message: text(&y["message"])?,
+                hint: text(&y["hint"])?,
+            }),
+            _ => Ok(Self {
+                message: text(y)?,
+                hint: None,
+            }),
+        }
+    }
+}
+
+/// The templates of each code, with those of each locale.
+#[derive(Debug, Clone, Default)]
+pub struct Messages {
+    templates: HashMap<String, Template>,
+    locales: HashMap<String, HashMap<String, Template>>,
+}
+
+fn is_code(key: &str) -> bool {
+    key.strip_prefix("DOKE")
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn templates_from_yaml(
+    y: &Yaml,
+    locales: Option<&mut HashMap<String, HashMap<String, Template>>>,
+) -> Result<HashMap<String, Template>, ConfigError> {
+    let mut templates = HashMap::new();
+    let mut locales = locales;
+    for (k, v) in y.as_hash().into_iter().flatten() {
+        let key = k
+            .as_str()
+            .ok_or_else(|| ConfigError::Invalid("'messages' keys must be strings".into()))?;
+        if is_code(key) {
+            templates.insert(key.to_string(), Template::from_yaml(key, v)?);
+            continue;
+        }
+        // Other keys are locales, one level deep
+        let Some(locales) = locales.as_deref_mut() else {
+            return Err(ConfigError::Invalid(format!(
+                "messages.{} isn't a diagnostic code (DOKE021...)",
+                key
+            )));
+        };
+        if !v.is_hash() {
+            return Err(ConfigError::Invalid(format!(
+                "messages.{} must map codes to messages",
+                key
+            )));
+        }
+        locales.insert(key.to_string(), templates_from_yaml(v, None)?);
+    }
+    Ok(templates)
+}
+
+impl Messages {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        if y.is_badvalue() {
+            return Ok(Self::default());
+        }
+        if !y.is_hash() {
+            return Err(ConfigError::Invalid(
+                "'messages' must map codes to messages".into(),
+            ));
+        }
+        let mut locales = HashMap::new();
+        let templates = templates_from_yaml(y, Some(&mut locales))?;
+        Ok(Self { templates, locales })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty() && self.locales.is_empty()
+    }
+
+    /// The template of `code` in `locale` (`fr_FR`, then `fr`), then in the default catalog.
+    fn template(&self, code: &str, locale: &str) -> Option<&Template> {
+        let language = locale.split(['_', '-']).next().unwrap_or(locale);
+        [locale, language]
+            .iter()
+            .filter_map(|l| self.locales.get(*l)?.get(code))
+            .chain(self.templates.get(code))
+            .next()
+    }
+
+    /// Rewords `diagnostic` with its template, filling `{message}`, `{code}`, `{file}`,
+    /// `{line}`, `{column}`, `{parser}` and the details of its code (`{statement}`...).
+    pub fn apply(&self, diagnostic: &mut Diagnostic, locale: &str) {
+        let Some(template) = self.template(&diagnostic.code, locale) else {
+            return;
+        };
+        let span = diagnostic.span.unwrap_or(SourceSpan::point(0, 0));
+        let mut args = vec![
+            ("code", diagnostic.code.clone()),
+            ("file", diagnostic.file.clone()),
+            ("line", span.start_line.to_string()),
+            ("column", span.start_column.to_string()),
+            ("parser", diagnostic.parser.clone()),
+            ("message", diagnostic.message.clone()),
+        ];
+        args.extend(diagnostic.details.iter().cloned());
+        if let Some(message) = &template.message {
+            diagnostic.message = fill(message, &args);
+        }
+        if let Some(hint) = &template.hint {
+            diagnostic.hint = Some(fill(hint, &args));
+        }
+    }
+}
+
+/// `template` with its `{name}`s replaced by their value, unknown ones kept as written.
+fn fill(template: &str, args: &[(&str, String)]) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let arg = after.find('}').and_then(|close| {
+            let (_, value) = args.iter().find(|(name, _)| *name == &after[..close])?;
+            Some((close, value))
+        });
+        match arg {
+            Some((close, value)) => {
+                out.push_str(value);
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
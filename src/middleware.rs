@@ -0,0 +1,194 @@
+// middleware.rs
+// Project-specific steps around the import without forking the pipeline : pre-parse
+// hooks rewrite a document's Doké section (macro expansion, filters...), post-parse
+// hooks rework the values built from it. Hooks are written in Rust or GDScript.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use doke::GodotValue;
+use godot::prelude::*;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MiddlewareError {
+    #[error("pre-parse hook failed : {0}")]
+    PreParse(String),
+    #[error("post-parse hook failed : {0}")]
+    PostParse(String),
+    #[error("a hook returned an unusable value : {0}")]
+    InvalidValue(String),
+}
+
+/// Rewrites a document's Doké section (frontmatter included) before anything else reads it.
+pub trait PreParseHook: Send + Sync {
+    /// The new section, or a message saying why the import fails.
+    fn pre_parse(&self, file_type: &str, path: &str, content: String) -> Result<String, String>;
+}
+
+/// Reworks the value built from a document, after the config's steps and before `required`.
+pub trait PostParseHook: Send + Sync {
+    fn post_parse(
+        &self,
+        file_type: &str,
+        path: &str,
+        value: &mut GodotValue,
+        frontmatter: &mut HashMap<String, GodotValue>,
+    ) -> Result<(), String>;
+}
+
+static PRE_PARSE: LazyLock<RwLock<Vec<Arc<dyn PreParseHook>>>> = LazyLock::new(Default::default);
+static POST_PARSE: LazyLock<RwLock<Vec<Arc<dyn PostParseHook>>>> = LazyLock::new(Default::default);
+
+/// Runs `hook` on the documents of every importer, after the hooks registered before it.
+pub fn register_pre_parse_hook(hook: impl PreParseHook + 'static) {
+    PRE_PARSE
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Arc::new(hook));
+}
+
+/// Runs `hook` on the values of every importer, after the hooks registered before it.
+pub fn register_post_parse_hook(hook: impl PostParseHook + 'static) {
+    POST_PARSE
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Arc::new(hook));
+}
+
+/// The GDScript hooks of an importer, run after the Rust ones.
+/// Pre-parse : `func(file_type, path, content) -> String`.
+/// Post-parse : `func(file_type, path, value, frontmatter) -> Variant`, returning the new
+/// value or null to keep it. Resources are `{type = ..., fields = {...}}` Dictionaries.
+#[derive(Default)]
+pub(crate) struct CallableHooks {
+    pub pre_parse: Vec<Callable>,
+    pub post_parse: Vec<Callable>,
+}
+
+impl CallableHooks {
+    pub fn pre_parse(
+        &self,
+        file_type: &str,
+        path: &str,
+        mut content: String,
+    ) -> Result<String, MiddlewareError> {
+        let hooks = PRE_PARSE.read().unwrap_or_else(|e| e.into_inner()).clone();
+        for hook in hooks {
+            content = hook
+                .pre_parse(file_type, path, content)
+                .map_err(MiddlewareError::PreParse)?;
+        }
+        for callable in &self.pre_parse {
+            let returned = callable.call(&[
+                file_type.to_variant(),
+                path.to_variant(),
+                content.to_variant(),
+            ]);
+            content = returned
+                .try_to::<String>()
+                .map_err(|_| MiddlewareError::InvalidValue(returned.to_string()))?;
+        }
+        Ok(content)
+    }
+
+    pub fn post_parse(
+        &self,
+        file_type: &str,
+        path: &str,
+        value: &mut GodotValue,
+        frontmatter: &mut HashMap<String, GodotValue>,
+    ) -> Result<(), MiddlewareError> {
+        let hooks = POST_PARSE.read().unwrap_or_else(|e| e.into_inner()).clone();
+        for hook in hooks {
+            hook.post_parse(file_type, path, value, frontmatter)
+                .map_err(MiddlewareError::PostParse)?;
+        }
+        for callable in &self.post_parse {
+            let returned = callable.call(&[
+                file_type.to_variant(),
+                path.to_variant(),
+                to_variant(value),
+                dictionary(frontmatter).to_variant(),
+            ]);
+            if !returned.is_nil() {
+                *value = from_variant(&returned)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A value as plain Variants, without instancing its resources.
+pub(crate) fn to_variant(value: &GodotValue) -> Variant {
+    match value {
+        GodotValue::Nil => Variant::nil(),
+        GodotValue::Bool(b) => b.to_variant(),
+        GodotValue::Int(i) => i.to_variant(),
+        GodotValue::Float(f) => f.to_variant(),
+        GodotValue::String(s) => s.to_variant(),
+        GodotValue::Array(items) => items
+            .iter()
+            .map(to_variant)
+            .collect::<VariantArray>()
+            .to_variant(),
+        GodotValue::Dict(map) => dictionary(map).to_variant(),
+        GodotValue::Resource {
+            type_name, fields, ..
+        } => {
+            let mut resource = Dictionary::new();
+            resource.set("type", type_name.clone());
+            resource.set("fields", dictionary(fields));
+            resource.to_variant()
+        }
+    }
+}
+
+fn dictionary(map: &HashMap<String, GodotValue>) -> Dictionary {
+    map.iter()
+        .map(|(k, v)| (k.to_variant(), to_variant(v)))
+        .collect()
+}
+
+pub(crate) fn from_variant(v: &Variant) -> Result<GodotValue, MiddlewareError> {
+    Ok(match v.get_type() {
+        VariantType::NIL => GodotValue::Nil,
+        VariantType::BOOL => GodotValue::Bool(v.to()),
+        VariantType::INT => GodotValue::Int(v.to()),
+        VariantType::FLOAT => GodotValue::Float(v.to()),
+        VariantType::STRING | VariantType::STRING_NAME => GodotValue::String(v.to_string()),
+        VariantType::ARRAY => GodotValue::Array(
+            v.to::<VariantArray>()
+                .iter_shared()
+                .map(|item| from_variant(&item))
+                .collect::<Result<_, _>>()?,
+        ),
+        VariantType::DICTIONARY => {
+            let dict = v.to::<Dictionary>();
+            let mut map = HashMap::new();
+            for (k, item) in dict.iter_shared() {
+                map.insert(k.to_string(), from_variant(&item)?);
+            }
+            resource_or_dict(map)
+        }
+        _ => return Err(MiddlewareError::InvalidValue(v.to_string())),
+    })
+}
+
+/// A resource when `map` is `{type, fields}`, as `to_variant` writes them.
+pub(crate) fn resource_or_dict(mut map: HashMap<String, GodotValue>) -> GodotValue {
+    match (map.remove("type"), map.remove("fields")) {
+        (Some(GodotValue::String(type_name)), Some(GodotValue::Dict(fields))) if map.is_empty() => {
+            GodotValue::Resource {
+                abstract_type_name: type_name.clone(),
+                type_name,
+                fields,
+            }
+        }
+        (type_name, fields) => {
+            map.extend(type_name.map(|t| ("type".to_string(), t)));
+            map.extend(fields.map(|f| ("fields".to_string(), f)));
+            GodotValue::Dict(map)
+        }
+    }
+}
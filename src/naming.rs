@@ -0,0 +1,105 @@
+// naming.rs
+// Maps the keys documents are written with to the property names of the
+// resources, so authors write natural language and resources stay idiomatic.
+
+use std::collections::HashMap;
+
+use doke::GodotValue;
+use yaml_rust2::Yaml;
+
+use crate::config::ConfigError;
+use crate::frontmatter::normalize_key;
+
+#[derive(Debug, Clone, Default)]
+pub struct Naming {
+    /// `rename:` normalized key -> property name
+    renames: HashMap<String, String>,
+    /// `case: snake` converts every other key to snake_case
+    snake_case: bool,
+}
+
+impl Naming {
+    pub fn from_yaml(rename: &Yaml, case: &Yaml) -> Result<Self, ConfigError> {
+        let mut naming = Self::default();
+        if let Some(hash) = rename.as_hash() {
+            for (k, v) in hash {
+                let (Some(from), Some(to)) = (k.as_str(), v.as_str()) else {
+                    return Err(ConfigError::Invalid(
+                        "'rename' entries must be `Written Name: property_name`".into(),
+                    ));
+                };
+                naming.renames.insert(normalize_key(from), to.to_string());
+            }
+        }
+        naming.snake_case = match case.as_str() {
+            None | Some("keep") => false,
+            Some("snake") | Some("snake_case") => true,
+            Some(other) => {
+                return Err(ConfigError::Invalid(format!(
+                    "Unknown case '{}' (expected snake or keep)",
+                    other
+                )));
+            }
+        };
+        Ok(naming)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty() && !self.snake_case
+    }
+
+    pub fn property_name(&self, key: &str) -> String {
+        if let Some(name) = self.renames.get(&normalize_key(key)) {
+            return name.clone();
+        }
+        if self.snake_case {
+            return to_snake_case(key);
+        }
+        key.to_string()
+    }
+
+    /// Renames the fields of a resource and of every resource nested in it.
+    pub fn apply(&self, value: &mut GodotValue) {
+        match value {
+            GodotValue::Resource { fields, .. } => {
+                *fields = std::mem::take(fields)
+                    .into_iter()
+                    .map(|(k, mut v)| {
+                        self.apply(&mut v);
+                        (self.property_name(&k), v)
+                    })
+                    .collect();
+            }
+            GodotValue::Array(items) => items.iter_mut().for_each(|v| self.apply(v)),
+            GodotValue::Dict(map) => map.values_mut().for_each(|v| self.apply(v)),
+            _ => {}
+        }
+    }
+
+    /// Renames the keys of a frontmatter.
+    pub fn apply_to_keys(&self, map: HashMap<String, GodotValue>) -> HashMap<String, GodotValue> {
+        map.into_iter()
+            .map(|(k, v)| (self.property_name(&k), v))
+            .collect()
+    }
+}
+
+/// `Display Name`, `displayName` and `display-name` all become `display_name`.
+pub fn to_snake_case(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    let mut prev: Option<char> = None;
+    for c in key.trim().chars() {
+        if c.is_alphanumeric() {
+            let lower_to_upper =
+                c.is_uppercase() && prev.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit());
+            if lower_to_upper && !out.ends_with('_') {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else if !out.is_empty() && !out.ends_with('_') {
+            out.push('_');
+        }
+        prev = Some(c);
+    }
+    out.trim_end_matches('_').to_string()
+}
@@ -0,0 +1,117 @@
+// outputs.rs
+// Extra resources built from the same document as the file type's own,
+// like a `ShopListing` next to an `Item`, declared in `outputs:`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use doke::GodotValue;
+use doke::file_builder::ResourceBuilder;
+use yaml_rust2::Yaml;
+
+use crate::config::{self, ConfigError};
+use crate::import::ImportError;
+use crate::spreadsheet;
+
+/// Name of the document's own resource among the outputs.
+pub const MAIN_OUTPUT: &str = "main";
+
+#[derive(Debug, Clone, Default)]
+pub struct Outputs {
+    outputs: Vec<Output>,
+}
+
+#[derive(Debug, Clone)]
+struct Output {
+    name: String,
+    /// Class of the output
+    root: String,
+    /// Built from the statements when the output has `children`
+    builder: Option<Arc<ResourceBuilder>>,
+    /// `map:` output field -> field of the main resource or frontmatter key (`a.b` for nested)
+    map: Vec<(String, String)>,
+}
+
+impl Outputs {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let mut outputs = Self::default();
+        let Some(hash) = y.as_hash() else {
+            return Ok(outputs);
+        };
+        for (name, output) in hash {
+            let name = name
+                .as_str()
+                .ok_or_else(|| ConfigError::Invalid("'outputs' keys must be names".into()))?;
+            let invalid = |e: String| ConfigError::Invalid(format!("Output '{}' : {}", name, e));
+            if name == MAIN_OUTPUT {
+                return Err(invalid(format!(
+                    "'{}' is the document's own resource",
+                    MAIN_OUTPUT
+                )));
+            }
+            let root = output["root"]
+                .as_str()
+                .ok_or_else(|| invalid("missing 'root' class".into()))?
+                .to_string();
+            let builder = match output["children"] {
+                Yaml::BadValue => None,
+                _ => Some(
+                    config::builder_config(output)
+                        .and_then(ResourceBuilder::from_config)
+                        .map_err(|e| invalid(e.to_string()))?
+                        .into(),
+                ),
+            };
+            let map = match output["map"].as_hash() {
+                None => Vec::new(),
+                Some(map) => map
+                    .iter()
+                    .map(|(k, v)| match (k.as_str(), v.as_str()) {
+                        (Some(k), Some(v)) => Ok((k.to_string(), v.to_string())),
+                        _ => Err(invalid("'map' entries must be `field: source`".into())),
+                    })
+                    .collect::<Result<_, _>>()?,
+            };
+            outputs.outputs.push(Output {
+                name: name.to_string(),
+                root,
+                builder,
+                map,
+            });
+        }
+        Ok(outputs)
+    }
+
+    /// Builds every output from the document's statement values, its built resource and frontmatter.
+    pub fn build(
+        &self,
+        values: Vec<GodotValue>,
+        main: &GodotValue,
+        frontmatter: &HashMap<String, GodotValue>,
+    ) -> Result<Vec<(String, GodotValue)>, ImportError> {
+        let mut built = Vec::with_capacity(self.outputs.len());
+        for output in &self.outputs {
+            let mut fields = match &output.builder {
+                Some(builder) => match builder.build_file_resource(values.clone())? {
+                    GodotValue::Resource { fields, .. } => fields,
+                    other => return Err(ImportError::NotAResource(other)),
+                },
+                None => HashMap::new(),
+            };
+            for (field, source) in &output.map {
+                if let Some(value) = spreadsheet::lookup(main, frontmatter, source) {
+                    fields.insert(field.clone(), value.clone());
+                }
+            }
+            built.push((
+                output.name.clone(),
+                GodotValue::Resource {
+                    type_name: output.root.clone(),
+                    abstract_type_name: output.root.clone(),
+                    fields,
+                },
+            ));
+        }
+        Ok(built)
+    }
+}
@@ -0,0 +1,433 @@
+// parser_api.rs
+// Parser plugins : dynamic libraries dropped in a folder, discovered at runtime
+// and registered by name, so file types can use parsers the extension doesn't ship.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use doke::GodotValue;
+use doke::semantic::{DokeNode, DokeNodeState, DokeParser};
+use libloading::Library;
+use thiserror::Error;
+
+/// Bumped whenever the plugin interface changes.
+pub const PARSER_API_VERSION: u32 = 1;
+
+/// `create_doke_parser` : the name the parser registers under, and the parser.
+/// The parser crosses the library boundary as a trait object, so plugins must be built
+/// with the same Rust compiler and doke version as the extension.
+pub type CreateParser = fn() -> (String, Box<dyn DokeParser>);
+/// `doke_parser_api_version` : the PARSER_API_VERSION the plugin was built against.
+pub type ApiVersion = extern "C" fn() -> u32;
+/// `doke_parser_priority`, optional : where the parser goes in the chain of its name, 0 by default.
+pub type Priority = extern "C" fn() -> i32;
+/// `doke_parser_version`, optional : the version of the parser, shown to tools.
+pub type Version = fn() -> &'static str;
+/// `doke_parser_default_options`, optional : the options file types don't set.
+pub type DefaultOptions = fn() -> GodotValue;
+/// `doke_parser_validate_options`, optional : checks the options of a file type when its
+/// pipe is built, so a mistake fails loading the config rather than each import.
+pub type ValidateOptions = fn(&GodotValue) -> Result<(), String>;
+
+const CREATE_PARSER_SYMBOL: &[u8] = b"create_doke_parser";
+const API_VERSION_SYMBOL: &[u8] = b"doke_parser_api_version";
+const PRIORITY_SYMBOL: &[u8] = b"doke_parser_priority";
+const VERSION_SYMBOL: &[u8] = b"doke_parser_version";
+const DEFAULT_OPTIONS_SYMBOL: &[u8] = b"doke_parser_default_options";
+const VALIDATE_OPTIONS_SYMBOL: &[u8] = b"doke_parser_validate_options";
+
+/// parse_data key naming the parser of a chain that handled a statement.
+pub const HANDLED_BY_KEY: &str = "doke_parser";
+/// parse_data key holding the options of the file type while a parser runs.
+pub const OPTIONS_KEY: &str = "doke_options";
+
+#[derive(Debug, Error)]
+pub enum PluginError {
+    #[error("couldn't read the plugin folder {0} : {1}")]
+    ReadDir(PathBuf, std::io::Error),
+    #[error("couldn't load {0} : {1}")]
+    Load(PathBuf, libloading::Error),
+    #[error("{0} doesn't export `create_doke_parser`")]
+    MissingSymbol(PathBuf),
+    #[error("{0} was built for parser API {1}, this extension has {PARSER_API_VERSION}")]
+    ApiVersion(PathBuf, u32),
+    #[cfg(feature = "wasm")]
+    #[error("couldn't load the WebAssembly parser {0} : {1}")]
+    Wasm(PathBuf, String),
+    #[cfg(not(feature = "wasm"))]
+    #[error("WebAssembly parsers need the extension built with the `wasm` feature")]
+    WasmDisabled,
+    #[error("a parser named '{0}' is already registered with priority {1}")]
+    Duplicate(String, i32),
+    #[error("invalid options for the parser '{0}' ({1}) : {2}")]
+    InvalidOptions(String, String, String),
+    #[error("the parser {0} panicked : {1}")]
+    Panicked(String, String),
+}
+
+static SHARED: LazyLock<RwLock<ParserRegistry>> = LazyLock::new(Default::default);
+
+/// The registry every importer builds its pipes from, see `DokeRegistry`.
+pub fn shared() -> RwLockReadGuard<'static, ParserRegistry> {
+    SHARED.read().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn shared_mut() -> RwLockWriteGuard<'static, ParserRegistry> {
+    SHARED.write().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Parsers registered by name, from plugins or from code. Several parsers can share a name :
+/// they form a fallback chain, tried from the highest priority down.
+#[derive(Default)]
+pub struct ParserRegistry {
+    parsers: HashMap<String, Vec<ChainedParser>>,
+}
+
+#[derive(Debug, Clone)]
+struct ChainedParser {
+    /// Where the parser comes from, for diagnostics
+    source: String,
+    priority: i32,
+    /// Empty when the parser doesn't say
+    version: String,
+    parser: Arc<dyn DokeParser>,
+    default_options: GodotValue,
+    validate_options: Option<ValidateOptions>,
+}
+
+/// What tools are shown of a registered parser.
+#[derive(Debug, Clone)]
+pub struct ParserInfo {
+    pub source: String,
+    pub priority: i32,
+    pub version: String,
+    pub default_options: GodotValue,
+}
+
+impl ParserRegistry {
+    pub fn register(
+        &mut self,
+        name: &str,
+        source: &str,
+        priority: i32,
+        parser: Arc<dyn DokeParser>,
+    ) -> Result<(), PluginError> {
+        self.insert(
+            name,
+            ChainedParser {
+                source: source.to_string(),
+                priority,
+                version: String::new(),
+                parser,
+                default_options: GodotValue::Nil,
+                validate_options: None,
+            },
+        )
+    }
+
+    /// Registers a parser in place of the one of the same name and priority, if any,
+    /// for hot-reloading. Returns whether a parser was replaced.
+    pub fn replace(
+        &mut self,
+        name: &str,
+        source: &str,
+        priority: i32,
+        parser: Arc<dyn DokeParser>,
+    ) -> bool {
+        let chain = self.parsers.entry(name.to_string()).or_default();
+        let replaced = chain
+            .iter()
+            .position(|p| p.priority == priority)
+            .map(|i| chain.remove(i));
+        let _ = self.register(name, source, priority, parser);
+        replaced.is_some()
+    }
+
+    /// Forgets the parsers registered under `name`. The pipes built with them keep them
+    /// until their file types are loaded again. Returns whether there were any.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.parsers.remove(name).is_some()
+    }
+
+    /// Two parsers of a name can't share a priority, the order they're tried in would be arbitrary.
+    fn insert(&mut self, name: &str, parser: ChainedParser) -> Result<(), PluginError> {
+        let chain = self.parsers.entry(name.to_string()).or_default();
+        if chain.iter().any(|p| p.priority == parser.priority) {
+            return Err(PluginError::Duplicate(name.to_string(), parser.priority));
+        }
+        chain.push(parser);
+        chain.sort_by_key(|p| std::cmp::Reverse(p.priority));
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.parsers.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// The parsers registered under `name`, highest priority first.
+    pub fn describe(&self, name: &str) -> Vec<ParserInfo> {
+        let chain = self
+            .parsers
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        chain
+            .iter()
+            .map(|p| ParserInfo {
+                source: p.source.clone(),
+                priority: p.priority,
+                version: p.version.clone(),
+                default_options: p.default_options.clone(),
+            })
+            .collect()
+    }
+
+    /// The chain of parsers registered under `name`, ready to be added to a pipe, with the
+    /// options of a file type over the defaults of each parser. None if there's no such parser.
+    pub fn get(
+        &self,
+        name: &str,
+        options: &GodotValue,
+    ) -> Result<Option<RegisteredParser>, PluginError> {
+        let Some(chain) = self.parsers.get(name) else {
+            return Ok(None);
+        };
+        let chain = chain
+            .iter()
+            .map(|chained| {
+                let options = merge_options(&chained.default_options, options);
+                if let Some(validate) = chained.validate_options {
+                    validate(&options).map_err(|e| {
+                        PluginError::InvalidOptions(name.to_string(), chained.source.clone(), e)
+                    })?;
+                }
+                Ok((chained.clone(), options))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Some(RegisteredParser(chain)))
+    }
+
+    /// Loads every dynamic library of `dir` as a parser plugin, and returns
+    /// the names registered and the plugins that failed, which don't stop the others.
+    pub fn load_dir(&mut self, dir: &Path) -> (Vec<String>, Vec<PluginError>) {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => return (Vec::new(), vec![PluginError::ReadDir(dir.to_path_buf(), e)]),
+        };
+        let mut names = Vec::new();
+        let mut errors = Vec::new();
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.extension()
+                    .is_some_and(|e| e == std::env::consts::DLL_EXTENSION)
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            match self.load(&path) {
+                Ok(name) => names.push(name),
+                Err(e) => errors.push(e),
+            }
+        }
+        (names, errors)
+    }
+
+    fn load(&mut self, path: &Path) -> Result<String, PluginError> {
+        // SAFETY: loading a library runs its initializers, the plugin folder is trusted
+        // like the extension itself. The symbols are checked against the API version first.
+        let library =
+            unsafe { Library::new(path) }.map_err(|e| PluginError::Load(path.to_path_buf(), e))?;
+        let api_version =
+            unsafe { library.get::<ApiVersion>(API_VERSION_SYMBOL) }.map_or(0, |version| version());
+        if api_version != PARSER_API_VERSION {
+            return Err(PluginError::ApiVersion(path.to_path_buf(), api_version));
+        }
+        let create = unsafe { library.get::<CreateParser>(CREATE_PARSER_SYMBOL) }
+            .map_err(|_| PluginError::MissingSymbol(path.to_path_buf()))?;
+        let priority =
+            unsafe { library.get::<Priority>(PRIORITY_SYMBOL) }.map_or(0, |priority| priority());
+        let version = unsafe { library.get::<Version>(VERSION_SYMBOL) }
+            .map_or(String::new(), |version| version().to_string());
+        let default_options = unsafe { library.get::<DefaultOptions>(DEFAULT_OPTIONS_SYMBOL) }
+            .map_or(GodotValue::Nil, |defaults| defaults());
+        let validate_options = unsafe { library.get::<ValidateOptions>(VALIDATE_OPTIONS_SYMBOL) }
+            .ok()
+            .map(|validate| *validate);
+        let (name, parser) = create();
+        let chained = ChainedParser {
+            source: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into(),
+            priority,
+            version,
+            parser: parser.into(),
+            default_options,
+            validate_options,
+        };
+        self.insert(&name, chained)?;
+        // The parser's code lives in the library : it stays loaded for the whole session
+        std::mem::forget(library);
+        Ok(name)
+    }
+}
+
+impl ParserRegistry {
+    /// Registers a WebAssembly parser (see wasm_parser.rs) under `name`.
+    #[cfg(feature = "wasm")]
+    pub fn load_wasm(&mut self, name: &str, path: &Path, priority: i32) -> Result<(), PluginError> {
+        let parser = crate::wasm_parser::WasmParser::load(path)
+            .map_err(|e| PluginError::Wasm(path.to_path_buf(), e.to_string()))?;
+        let source = path.file_name().unwrap_or_default().to_string_lossy();
+        self.register(name, &source, priority, Arc::new(parser))
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn load_wasm(
+        &mut self,
+        _name: &str,
+        _path: &Path,
+        _priority: i32,
+    ) -> Result<(), PluginError> {
+        Err(PluginError::WasmDisabled)
+    }
+}
+
+/// The parsers registered under a name, shared by the pipes of the file types using it.
+/// A statement a parser leaves unresolved or rejects goes to the next parser of the chain.
+/// The parser that handled it, or the last one rejecting it, is named in its parse_data,
+/// under `doke_parser`.
+/// Each parser finds its options in the parse_data, under `doke_options`, while it runs.
+#[derive(Debug, Clone)]
+pub struct RegisteredParser(Vec<(ChainedParser, GodotValue)>);
+
+impl DokeParser for RegisteredParser {
+    fn process(&self, node: &mut DokeNode, frontmatter: &HashMap<String, GodotValue>) {
+        let mut pending = Vec::new();
+        unresolved_paths(node, &mut Vec::new(), &mut pending);
+        // The last parser rejecting each statement, and its error
+        let mut errors: HashMap<Vec<usize>, (String, DokeNodeState)> = HashMap::new();
+        for (chained, options) in &self.0 {
+            if pending.is_empty() {
+                break;
+            }
+            set_options(node, Some(options));
+            let run = panic::catch_unwind(AssertUnwindSafe(|| {
+                chained.parser.process(node, frontmatter);
+            }));
+            set_options(node, None);
+            if let Err(payload) = run {
+                // What the parser did before panicking isn't trusted : its statements are
+                // rejected, and left to the next parser
+                for path in &pending {
+                    if let Some(n) = node_at(node, path) {
+                        n.state = DokeNodeState::Unresolved;
+                    }
+                    let error =
+                        PluginError::Panicked(chained.source.clone(), panic_message(&payload));
+                    let state = DokeNodeState::Error(Box::new(error));
+                    errors.insert(path.clone(), (chained.source.clone(), state));
+                }
+                continue;
+            }
+            pending.retain(|path| {
+                let Some(n) = node_at(node, path) else {
+                    return false;
+                };
+                match n.state {
+                    DokeNodeState::Unresolved => true,
+                    // Rejected : the next parser gets a try, the error is kept if none succeeds
+                    DokeNodeState::Error(_) => {
+                        let error = std::mem::replace(&mut n.state, DokeNodeState::Unresolved);
+                        errors.insert(path.clone(), (chained.source.clone(), error));
+                        true
+                    }
+                    DokeNodeState::Resolved(_) | DokeNodeState::Hypothesis(_) => {
+                        n.parse_data.insert(
+                            HANDLED_BY_KEY.to_string(),
+                            GodotValue::String(chained.source.clone()),
+                        );
+                        false
+                    }
+                }
+            });
+        }
+        for path in pending {
+            if let (Some(n), Some((source, error))) = (node_at(node, &path), errors.remove(&path)) {
+                n.parse_data
+                    .insert(HANDLED_BY_KEY.to_string(), GodotValue::String(source));
+                n.state = error;
+            }
+        }
+    }
+}
+
+impl RegisteredParser {
+    /// `source@version` for each parser of the chain, `source` alone when it has no version.
+    pub fn versions(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|(p, _)| match p.version.as_str() {
+                "" => p.source.clone(),
+                version => format!("{}@{}", p.source, version),
+            })
+            .collect()
+    }
+}
+
+fn panic_message(payload: &Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "no message".into())
+}
+
+/// Options over the defaults, key by key when both are maps.
+fn merge_options(defaults: &GodotValue, options: &GodotValue) -> GodotValue {
+    match (defaults, options) {
+        (GodotValue::Dict(defaults), GodotValue::Dict(options)) => {
+            let mut merged = defaults.clone();
+            merged.extend(options.iter().map(|(k, v)| (k.clone(), v.clone())));
+            GodotValue::Dict(merged)
+        }
+        (defaults, GodotValue::Nil) => defaults.clone(),
+        (_, options) => options.clone(),
+    }
+}
+
+fn set_options(node: &mut DokeNode, options: Option<&GodotValue>) {
+    match options {
+        Some(options) => node
+            .parse_data
+            .insert(OPTIONS_KEY.to_string(), options.clone()),
+        None => node.parse_data.remove(OPTIONS_KEY),
+    };
+    for child in &mut node.children {
+        set_options(child, options);
+    }
+}
+
+/// Paths (child indices) of the unresolved nodes of a tree.
+fn unresolved_paths(node: &DokeNode, path: &mut Vec<usize>, paths: &mut Vec<Vec<usize>>) {
+    if matches!(node.state, DokeNodeState::Unresolved) {
+        paths.push(path.clone());
+    }
+    for (i, child) in node.children.iter().enumerate() {
+        path.push(i);
+        unresolved_paths(child, path, paths);
+        path.pop();
+    }
+}
+
+fn node_at<'a>(node: &'a mut DokeNode, path: &[usize]) -> Option<&'a mut DokeNode> {
+    path.iter()
+        .try_fold(node, |node, &i| node.children.get_mut(i))
+}
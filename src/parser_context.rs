@@ -0,0 +1,193 @@
+// parser_context.rs
+// The document a pipe is parsing, with file helpers restricted to the project,
+// so parsers needing auxiliary files (CSV lookups, shared data) don't roll their own IO.
+
+use std::cell::RefCell;
+use std::path::{Component, Path, PathBuf};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ContextError {
+    #[error("no document is being parsed")]
+    NoDocument,
+    #[error("{0} is outside of the project")]
+    OutsideProject(String),
+    #[error("couldn't read {0} : {1}")]
+    Read(String, std::io::Error),
+    #[error("{0} is {1} bytes, over the limit of {2}")]
+    FileTooLarge(String, u64, usize),
+}
+
+/// The document being parsed, and the folder parsers may read from.
+#[derive(Debug, Clone)]
+pub struct ParserContext {
+    document: PathBuf,
+    root: PathBuf,
+    /// Bytes of the files read through the context, like the document's own
+    max_file_size: usize,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<ParserContext>> = const { RefCell::new(None) };
+}
+
+/// Clears the current context when the pipe is done with the document.
+pub struct ContextGuard {
+    previous: Option<ParserContext>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|c| *c.borrow_mut() = self.previous.take());
+    }
+}
+
+/// Makes `context` the current one until the guard is dropped.
+pub fn enter(context: ParserContext) -> ContextGuard {
+    let previous = CURRENT.with(|c| c.borrow_mut().replace(context));
+    ContextGuard { previous }
+}
+
+/// Runs `f` with the context of the document being parsed.
+pub fn with_current<R>(
+    f: impl FnOnce(&ParserContext) -> Result<R, ContextError>,
+) -> Result<R, ContextError> {
+    CURRENT.with(|c| match c.borrow().as_ref() {
+        Some(context) => f(context),
+        None => Err(ContextError::NoDocument),
+    })
+}
+
+impl ParserContext {
+    /// The context of `document`, reading files of the project at `root`.
+    pub fn new(document: &Path, root: &Path, max_file_size: usize) -> Self {
+        Self {
+            document: absolute(document),
+            root: std::fs::canonicalize(root).unwrap_or_else(|_| absolute(root)),
+            max_file_size,
+        }
+    }
+
+    /// The context of another document of the same project.
+    pub fn with_document(&self, document: &Path) -> Self {
+        Self {
+            document: absolute(document),
+            ..self.clone()
+        }
+    }
+
+    pub fn document(&self) -> &Path {
+        &self.document
+    }
+
+    /// The file a path names : `res://` paths start from the project root, relative ones
+    /// from the document's folder. Paths leading out of the project are refused.
+    pub fn resolve_res_path(&self, path: &str) -> Result<PathBuf, ContextError> {
+        let resolved = match path.strip_prefix("res://") {
+            Some(rest) => self.root.join(rest),
+            // `user://` and other schemes aren't in the project
+            None if path.contains("://") => {
+                return Err(ContextError::OutsideProject(path.to_string()));
+            }
+            None => self.document.parent().unwrap_or(&self.root).join(path),
+        };
+        // Canonical when the file exists, so symlinks can't lead out either
+        let resolved = std::fs::canonicalize(&resolved).unwrap_or_else(|_| absolute(&resolved));
+        if !resolved.starts_with(&self.root) {
+            return Err(ContextError::OutsideProject(path.to_string()));
+        }
+        Ok(resolved)
+    }
+
+    /// [`Self::resolve_res_path`] for a file about to be read, refused when it's over
+    /// `max_file_size`. Missing files are left for the read to report.
+    pub fn file_to_read(&self, path: &str) -> Result<PathBuf, ContextError> {
+        let resolved = self.resolve_res_path(path)?;
+        if let Ok(metadata) = std::fs::metadata(&resolved)
+            && metadata.len() > self.max_file_size as u64
+        {
+            return Err(ContextError::FileTooLarge(
+                path.to_string(),
+                metadata.len(),
+                self.max_file_size,
+            ));
+        }
+        Ok(resolved)
+    }
+
+    /// Reads a file next to the document, or anywhere in the project with a `res://` path.
+    pub fn read_sibling_file(&self, rel_path: &str) -> Result<String, ContextError> {
+        let path = self.file_to_read(rel_path)?;
+        std::fs::read_to_string(&path).map_err(|e| ContextError::Read(rel_path.to_string(), e))
+    }
+}
+
+/// The absolute form of a path, with `.` and `..` worked out without touching the disk.
+fn absolute(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_out_of_the_project_are_refused() {
+        let root = std::env::temp_dir().join(format!("doke_context_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("items")).unwrap();
+        let context = ParserContext::new(&root.join("items/sword.md"), &root, 1 << 20);
+
+        let sibling = context.resolve_res_path("drops.csv").unwrap();
+        assert_eq!(sibling, context.root.join("items/drops.csv"));
+        let from_root = context.resolve_res_path("res://data/waves.csv").unwrap();
+        assert_eq!(from_root, context.root.join("data/waves.csv"));
+
+        for path in [
+            "../../secrets.csv",
+            "/etc/passwd",
+            "user://save.csv",
+            "res://../x",
+        ] {
+            assert!(
+                matches!(
+                    context.resolve_res_path(path),
+                    Err(ContextError::OutsideProject(_))
+                ),
+                "{path}"
+            );
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn files_over_the_limit_are_refused() {
+        let root = std::env::temp_dir().join(format!("doke_context_size_{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("drops.csv"), "item,chance\n").unwrap();
+        let context = ParserContext::new(&root.join("sword.md"), &root, 4);
+
+        assert!(matches!(
+            context.read_sibling_file("drops.csv"),
+            Err(ContextError::FileTooLarge(_, 12, 4))
+        ));
+        assert!(
+            context
+                .with_document(&root.join("shield.md"))
+                .file_to_read("drops.csv")
+                .is_err()
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,178 @@
+// query.rs
+// Filters over the frontmatter of indexed documents (`{rarity: "epic", level: {"<=": 10}}`),
+// so content selection doesn't loop over every document by hand.
+
+use std::cmp::Ordering;
+
+use godot::prelude::*;
+
+use crate::index;
+
+/// Comparisons that must all hold.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    comparisons: Vec<(String, Op, Variant)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// The value is one of an Array
+    In,
+    /// The Array or String value contains the operand
+    Has,
+    /// The document has the tag
+    Tag,
+}
+
+impl Op {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "==" => Self::Eq,
+            "!=" => Self::Ne,
+            "<" => Self::Lt,
+            "<=" => Self::Le,
+            ">" => Self::Gt,
+            ">=" => Self::Ge,
+            "in" => Self::In,
+            "has" => Self::Has,
+            _ => return None,
+        })
+    }
+}
+
+impl Filter {
+    /// `field: value` for equality (or for containing it when the field is an Array),
+    /// `field: {"<=": 10, ">": 2}` for comparisons (`==`, `!=`, `<`, `<=`, `>`, `>=`,
+    /// `in`, `has`), and `tag: "rare"` or `tag: ["rare", "weapon"]` for tags.
+    pub fn from_dictionary(filter: &Dictionary) -> Result<Self, String> {
+        let mut comparisons = Vec::new();
+        for (key, value) in filter.iter_shared() {
+            let key = key.to_string();
+            if key == "tag" || key == "tags" {
+                match value.try_to::<VariantArray>() {
+                    Ok(tags) => comparisons.extend(
+                        tags.iter_shared()
+                            .map(|t| (key.clone(), Op::Tag, t.to_string().to_variant())),
+                    ),
+                    Err(_) => comparisons.push((key, Op::Tag, value.to_string().to_variant())),
+                }
+                continue;
+            }
+            match value.try_to::<Dictionary>() {
+                Ok(ops) => {
+                    for (op, operand) in ops.iter_shared() {
+                        let op = Op::from_name(&op.to_string()).ok_or_else(|| {
+                            format!(
+                                "unknown operator '{}' for '{}' (expected ==, !=, <, <=, >, >=, in or has)",
+                                op, key
+                            )
+                        })?;
+                        comparisons.push((key.clone(), op, operand));
+                    }
+                }
+                Err(_) => comparisons.push((key, Op::Eq, value)),
+            }
+        }
+        Ok(Self { comparisons })
+    }
+
+    /// Whether a document matches, `document` being a Dictionary with its `frontmatter`
+    /// and `tags`. Fields are looked up in its frontmatter first, `a.b` reading nested values.
+    pub fn matches(&self, document: &Dictionary) -> bool {
+        let frontmatter = document
+            .get("frontmatter")
+            .and_then(|f| f.try_to::<Dictionary>().ok())
+            .unwrap_or_default();
+        self.comparisons.iter().all(|(field, op, operand)| {
+            if *op == Op::Tag {
+                let tag = index::normalize_tag(&operand.to_string());
+                return document
+                    .get("tags")
+                    .and_then(|t| t.try_to::<PackedStringArray>().ok())
+                    .is_some_and(|tags| tags.as_slice().iter().any(|t| t.to_string() == tag));
+            }
+            let value = lookup(&frontmatter, field).or_else(|| lookup(document, field));
+            match value {
+                Some(value) => compare(&value, *op, operand),
+                None => *op == Op::Ne,
+            }
+        })
+    }
+}
+
+fn lookup(dict: &Dictionary, field: &str) -> Option<Variant> {
+    let mut parts = field.split('.');
+    let mut current = dict.get(parts.next()?)?;
+    for part in parts {
+        current = current.try_to::<Dictionary>().ok()?.get(part)?;
+    }
+    Some(current)
+}
+
+fn number(v: &Variant) -> Option<f64> {
+    match v.get_type() {
+        VariantType::INT => Some(v.to::<i64>() as f64),
+        VariantType::FLOAT => Some(v.to::<f64>()),
+        _ => None,
+    }
+}
+
+/// Numbers compare as numbers, strings as strings.
+fn order(a: &Variant, b: &Variant) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (number(a), number(b)) {
+        return a.partial_cmp(&b);
+    }
+    let is_text =
+        |v: &Variant| matches!(v.get_type(), VariantType::STRING | VariantType::STRING_NAME);
+    (is_text(a) && is_text(b)).then(|| a.to_string().cmp(&b.to_string()))
+}
+
+fn equal(a: &Variant, b: &Variant) -> bool {
+    order(a, b).map_or(a == b, Ordering::is_eq)
+}
+
+fn contains(container: &Variant, item: &Variant) -> bool {
+    match container.get_type() {
+        VariantType::ARRAY => container
+            .to::<VariantArray>()
+            .iter_shared()
+            .any(|v| equal(&v, item)),
+        VariantType::PACKED_STRING_ARRAY => container
+            .to::<PackedStringArray>()
+            .as_slice()
+            .iter()
+            .any(|s| s.to_string() == item.to_string()),
+        VariantType::STRING | VariantType::STRING_NAME => {
+            container.to_string().contains(&item.to_string())
+        }
+        _ => false,
+    }
+}
+
+fn compare(value: &Variant, op: Op, operand: &Variant) -> bool {
+    match op {
+        Op::Eq => equal(value, operand) || contains_element(value, operand),
+        Op::Ne => !equal(value, operand) && !contains_element(value, operand),
+        Op::Lt => order(value, operand).is_some_and(Ordering::is_lt),
+        Op::Le => order(value, operand).is_some_and(Ordering::is_le),
+        Op::Gt => order(value, operand).is_some_and(Ordering::is_gt),
+        Op::Ge => order(value, operand).is_some_and(Ordering::is_ge),
+        Op::In => contains(operand, value),
+        Op::Has => contains(value, operand),
+        Op::Tag => false,
+    }
+}
+
+/// An Array field equals a value it contains.
+fn contains_element(value: &Variant, operand: &Variant) -> bool {
+    matches!(
+        value.get_type(),
+        VariantType::ARRAY | VariantType::PACKED_STRING_ARRAY
+    ) && contains(value, operand)
+}
@@ -0,0 +1,145 @@
+// registry.rs
+// The parsers loaded in the session, shared by every DokeImporter, editor plugin and
+// runtime system instead of each importer loading its own. Add DokeRegistry as an autoload.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use godot::classes::ProjectSettings;
+use godot::global::push_error;
+use godot::prelude::*;
+
+use crate::callable_parser::CallableParser;
+use crate::parser_api;
+
+#[derive(GodotClass)]
+#[class(init, base=Node)]
+/// Loads and registers parsers for all the importers. The importers' own
+/// `load_parser_plugins`, `register_gdscript_parser`... do the same.
+pub struct DokeRegistry {
+    base: Base<Node>,
+}
+
+#[godot_api]
+impl DokeRegistry {
+    #[func]
+    /// Loads the parser plugins of a folder : dynamic libraries exporting `create_doke_parser`
+    /// (see parser_api.rs). Load them before the file types whose `plugins:` use them.
+    /// Returns `{parsers: [names loaded], errors: [messages]}`, the errors are also pushed.
+    fn load_parser_plugins(&self, dir: String) -> Dictionary {
+        load_parser_plugins(&dir)
+    }
+
+    #[func]
+    /// Registers a GDScript parser under `name`, for the file types listing it in `plugins:`.
+    /// `callable(statement : String, context : Dictionary) -> Dictionary` is called on each
+    /// unresolved statement, see callable_parser.rs for what it returns. Parsers sharing
+    /// a name are tried from the highest `priority` down.
+    fn register_gdscript_parser(&self, name: String, callable: Callable, priority: i32) -> i64 {
+        register_gdscript_parser(&name, callable, priority)
+    }
+
+    #[func]
+    /// Registers a GDScript parser in place of the one with the same name and priority,
+    /// e.g. when its script is reloaded. Load the file types using it again to pick it up.
+    fn replace_gdscript_parser(&self, name: String, callable: Callable, priority: i32) {
+        replace_gdscript_parser(&name, callable, priority)
+    }
+
+    #[func]
+    /// Registers a WebAssembly parser under `name`, for the file types listing it in `plugins:`.
+    /// It runs sandboxed, see wasm_parser.rs for its ABI. Needs the `wasm` feature.
+    fn load_wasm_parser(&self, name: String, path: String, priority: i32) -> i64 {
+        load_wasm_parser(&name, &path, priority)
+    }
+
+    #[func]
+    /// Forgets the parsers registered under `name`, whatever their priority. File types already
+    /// loaded keep them until they are loaded again. Returns whether there were any.
+    fn unregister_parser(&self, name: String) -> bool {
+        parser_api::shared_mut().unregister(&name)
+    }
+
+    #[func]
+    /// The names parsers are registered under, sorted.
+    fn get_parser_names(&self) -> PackedStringArray {
+        parser_api::shared()
+            .names()
+            .iter()
+            .map(GString::from)
+            .collect()
+    }
+
+    #[func]
+    /// Registered parser name -> Array of `{source, priority, version}`, highest priority first.
+    /// `version` is empty for parsers that don't give one.
+    fn get_parser_versions(&self) -> Dictionary {
+        parser_versions()
+    }
+}
+
+pub(crate) fn load_parser_plugins(dir: &str) -> Dictionary {
+    let path = ProjectSettings::singleton().globalize_path(dir).to_string();
+    let (names, errors) = parser_api::shared_mut().load_dir(Path::new(&path));
+    let errors: Array<GString> = errors
+        .iter()
+        .map(|e| {
+            push_error(&[Variant::from(e.to_string())]);
+            GString::from(&e.to_string())
+        })
+        .collect();
+    let names: Array<GString> = names.iter().map(GString::from).collect();
+    let mut result = Dictionary::new();
+    result.set("parsers", names);
+    result.set("errors", errors);
+    result
+}
+
+pub(crate) fn register_gdscript_parser(name: &str, callable: Callable, priority: i32) -> i64 {
+    let parser = Arc::new(CallableParser::new(callable));
+    match parser_api::shared_mut().register(name, "gdscript", priority, parser) {
+        Ok(()) => 0,
+        Err(e) => {
+            push_error(&[Variant::from(e.to_string())]);
+            1
+        }
+    }
+}
+
+pub(crate) fn replace_gdscript_parser(name: &str, callable: Callable, priority: i32) {
+    let parser = Arc::new(CallableParser::new(callable));
+    parser_api::shared_mut().replace(name, "gdscript", priority, parser);
+}
+
+pub(crate) fn load_wasm_parser(name: &str, path: &str, priority: i32) -> i64 {
+    let path = ProjectSettings::singleton()
+        .globalize_path(path)
+        .to_string();
+    match parser_api::shared_mut().load_wasm(name, Path::new(&path), priority) {
+        Ok(()) => 0,
+        Err(e) => {
+            push_error(&[Variant::from(e.to_string())]);
+            1
+        }
+    }
+}
+
+pub(crate) fn parser_versions() -> Dictionary {
+    let registry = parser_api::shared();
+    let mut versions = Dictionary::new();
+    for name in registry.names() {
+        let chain: Array<Dictionary> = registry
+            .describe(&name)
+            .into_iter()
+            .map(|info| {
+                let mut d = Dictionary::new();
+                d.set("source", info.source);
+                d.set("priority", info.priority);
+                d.set("version", info.version);
+                d
+            })
+            .collect();
+        versions.set(name, chain);
+    }
+    versions
+}
@@ -0,0 +1,93 @@
+// safe_mode.rs
+// Import profile for content the project doesn't trust, like player mods :
+// no script classes, no resources loaded by path and bounded output.
+
+use godot::classes::ClassDb;
+use godot::prelude::*;
+use thiserror::Error;
+use yaml_rust2::Yaml;
+
+use crate::config::{ConfigError, Limits};
+
+const DEFAULT_MAX_DEPTH: usize = 16;
+const DEFAULT_MAX_ELEMENTS: usize = 10_000;
+const DEFAULT_MAX_FILE_SIZE: usize = 1 << 20;
+const DEFAULT_MAX_MARKDOWN_DEPTH: usize = 24;
+const DEFAULT_MAX_MARKDOWN_NODES: usize = 20_000;
+
+#[derive(Debug, Error)]
+pub enum SafeModeError {
+    #[error("safe mode : class '{0}' isn't one of the allowed built-in classes")]
+    ClassNotAllowed(String),
+    #[error("safe mode : resources can't be loaded by path ({0})")]
+    PathLoading(String),
+}
+
+/// `safe_mode:` `true` for the defaults, or a map overriding them.
+#[derive(Debug, Clone)]
+pub struct SafeMode {
+    /// `classes:` built-in Resource classes documents may instantiate
+    pub classes: Vec<String>,
+    /// The `max_*` keys of `limits:`, lower than the usual ones
+    pub limits: Limits,
+}
+
+impl Default for SafeMode {
+    fn default() -> Self {
+        Self {
+            classes: vec!["Resource".into()],
+            limits: Limits {
+                max_depth: DEFAULT_MAX_DEPTH,
+                max_elements: DEFAULT_MAX_ELEMENTS,
+                max_file_size: DEFAULT_MAX_FILE_SIZE,
+                max_markdown_depth: DEFAULT_MAX_MARKDOWN_DEPTH,
+                max_markdown_nodes: DEFAULT_MAX_MARKDOWN_NODES,
+            },
+        }
+    }
+}
+
+impl SafeMode {
+    pub fn from_yaml(y: &Yaml) -> Result<Option<Self>, ConfigError> {
+        let invalid = |key: &str| ConfigError::Invalid(format!("safe_mode.{} is invalid", key));
+        match y {
+            Yaml::BadValue | Yaml::Boolean(false) => return Ok(None),
+            Yaml::Boolean(true) => return Ok(Some(Self::default())),
+            Yaml::Hash(_) => {}
+            _ => {
+                return Err(ConfigError::Invalid(
+                    "'safe_mode' must be true, false or a map".into(),
+                ));
+            }
+        }
+        let mut safe = Self::default();
+        match &y["classes"] {
+            Yaml::BadValue => {}
+            Yaml::Array(classes) => {
+                safe.classes = classes
+                    .iter()
+                    .map(|c| c.as_str().map(str::to_string))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| invalid("classes"))?;
+            }
+            _ => return Err(invalid("classes")),
+        }
+        safe.limits = Limits::from_yaml(y, safe.limits)
+            .map_err(|key| ConfigError::Invalid(format!("safe_mode.{} is invalid", key)))?;
+        Ok(Some(safe))
+    }
+
+    /// Only listed classes that Godot itself provides and that are resources : script classes are refused.
+    pub fn check_class(&self, type_name: &str) -> Result<(), SafeModeError> {
+        let class = StringName::from(type_name);
+        let db = ClassDb::singleton();
+        let allowed = self.classes.iter().any(|c| c == type_name)
+            && db.class_exists(&class)
+            && db.is_parent_class(&class, "Resource");
+        if allowed {
+            Ok(())
+        } else {
+            Err(SafeModeError::ClassNotAllowed(type_name.to_string()))
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// search.rs
+// The words of documents, saved in the DokeIndex at import time, so codex and wiki
+// screens search content text without parsing markdown at runtime.
+
+use std::collections::HashMap;
+
+use godot::prelude::*;
+
+/// term -> document -> times it appears.
+pub type Terms = HashMap<String, HashMap<i64, i64>>;
+
+/// The lowercase words of a text, one letter words left out.
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() > 1)
+        .map(str::to_lowercase)
+}
+
+pub fn add_document(terms: &mut Terms, document: i64, text: &str) {
+    for word in words(text) {
+        *terms.entry(word).or_default().entry(document).or_default() += 1;
+    }
+}
+
+/// The terms as `{term: {document: count}}`, how the DokeIndex saves them.
+pub fn to_dictionary(terms: &Terms) -> Dictionary {
+    let mut d = Dictionary::new();
+    for (term, documents) in terms {
+        let mut counts = Dictionary::new();
+        for (document, count) in documents {
+            counts.set(*document, *count);
+        }
+        d.set(term.as_str(), counts);
+    }
+    d
+}
+
+/// The documents having every word of `query`, as `(document, score)` from the best match.
+/// Words match the terms they start, so a query typed halfway already finds something,
+/// and the score is how many times they appear.
+pub fn search(terms: &Dictionary, query: &str) -> Vec<(i64, i64)> {
+    let query: Vec<String> = words(query).collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    // document -> (score, words of the query found)
+    let mut found: HashMap<i64, (i64, Vec<bool>)> = HashMap::new();
+    for (term, documents) in terms.iter_shared() {
+        let term = term.to_string();
+        let Ok(documents) = documents.try_to::<Dictionary>() else {
+            continue;
+        };
+        for (i, word) in query.iter().enumerate() {
+            if !term.starts_with(word.as_str()) {
+                continue;
+            }
+            for (document, count) in documents.iter_shared() {
+                let (Ok(document), Ok(count)) = (document.try_to::<i64>(), count.try_to::<i64>())
+                else {
+                    continue;
+                };
+                let (score, matched) = found
+                    .entry(document)
+                    .or_insert_with(|| (0, vec![false; query.len()]));
+                *score += count;
+                matched[i] = true;
+            }
+        }
+    }
+    let mut results: Vec<(i64, i64)> = found
+        .into_iter()
+        .filter(|(_, (_, matched))| matched.iter().all(|m| *m))
+        .map(|(document, (score, _))| (document, score))
+        .collect();
+    results.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    results
+}
@@ -0,0 +1,362 @@
+// sections.rs
+// Headings of the doke section that carry meaning for the statements under
+// them, applied to the node tree between the parsers and validation.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use doke::file_builder::ResourceBuilder;
+use doke::semantic::{DokeNodeState, DokeValidate};
+use doke::{DokeNode, GodotValue};
+use yaml_rust2::Yaml;
+
+use crate::config::{self, ConfigError};
+use crate::frontmatter::{normalize_key, yaml_to_godot};
+use crate::import::ImportError;
+
+/// `headings:` field -> (heading text -> value), set on the values under those headings.
+#[derive(Debug, Clone, Default)]
+pub struct HeadingFields {
+    fields: Vec<(String, HashMap<String, GodotValue>)>,
+}
+
+impl HeadingFields {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let mut headings = Self::default();
+        let Some(hash) = y.as_hash() else {
+            return Ok(headings);
+        };
+        for (field, values) in hash {
+            let (Some(field), Some(values)) = (field.as_str(), values.as_hash()) else {
+                return Err(ConfigError::Invalid(
+                    "'headings' entries must be `field: {Heading: value}`".into(),
+                ));
+            };
+            let values = values
+                .iter()
+                .map(|(heading, value)| {
+                    let heading = heading.as_str().ok_or_else(|| {
+                        ConfigError::Invalid(format!("Headings of '{}' must be strings", field))
+                    })?;
+                    Ok((normalize_key(heading), yaml_to_godot(value.clone())))
+                })
+                .collect::<Result<_, ConfigError>>()?;
+            headings.fields.push((field.to_string(), values));
+        }
+        Ok(headings)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Values the heading sets, for the heading text of a statement.
+    fn values_for(&self, heading: &str) -> Vec<(String, GodotValue)> {
+        let key = normalize_key(heading.trim_end_matches(':'));
+        self.fields
+            .iter()
+            .filter_map(|(field, values)| Some((field.clone(), values.get(&key)?.clone())))
+            .collect()
+    }
+
+    /// Removes the configured headings from the tree and sets their fields on
+    /// every statement of their section : the list right under them and the following
+    /// statements, until a heading of the same or a higher level.
+    /// `body` is the markdown the nodes were parsed from, to tell headings apart.
+    pub fn apply(&self, nodes: Vec<DokeNode>, body: &str) -> Vec<DokeNode> {
+        let mut out = Vec::with_capacity(nodes.len());
+        // (heading level, field, value) of the enclosing configured headings
+        let mut active: Vec<(usize, String, GodotValue)> = Vec::new();
+        for mut node in nodes {
+            if let Some(level) = heading_level(body, node.span.start) {
+                active.retain(|(l, _, _)| *l < level);
+                let values = self.values_for(&node.statement);
+                if !values.is_empty() {
+                    active.extend(values.into_iter().map(|(f, v)| (level, f, v)));
+                    for mut child in std::mem::take(&mut node.children) {
+                        add_fields(&mut child, &active);
+                        out.push(child);
+                    }
+                    continue;
+                }
+            }
+            add_fields(&mut node, &active);
+            out.push(node);
+        }
+        out
+    }
+}
+
+// -----------------------
+// Heading numbers
+// -----------------------
+
+/// A heading of the doke section, numbered like a table of contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    pub title: String,
+    pub level: usize,
+    /// Position among all the headings, from 0
+    pub index: usize,
+    /// Hierarchical number, `1.2.3`
+    pub number: String,
+}
+
+/// The heading each top-level node is, if any. Skipped levels (`##` then `####`) don't add a `.0`.
+pub fn number_headings(nodes: &[DokeNode], body: &str) -> Vec<Option<Heading>> {
+    // (level, count) of the enclosing headings
+    let mut counters: Vec<(usize, usize)> = Vec::new();
+    let mut index = 0;
+    nodes
+        .iter()
+        .map(|node| {
+            let level = heading_level(body, node.span.start)?;
+            counters.retain(|(l, _)| *l <= level);
+            match counters.last_mut() {
+                Some((l, count)) if *l == level => *count += 1,
+                _ => counters.push((level, 1)),
+            }
+            index += 1;
+            Some(Heading {
+                title: node.statement.trim().trim_end_matches(':').to_string(),
+                level,
+                index: index - 1,
+                number: counters
+                    .iter()
+                    .map(|(_, c)| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join("."),
+            })
+        })
+        .collect()
+}
+
+/// `heading_numbers:` fields the statements under a heading get its `index` and `number` in,
+/// for builders that care about authoring order (tutorial steps, chapters).
+#[derive(Debug, Clone, Default)]
+pub struct HeadingNumbers {
+    index_field: Option<String>,
+    number_field: Option<String>,
+}
+
+impl HeadingNumbers {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let field = |key: &str| match &y[key] {
+            Yaml::BadValue => Ok(None),
+            Yaml::String(s) => Ok(Some(s.clone())),
+            _ => Err(ConfigError::Invalid(format!(
+                "heading_numbers.{} must be a field name",
+                key
+            ))),
+        };
+        Ok(Self {
+            index_field: field("index")?,
+            number_field: field("number")?,
+        })
+    }
+
+    /// Stores `heading_index`, `heading_number` and `heading_level` in the `parse_data` of
+    /// every heading, and the same of the enclosing heading as `section_*` in the statements
+    /// under it, along with the configured fields.
+    pub fn apply(&self, nodes: &mut [DokeNode], body: &str) {
+        let headings = number_headings(nodes, body);
+        let mut current: Option<&Heading> = None;
+        for (node, heading) in nodes.iter_mut().zip(&headings) {
+            if let Some(heading) = heading {
+                set_numbers(node, "heading", heading);
+                current = Some(heading);
+                for child in &mut node.children {
+                    self.apply_to_statement(child, heading);
+                }
+            } else if let Some(heading) = current {
+                self.apply_to_statement(node, heading);
+            }
+        }
+    }
+
+    fn apply_to_statement(&self, node: &mut DokeNode, heading: &Heading) {
+        set_numbers(node, "section", heading);
+        let fields: Vec<(usize, String, GodotValue)> = [
+            (&self.index_field, GodotValue::Int(heading.index as i64)),
+            (
+                &self.number_field,
+                GodotValue::String(heading.number.clone()),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(field, value)| Some((0, field.clone()?, value)))
+        .collect();
+        add_fields(node, &fields);
+    }
+}
+
+fn set_numbers(node: &mut DokeNode, prefix: &str, heading: &Heading) {
+    let data = &mut node.parse_data;
+    data.insert(
+        format!("{}_index", prefix),
+        GodotValue::Int(heading.index as i64),
+    );
+    data.insert(
+        format!("{}_number", prefix),
+        GodotValue::String(heading.number.clone()),
+    );
+    data.insert(
+        format!("{}_level", prefix),
+        GodotValue::Int(heading.level as i64),
+    );
+}
+
+/// Level of the markdown heading a statement starting at `start` is, if it is one.
+/// The headings with no statement under them : nothing before the next heading of the
+/// same or a higher level, or the end of the document.
+pub fn empty_headings<'a>(nodes: &'a [DokeNode], body: &str) -> Vec<&'a DokeNode> {
+    let levels: Vec<Option<usize>> = nodes
+        .iter()
+        .map(|n| heading_level(body, n.span.start))
+        .collect();
+    nodes
+        .iter()
+        .enumerate()
+        .filter(|(i, node)| {
+            let Some(level) = levels[*i] else {
+                return false;
+            };
+            let next = levels.get(i + 1).copied();
+            node.children.is_empty() && next.is_none_or(|n| n.is_some_and(|n| n <= level))
+        })
+        .map(|(_, node)| node)
+        .collect()
+}
+
+fn heading_level(body: &str, start: usize) -> Option<usize> {
+    let before = body.get(..start)?;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let marker = before[line_start..].trim();
+    (!marker.is_empty() && marker.chars().all(|c| c == '#')).then_some(marker.len())
+}
+
+/// Adds the fields as already resolved constituents, which validation hands to the statement's value.
+fn add_fields(node: &mut DokeNode, fields: &[(usize, String, GodotValue)]) {
+    for (_, field, value) in fields {
+        let constituent = DokeNode {
+            statement: node.statement.clone(),
+            state: DokeNodeState::Resolved(Box::new(value.clone())),
+            children: Vec::new(),
+            parse_data: HashMap::new(),
+            constituents: HashMap::new(),
+            span: node.span.clone(),
+        };
+        node.constituents.insert(field.clone(), constituent);
+    }
+}
+
+// -----------------------
+// Sections built as sub-resources
+// -----------------------
+
+/// `sections:` headings whose statements build a sub-resource instead of the document's fields.
+#[derive(Debug, Clone, Default)]
+pub struct SectionResources {
+    sections: Vec<SectionResource>,
+}
+
+#[derive(Debug, Clone)]
+struct SectionResource {
+    /// Normalized heading text
+    heading: String,
+    /// Field of the document's resource the sub-resource goes in
+    field: String,
+    /// Built from the section's own `root` and `children`
+    builder: Arc<ResourceBuilder>,
+}
+
+/// The statements of each section found in a document, by section index.
+pub type SectionNodes = Vec<(usize, Vec<DokeNode>)>;
+
+impl SectionResources {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let mut sections = Self::default();
+        let Some(hash) = y.as_hash() else {
+            return Ok(sections);
+        };
+        for (heading, section) in hash {
+            let heading = heading
+                .as_str()
+                .ok_or_else(|| ConfigError::Invalid("'sections' keys must be headings".into()))?;
+            let invalid =
+                |e: String| ConfigError::Invalid(format!("Section '{}' : {}", heading, e));
+            let field = section["field"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| normalize_key(heading));
+            let builder = config::builder_config(section)
+                .and_then(ResourceBuilder::from_config)
+                .map_err(|e| invalid(e.to_string()))?;
+            sections.sections.push(SectionResource {
+                heading: normalize_key(heading),
+                field,
+                builder: builder.into(),
+            });
+        }
+        Ok(sections)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// Takes the statements of the configured sections out of the tree : the list right
+    /// under their heading and the following statements, until a heading of the same or a higher level.
+    pub fn split(&self, nodes: Vec<DokeNode>, body: &str) -> (Vec<DokeNode>, SectionNodes) {
+        let mut rest = Vec::with_capacity(nodes.len());
+        let mut found: SectionNodes = Vec::new();
+        // (heading level, index in `found`) of the section being read
+        let mut current: Option<(usize, usize)> = None;
+        for mut node in nodes {
+            if let Some(level) = heading_level(body, node.span.start) {
+                if current.is_some_and(|(l, _)| level <= l) {
+                    current = None;
+                }
+                let key = normalize_key(node.statement.trim_end_matches(':'));
+                if let Some(index) = self.sections.iter().position(|s| s.heading == key) {
+                    let slot = match found.iter().position(|(i, _)| *i == index) {
+                        Some(slot) => slot,
+                        None => {
+                            found.push((index, Vec::new()));
+                            found.len() - 1
+                        }
+                    };
+                    found[slot].1.append(&mut node.children);
+                    current = Some((level, slot));
+                    continue;
+                }
+            }
+            match current {
+                Some((_, slot)) => found[slot].1.push(node),
+                None => rest.push(node),
+            }
+        }
+        (rest, found)
+    }
+
+    /// Validates and builds the sections taken by `split`, and sets them on the document's value.
+    pub fn build(
+        &self,
+        value: &mut GodotValue,
+        found: SectionNodes,
+        frontmatter: &HashMap<String, GodotValue>,
+    ) -> Result<(), ImportError> {
+        let GodotValue::Resource { fields, .. } = value else {
+            return Err(ImportError::NotAResource(value.clone()));
+        };
+        for (index, mut nodes) in found {
+            let section = &self.sections[index];
+            let values = DokeValidate::validate_tree(&mut nodes, frontmatter)?;
+            fields.insert(
+                section.field.clone(),
+                section.builder.build_file_resource(values)?,
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+// source.rs
+// Where a built resource comes from, stamped as metadata so runtime debugging
+// and editor tooling can trace it back to its markdown document.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use godot::classes::Resource;
+use godot::prelude::*;
+
+use crate::variables::Variables;
+
+pub const SOURCE_PATH_META: &str = "doke_source";
+pub const SOURCE_HASH_META: &str = "doke_source_hash";
+pub const IMPORTED_AT_META: &str = "doke_imported_at";
+pub const VERSION_META: &str = "doke_version";
+pub const PARSER_VERSIONS_META: &str = "doke_parser_versions";
+
+/// The document a resource was built from, read once for all the resources it makes.
+#[derive(Debug, Clone)]
+pub struct Source {
+    path: String,
+    /// The content, with the `{{variables}}` and extended documents it is prepared with
+    hash: u64,
+    /// The parsers of the file type's pipe with their versions, see `RegisteredParser::versions`
+    parsers: String,
+    imported_at: u64,
+}
+
+impl Source {
+    pub fn of(md_path: &str, parsers: &str, variables: &Variables, extended: &[PathBuf]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        std::fs::read(md_path).unwrap_or_default().hash(&mut hasher);
+        variables.hash().hash(&mut hasher);
+        for path in extended {
+            std::fs::read(path).unwrap_or_default().hash(&mut hasher);
+        }
+        Self {
+            path: md_path.to_string(),
+            hash: hasher.finish(),
+            parsers: parsers.to_string(),
+            imported_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        }
+    }
+
+    /// The content hash, mixed with the parser versions : a resource imported before
+    /// a parser's version changed doesn't match anymore.
+    pub fn hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.hash, &self.parsers).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether a resource stamped earlier was built from this content by these parsers.
+    pub fn is_stamp_of(&self, res: &Gd<Resource>) -> bool {
+        let meta = |key: &str| {
+            res.get_meta_ex(key)
+                .default(&"".to_variant())
+                .done()
+                .to_string()
+        };
+        meta(SOURCE_HASH_META) == format!("{:016x}", self.hash)
+            && meta(PARSER_VERSIONS_META) == self.parsers
+    }
+
+    /// Names the resource after its document when nothing else named it,
+    /// and sets the `doke_*` metadata.
+    pub fn stamp(&self, res: &mut Gd<Resource>) {
+        if res.get_name().is_empty() {
+            let name = Path::new(&self.path)
+                .file_stem()
+                .map_or(self.path.clone(), |n| n.to_string_lossy().into_owned());
+            res.set_name(&name);
+        }
+        res.set_meta(SOURCE_PATH_META, &self.path.to_variant());
+        // Hex, as a u64 doesn't fit Godot's signed ints
+        res.set_meta(
+            SOURCE_HASH_META,
+            &format!("{:016x}", self.hash).to_variant(),
+        );
+        res.set_meta(IMPORTED_AT_META, &(self.imported_at as i64).to_variant());
+        res.set_meta(VERSION_META, &env!("CARGO_PKG_VERSION").to_variant());
+        res.set_meta(PARSER_VERSIONS_META, &self.parsers.to_variant());
+    }
+}
@@ -0,0 +1,65 @@
+// span.rs
+// Where an error is in its document, by line and column, so authors and editor
+// tools can go straight to the text to fix.
+
+use std::fmt;
+
+/// A range of a document, 1-based and inclusive. Columns count characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceSpan {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl SourceSpan {
+    pub fn point(line: usize, column: usize) -> Self {
+        Self {
+            start_line: line,
+            start_column: column,
+            end_line: line,
+            end_column: column,
+        }
+    }
+
+    /// The span of the bytes `start..end` of `text`, `text` starting at `first_line` of the document.
+    pub fn of_range(text: &str, start: usize, end: usize, first_line: usize) -> Self {
+        let (start_line, start_column) = line_column(text, start, first_line);
+        let last = end.saturating_sub(1).max(start);
+        let (end_line, end_column) = line_column(text, last, first_line);
+        Self {
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+        }
+    }
+}
+
+/// Line and column of a byte of `text`, `text` starting at `first_line`.
+fn line_column(text: &str, offset: usize, first_line: usize) -> (usize, usize) {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        first_line + before.matches('\n').count(),
+        1 + before[line_start..].chars().count(),
+    )
+}
+
+impl fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.start_line, self.start_column)?;
+        if self.start_line != self.end_line {
+            write!(f, "-{}:{}", self.end_line, self.end_column)
+        } else if self.start_column != self.end_column {
+            write!(f, "-{}", self.end_column)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,296 @@
+// spreadsheet.rs
+// CSV export of parsed documents, straight from the `GodotValue`s the
+// pipeline produces, so balancing spreadsheets can be generated without Godot.
+
+use std::collections::HashMap;
+
+use doke::GodotValue;
+
+/// Column holding the document path, always first.
+pub const PATH_COLUMN: &str = "path";
+
+/// Finds a field in a built document : first in the built resource fields,
+/// then in the frontmatter. `stats.damage` looks into nested values.
+pub fn lookup<'a>(
+    value: &'a GodotValue,
+    frontmatter: &'a HashMap<String, GodotValue>,
+    field: &str,
+) -> Option<&'a GodotValue> {
+    let mut parts = field.split('.');
+    let first = parts.next()?;
+    let mut current = match value {
+        GodotValue::Resource { fields, .. } => fields.get(first),
+        _ => None,
+    }
+    .or_else(|| frontmatter.get(first))?;
+    for part in parts {
+        current = match current {
+            GodotValue::Resource { fields, .. } => fields.get(part)?,
+            GodotValue::Dict(map) => map.get(part)?,
+            GodotValue::Array(items) => items.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+/// Text of a value in a cell. Strings aren't quoted, missing values are empty.
+pub fn cell(value: Option<&GodotValue>) -> String {
+    match value {
+        None | Some(GodotValue::Nil) => String::new(),
+        Some(GodotValue::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Escapes a cell per RFC 4180.
+pub fn escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+pub fn write_row(out: &mut String, cells: &[String]) {
+    let escaped: Vec<String> = cells.iter().map(|c| escape(c)).collect();
+    out.push_str(&escaped.join(","));
+    out.push('\n');
+}
+
+// -----------------------
+// Re-import : reading CSVs and writing values back into documents
+// -----------------------
+
+/// Parses CSV text into rows of cells (RFC 4180 quoting).
+pub fn parse(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if cell.is_empty() => in_quotes = true,
+            (',', false) => row.push(std::mem::take(&mut cell)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut cell));
+                rows.push(std::mem::take(&mut row));
+            }
+            (c, _) => cell.push(c),
+        }
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Line ranges of a document : (frontmatter lines, doke section lines),
+/// both excluding the `---` separators.
+fn sections(lines: &[&str]) -> (std::ops::Range<usize>, std::ops::Range<usize>) {
+    let separators: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| l.trim() == "---")
+        .map(|(i, _)| i)
+        .take(3)
+        .collect();
+    match separators[..] {
+        [a, b, c] => (a + 1..b, b + 1..c),
+        [a, b] => (a + 1..b, b + 1..lines.len()),
+        _ => (0..0, 0..lines.len()),
+    }
+}
+
+/// A value of a CSV row differing from the document.
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+    pub result: WriteBack,
+}
+
+/// Writes the cells of `row` that differ from the parsed document into `text`.
+/// Values set by the frontmatter are edited there, values built from statements
+/// are replaced in the statement they appear in. Lists, maps and resources are skipped.
+pub fn apply_row(
+    text: &mut String,
+    value: &GodotValue,
+    frontmatter: &HashMap<String, GodotValue>,
+    header: &[String],
+    row: &[String],
+    skip: &[&str],
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (field, new) in header.iter().zip(row) {
+        if skip.contains(&field.as_str()) {
+            continue;
+        }
+        let current = lookup(value, frontmatter, field);
+        if matches!(
+            current,
+            Some(GodotValue::Array(_) | GodotValue::Dict(_) | GodotValue::Resource { .. })
+        ) {
+            continue;
+        }
+        let old = cell(current);
+        let same_number = matches!(
+            (old.parse::<f64>(), new.parse::<f64>()),
+            (Ok(a), Ok(b)) if a == b
+        );
+        if old == *new || same_number {
+            continue;
+        }
+        let from_statement = field.contains('.')
+            || matches!(value, GodotValue::Resource { fields, .. } if fields.contains_key(field));
+        let result = if from_statement {
+            replace_in_statements(text, &old, new)
+        } else {
+            set_frontmatter_value(text, &crate::frontmatter::normalize_key(field), new)
+        };
+        changes.push(Change {
+            field: field.clone(),
+            old,
+            new: new.clone(),
+            result,
+        });
+    }
+    changes
+}
+
+/// Outcome of writing one value back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteBack {
+    Written,
+    /// The value comes from a `{{variable}}`, editing it would lose the reference
+    Templated,
+    /// The old value isn't in the document exactly once
+    NotFound,
+}
+
+/// Sets a top-level frontmatter key, keeping the other lines untouched.
+/// A key that isn't written in the document (inherited or defaulted) is added.
+pub fn set_frontmatter_value(text: &mut String, key: &str, value: &str) -> WriteBack {
+    let lines: Vec<&str> = text.lines().collect();
+    let (fm, _) = sections(&lines);
+    let mut new_lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+
+    let existing = fm.clone().find(|&i| {
+        let line = lines[i];
+        !line.starts_with([' ', '\t'])
+            && line
+                .split_once(':')
+                .is_some_and(|(k, _)| crate::frontmatter::normalize_key(k) == key)
+    });
+    match existing {
+        Some(i) => {
+            let (k, old) = lines[i].split_once(':').unwrap_or_default();
+            if old.contains("{{") {
+                return WriteBack::Templated;
+            }
+            if old.trim().is_empty() {
+                // Block values (lists, maps) span several lines
+                return WriteBack::NotFound;
+            }
+            new_lines[i] = format!("{}: {}", k, yaml_scalar(value, old.trim()));
+        }
+        None if fm.end > 0 => {
+            new_lines.insert(fm.end, format!("{}: {}", key, yaml_scalar(value, "")))
+        }
+        None => return WriteBack::NotFound,
+    }
+    *text = join_lines(new_lines, text.ends_with('\n'));
+    WriteBack::Written
+}
+
+/// Replaces a value written in a statement, if it appears exactly once as a
+/// whole word in the doke section. A value that isn't written but could come
+/// from a statement's `{{variable}}` is left alone as templated.
+pub fn replace_in_statements(text: &mut String, old: &str, new: &str) -> WriteBack {
+    if old.is_empty() {
+        return WriteBack::NotFound;
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let (_, body) = sections(&lines);
+    let mut found = None;
+    for i in body.clone() {
+        let line = lines[i];
+        for (pos, _) in line.match_indices(old) {
+            let mut before = line[..pos].chars().rev();
+            let mut after = line[pos + old.len()..].chars();
+            let (first, last) = (old.chars().next(), old.chars().next_back());
+            if continues_word(before.next(), before.next(), first)
+                || continues_word(after.next(), after.next(), last)
+            {
+                continue;
+            }
+            if found.is_some() {
+                return WriteBack::NotFound;
+            }
+            found = Some((i, pos));
+        }
+    }
+    let Some((i, pos)) = found else {
+        if lines[body].iter().any(|line| is_templated(line)) {
+            return WriteBack::Templated;
+        }
+        return WriteBack::NotFound;
+    };
+    let mut new_lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    new_lines[i].replace_range(pos..pos + old.len(), new);
+    *text = join_lines(new_lines, text.ends_with('\n'));
+    WriteBack::Written
+}
+
+/// Whether the character `next` to a value makes it part of a longer word : a letter
+/// or digit, or a '.' between digits like in `2.5`. `beyond` is the character past
+/// `next`, `edge` the value's own character next to it.
+fn continues_word(next: Option<char>, beyond: Option<char>, edge: Option<char>) -> bool {
+    let digit = |c: Option<char>| c.is_some_and(|c| c.is_ascii_digit());
+    match next {
+        Some('.') => digit(beyond) && digit(edge),
+        Some(c) => c.is_alphanumeric(),
+        None => false,
+    }
+}
+
+/// Whether a statement has a `{{variable}}`, whose value isn't written in the document.
+fn is_templated(line: &str) -> bool {
+    line.find("{{")
+        .is_some_and(|start| line[start..].contains("}}"))
+}
+
+fn join_lines(lines: Vec<String>, trailing_newline: bool) -> String {
+    let mut out = lines.join("\n");
+    if trailing_newline {
+        out.push('\n');
+    }
+    out
+}
+
+/// Writes a value as a yaml scalar, keeping the quote style of the old value.
+fn yaml_scalar(value: &str, old: &str) -> String {
+    let quote = match old.chars().next() {
+        Some(q @ ('"' | '\'')) => Some(q),
+        _ => None,
+    };
+    let plain = value.parse::<f64>().is_ok()
+        || !(value.is_empty()
+            || value.contains([':', '#', '"', '\'', '[', ']', '{', '}', ','])
+            || value.trim() != value);
+    match quote {
+        Some('\'') => format!("'{}'", value.replace('\'', "''")),
+        Some(_) => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+        None if plain => value.to_string(),
+        None => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+    }
+}
@@ -0,0 +1,198 @@
+// stats.rs
+// Counts and durations of the imports of a session, appended to a history
+// file so the trend over months of content growth can be looked at, and the
+// stage timings of the last import to find what makes it slow.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use godot::classes::ProjectSettings;
+use godot::prelude::*;
+
+use crate::spreadsheet;
+
+/// Where the history goes, under the project's `.godot` folder.
+const HISTORY_PATH: &str = "res://.godot/doke_stats/history.csv";
+const HISTORY_HEADER: [&str; 5] = ["timestamp", "file_type", "documents", "errors", "total_ms"];
+
+pub fn history_path() -> PathBuf {
+    PathBuf::from(
+        ProjectSettings::singleton()
+            .globalize_path(HISTORY_PATH)
+            .to_string(),
+    )
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct FileTypeStats {
+    documents: u64,
+    errors: u64,
+    duration: Duration,
+}
+
+/// Imports since the last time the stats were saved, by file type.
+#[derive(Debug, Default)]
+pub struct ImportStats {
+    by_type: BTreeMap<String, FileTypeStats>,
+}
+
+impl ImportStats {
+    pub fn record(&mut self, file_type: &str, duration: Duration, ok: bool) {
+        let stats = self.by_type.entry(file_type.to_string()).or_default();
+        stats.documents += 1;
+        stats.errors += u64::from(!ok);
+        stats.duration += duration;
+    }
+
+    /// file type -> Dictionary of `documents`, `errors` and `total_ms`.
+    pub fn to_dictionary(&self) -> Dictionary {
+        let mut d = Dictionary::new();
+        for (file_type, stats) in &self.by_type {
+            d.set(
+                file_type.as_str(),
+                run_dictionary(
+                    None,
+                    stats.documents,
+                    stats.errors,
+                    stats.duration.as_secs_f64() * 1000.0,
+                ),
+            );
+        }
+        d
+    }
+
+    /// Appends one row per file type to the history, then starts counting again.
+    pub fn save(&mut self, path: &Path) -> std::io::Result<()> {
+        if self.by_type.is_empty() {
+            return Ok(());
+        }
+        let mut out = String::new();
+        if !path.exists() {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            spreadsheet::write_row(&mut out, &HISTORY_HEADER.map(String::from));
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        for (file_type, stats) in std::mem::take(&mut self.by_type) {
+            spreadsheet::write_row(
+                &mut out,
+                &[
+                    timestamp.to_string(),
+                    file_type,
+                    stats.documents.to_string(),
+                    stats.errors.to_string(),
+                    format!("{:.3}", stats.duration.as_secs_f64() * 1000.0),
+                ],
+            );
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        std::io::Write::write_all(&mut file, out.as_bytes())
+    }
+}
+
+/// How long each stage of one import took.
+#[derive(Debug, Clone)]
+pub struct StageTimings {
+    file_type: String,
+    path: String,
+    stages: Vec<(&'static str, Duration)>,
+    /// The end of the last stage, the start of the next one
+    last: Instant,
+}
+
+impl Default for StageTimings {
+    fn default() -> Self {
+        Self::start("", "")
+    }
+}
+
+impl StageTimings {
+    pub fn start(file_type: &str, path: &str) -> Self {
+        Self {
+            file_type: file_type.to_string(),
+            path: path.to_string(),
+            stages: Vec::new(),
+            last: Instant::now(),
+        }
+    }
+
+    /// Ends `stage`, which started when the previous one ended.
+    pub fn lap(&mut self, stage: &'static str) {
+        let now = Instant::now();
+        self.stages.push((stage, now - self.last));
+        self.last = now;
+    }
+
+    /// `file_type`, `path`, `total_ms` and `stages`, stage name -> ms in the order they ran.
+    pub fn to_dictionary(&self) -> Dictionary {
+        let mut stages = Dictionary::new();
+        for (stage, duration) in &self.stages {
+            stages.set(*stage, duration.as_secs_f64() * 1000.0);
+        }
+        let total: Duration = self.stages.iter().map(|(_, d)| *d).sum();
+        let mut d = Dictionary::new();
+        d.set("file_type", self.file_type.as_str());
+        d.set("path", self.path.as_str());
+        d.set("total_ms", total.as_secs_f64() * 1000.0);
+        d.set("stages", stages);
+        d
+    }
+}
+
+/// The saved runs of a file type, oldest first, or of every type if `file_type` is empty.
+pub fn read_history(path: &Path, file_type: &str) -> std::io::Result<Array<Dictionary>> {
+    if !path.exists() {
+        return Ok(Array::new());
+    }
+    let rows = spreadsheet::parse(&std::fs::read_to_string(path)?);
+    Ok(rows
+        .iter()
+        .skip(1)
+        .filter(|row| file_type.is_empty() || row.get(1).is_some_and(|t| t == file_type))
+        .filter_map(|row| {
+            let [timestamp, ty, documents, errors, total_ms] = row.as_slice() else {
+                return None;
+            };
+            let mut d = run_dictionary(
+                Some(timestamp.parse().ok()?),
+                documents.parse().ok()?,
+                errors.parse().ok()?,
+                total_ms.parse().ok()?,
+            );
+            d.set("file_type", ty.as_str());
+            Some(d)
+        })
+        .collect())
+}
+
+fn run_dictionary(
+    timestamp: Option<u64>,
+    documents: u64,
+    errors: u64,
+    total_ms: f64,
+) -> Dictionary {
+    let mut d = Dictionary::new();
+    if let Some(timestamp) = timestamp {
+        d.set("timestamp", timestamp as i64);
+    }
+    d.set("documents", documents as i64);
+    d.set("errors", errors as i64);
+    d.set("total_ms", total_ms);
+    d.set(
+        "average_ms",
+        if documents == 0 {
+            0.0
+        } else {
+            total_ms / documents as f64
+        },
+    );
+    d
+}
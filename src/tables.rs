@@ -0,0 +1,192 @@
+// tables.rs
+// Markdown tables and CSV files read as lists of rows (`tables:` in a config),
+// for data authored one row per entry, like the waves of a level.
+
+use std::collections::HashMap;
+
+use doke::GodotValue;
+use markdown::ParseOptions;
+use markdown::mdast::Node;
+use yaml_rust2::Yaml;
+
+use crate::config::{Coercion, ConfigError};
+use crate::file_error::ErrorContext;
+use crate::frontmatter::{normalize_key, yaml_to_godot};
+use crate::import::ImportError;
+use crate::parser_context::ParserContext;
+use crate::safe_mode::SafeModeError;
+use crate::spreadsheet;
+
+/// `tables:` field of the built resource -> where its rows come from.
+#[derive(Debug, Clone, Default)]
+pub struct Tables {
+    tables: Vec<Table>,
+}
+
+#[derive(Debug, Clone)]
+struct Table {
+    field: String,
+    /// `heading:` the table under this heading of the wiki part, the first one without
+    heading: Option<String>,
+    /// `type:` class of the rows, Dictionaries without
+    type_name: Option<String>,
+    /// `columns:` normalized column -> type of its cells, guessed for the others
+    columns: HashMap<String, Coercion>,
+}
+
+fn normalize_title(title: &str) -> String {
+    title.trim().trim_end_matches(':').trim().to_lowercase()
+}
+
+impl Tables {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let mut tables = Self::default();
+        let Some(hash) = y.as_hash() else {
+            return Ok(tables);
+        };
+        for (field, table) in hash {
+            let field = field
+                .as_str()
+                .ok_or_else(|| ConfigError::Invalid("'tables' keys must be strings".into()))?;
+            let invalid = |e: &str| ConfigError::Invalid(format!("tables.{} : {}", field, e));
+            let mut columns = HashMap::new();
+            for (column, ty) in table["columns"].as_hash().into_iter().flatten() {
+                let (Some(column), Some(ty)) = (column.as_str(), ty.as_str()) else {
+                    return Err(invalid("'columns' entries must be `column: type`"));
+                };
+                let coercion = Coercion::from_name(ty)
+                    .ok_or_else(|| invalid("column types are int, float, String or bool"))?;
+                columns.insert(normalize_key(column), coercion);
+            }
+            tables.tables.push(Table {
+                field: field.to_string(),
+                heading: table["heading"].as_str().map(normalize_title),
+                type_name: table["type"].as_str().map(str::to_string),
+                columns,
+            });
+        }
+        Ok(tables)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Sets the rows of each table on the document's resource. A frontmatter key named
+    /// like the field and ending in `.csv` reads that file instead, relative to the
+    /// document or from `res://`. Tables the document doesn't have are left alone.
+    pub fn apply(
+        &self,
+        value: &mut GodotValue,
+        frontmatter: &HashMap<String, GodotValue>,
+        wiki: &str,
+        context: &ParserContext,
+        safe_mode: bool,
+    ) -> Result<(), ImportError> {
+        let GodotValue::Resource { fields, .. } = value else {
+            return Ok(());
+        };
+        let root = markdown::to_mdast(wiki, &ParseOptions::gfm()).ok();
+        for table in &self.tables {
+            let rows = match frontmatter.get(&normalize_key(&table.field)) {
+                Some(GodotValue::String(csv)) if csv.ends_with(".csv") => {
+                    if safe_mode {
+                        return Err(SafeModeError::PathLoading(csv.clone()).into());
+                    }
+                    let path = context.file_to_read(csv)?;
+                    spreadsheet::parse(&std::fs::read_to_string(&path).in_file(&path)?)
+                }
+                _ => match root
+                    .as_ref()
+                    .and_then(|root| markdown_table(root, &table.heading))
+                {
+                    Some(rows) => rows,
+                    None => continue,
+                },
+            };
+            fields.insert(table.field.clone(), table.build(rows));
+        }
+        Ok(())
+    }
+}
+
+/// The cells of the first table under `heading`, or of the first table, header row first.
+fn markdown_table(root: &Node, heading: &Option<String>) -> Option<Vec<Vec<String>>> {
+    let mut current = None;
+    for node in root.children()? {
+        match node {
+            Node::Heading(_) => current = Some(normalize_title(&node.to_string())),
+            Node::Table(table) if heading.is_none() || *heading == current => {
+                return Some(
+                    table
+                        .children
+                        .iter()
+                        .map(|row| {
+                            row.children()
+                                .into_iter()
+                                .flatten()
+                                .map(|cell| cell.to_string().trim().to_string())
+                                .collect()
+                        })
+                        .collect(),
+                );
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+impl Table {
+    /// A row per line after the header, its columns named by the header.
+    fn build(&self, rows: Vec<Vec<String>>) -> GodotValue {
+        let mut rows = rows.into_iter();
+        let header: Vec<String> = rows
+            .next()
+            .unwrap_or_default()
+            .iter()
+            .map(|c| normalize_key(c))
+            .collect();
+        let rows = rows
+            .filter(|row| row.iter().any(|c| !c.trim().is_empty()))
+            .map(|row| {
+                let cells = header
+                    .iter()
+                    .zip(row)
+                    .map(|(column, cell)| (column.clone(), self.cell(column, &cell)))
+                    .collect();
+                match &self.type_name {
+                    Some(type_name) => GodotValue::Resource {
+                        type_name: type_name.clone(),
+                        abstract_type_name: type_name.clone(),
+                        fields: cells,
+                    },
+                    None => GodotValue::Dict(cells),
+                }
+            })
+            .collect();
+        GodotValue::Array(rows)
+    }
+
+    /// A cell as the type of its column, or as what it looks like.
+    fn cell(&self, column: &str, cell: &str) -> GodotValue {
+        let cell = cell.trim();
+        if let Some(coercion) = self.columns.get(column) {
+            return coercion
+                .apply(&Yaml::String(cell.to_string()))
+                .map(yaml_to_godot)
+                .unwrap_or(GodotValue::Nil);
+        }
+        if cell.is_empty() {
+            GodotValue::Nil
+        } else if let Ok(i) = cell.parse() {
+            GodotValue::Int(i)
+        } else if let Ok(f) = cell.parse() {
+            GodotValue::Float(f)
+        } else if let Ok(b) = cell.parse() {
+            GodotValue::Bool(b)
+        } else {
+            GodotValue::String(cell.to_string())
+        }
+    }
+}
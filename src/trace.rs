@@ -0,0 +1,132 @@
+// trace.rs
+// Opt-in record of a document after each stage of the import, for tools
+// that step through what every stage did to it.
+
+use std::fmt::Write;
+
+use doke::semantic::DokeNodeState;
+use doke::{DokeNode, GodotValue};
+use godot::prelude::*;
+
+use crate::parser_api::HANDLED_BY_KEY;
+
+/// The stages an import records, in order. Stages that don't apply to a document
+/// (no frontmatter rules, nothing dropped) are skipped, `error` ends failed imports.
+pub const STAGES: &[&str] = &[
+    "raw",
+    "pre_parse",
+    "variables",
+    "extends",
+    "frontmatter_rules",
+    "nodes",
+    "dropped",
+    "frontmatter",
+    "matched",
+    "values",
+    "resource",
+    "error",
+];
+
+#[derive(Debug, Default)]
+pub struct Trace {
+    enabled: bool,
+    stages: Vec<(&'static str, String)>,
+}
+
+impl Trace {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            stages: Vec::new(),
+        }
+    }
+
+    /// Records a stage. The text is only built when tracing is enabled.
+    pub fn record(&mut self, stage: &'static str, text: impl FnOnce() -> String) {
+        debug_assert!(STAGES.contains(&stage), "{} isn't in trace::STAGES", stage);
+        if self.enabled {
+            self.stages.push((stage, text()));
+        }
+    }
+
+    /// One Dictionary per stage : `stage` and `text`.
+    pub fn to_array(&self) -> Array<Dictionary> {
+        self.stages
+            .iter()
+            .map(|(stage, text)| {
+                let mut d = Dictionary::new();
+                d.set("stage", *stage);
+                d.set("text", text.as_str());
+                d
+            })
+            .collect()
+    }
+}
+
+/// The node tree, one statement per line with its state.
+pub fn format_nodes(nodes: &[DokeNode]) -> String {
+    let mut out = String::new();
+    write_nodes(&mut out, nodes, 0);
+    out
+}
+
+fn write_nodes(out: &mut String, nodes: &[DokeNode], depth: usize) {
+    for node in nodes {
+        let state = match &node.state {
+            DokeNodeState::Unresolved => "unresolved".to_string(),
+            DokeNodeState::Resolved(value) => {
+                format!("{} -> {}", value.kind(), value.to_godot())
+            }
+            DokeNodeState::Hypothesis(hypos) => {
+                let kinds: Vec<String> = hypos
+                    .iter()
+                    .map(|h| format!("{} ({})", h.kind(), h.confidence()))
+                    .collect();
+                format!("hypotheses : {}", kinds.join(", "))
+            }
+            DokeNodeState::Error(e) => format!("error : {}", e),
+        };
+        let state = match node.parse_data.get(HANDLED_BY_KEY) {
+            Some(parser) => format!("{} (by {})", state, parser),
+            None => state,
+        };
+        let _ = writeln!(
+            out,
+            "{}- {} [{}] : {}",
+            "  ".repeat(depth),
+            node.statement.trim(),
+            node.span,
+            state
+        );
+        let mut constituents: Vec<_> = node.constituents.iter().collect();
+        constituents.sort_by_key(|(name, _)| name.as_str());
+        for (name, constituent) in constituents {
+            let _ = writeln!(out, "{}  {{{}}} :", "  ".repeat(depth), name);
+            write_nodes(out, std::slice::from_ref(constituent), depth + 2);
+        }
+        write_nodes(out, &node.children, depth + 1);
+    }
+}
+
+/// The values validation produced, one per line.
+pub fn format_values(values: &[GodotValue]) -> String {
+    values.iter().map(|v| format!("{}\n", v)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::STAGES;
+
+    /// Every stage recorded by the import is listed, so the list the docs point at stays true.
+    #[test]
+    fn recorded_stages_are_listed() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let source = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            for recorded in source.split("trace.record(\"").skip(1) {
+                let stage = &recorded[..recorded.find('"').unwrap()];
+                assert!(STAGES.contains(&stage), "{} isn't in STAGES", stage);
+            }
+        }
+    }
+}
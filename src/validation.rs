@@ -0,0 +1,261 @@
+// validation.rs
+// Checks on a built document that doke's builder doesn't do, reported with
+// the place in the document where the fix goes.
+
+use std::collections::HashMap;
+
+use doke::GodotValue;
+use doke::semantic::{DokeNode, DokeNodeState, DokeValidationError};
+use markdown::mdast::Node;
+use thiserror::Error;
+use yaml_rust2::Yaml;
+
+use crate::config::{ConfigError, Limits};
+use crate::grammar::{self, Pattern};
+use crate::lint::Finding;
+use crate::parser_api::HANDLED_BY_KEY;
+use crate::span::SourceSpan;
+use crate::spreadsheet;
+
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("{0}:{1} : missing required fields {missing}", missing = .2.join(", "))]
+    MissingFields(String, SourceSpan, Vec<String>),
+    #[error("{0}")]
+    Statement(Box<StatementError>),
+    #[error("{count} problems :\n{list}", count = .0.len(), list = list(.0))]
+    Many(Vec<ValidationError>),
+    #[error("{0}:{span} : {message}", span = .1.span, message = .1.message)]
+    Lint(String, Box<Finding>),
+    #[error("{0}:{1} : markdown nests deeper than the limit of {2} levels")]
+    TooDeep(String, SourceSpan, usize),
+    #[error("{0} : more than the limit of {1} markdown nodes")]
+    TooManyNodes(String, usize),
+}
+
+/// A statement rejected by a parser or matching no sentence.
+#[derive(Debug, Error)]
+#[error("{path}:{span} : '{statement}' {message}")]
+pub struct StatementError {
+    pub path: String,
+    pub span: SourceSpan,
+    pub statement: String,
+    pub message: String,
+    /// The parser that rejected it, if one did
+    pub parser: Option<String>,
+    /// No parser matched it, rather than one rejecting it
+    pub unmatched: bool,
+    /// The sentence pattern it likely meant, when it matched none
+    pub suggestion: Option<String>,
+}
+
+fn list(errors: &[ValidationError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("  {}", e))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `required:` fields every document must set, in its statements or frontmatter.
+pub fn required_from_yaml(y: &Yaml) -> Result<Vec<String>, ConfigError> {
+    names_from_yaml(y, "required")
+}
+
+/// A list of field or key names under `key`, empty when the config doesn't have it.
+pub fn names_from_yaml(y: &Yaml, key: &str) -> Result<Vec<String>, ConfigError> {
+    let invalid = || ConfigError::Invalid(format!("'{}' must be a list of field names", key));
+    match y {
+        Yaml::BadValue => Ok(Vec::new()),
+        Yaml::Array(fields) => fields
+            .iter()
+            .map(|f| f.as_str().map(str::to_string).ok_or_else(invalid))
+            .collect(),
+        _ => Err(invalid()),
+    }
+}
+
+/// Lists the required fields missing from a built document. Fields are looked up
+/// like `export_csv` does, so `stats.health` checks a nested value.
+/// The span is the frontmatter (or first line), where missing values are usually added.
+pub fn check_required(
+    path: &str,
+    source: &str,
+    required: &[String],
+    value: &GodotValue,
+    frontmatter: &HashMap<String, GodotValue>,
+) -> Result<(), ValidationError> {
+    let missing: Vec<String> = required
+        .iter()
+        .filter(|field| {
+            matches!(
+                spreadsheet::lookup(value, frontmatter, field),
+                None | Some(GodotValue::Nil)
+            )
+        })
+        .cloned()
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(ValidationError::MissingFields(
+        path.to_string(),
+        frontmatter_span(source),
+        missing,
+    ))
+}
+
+/// The frontmatter, separators included.
+pub fn frontmatter_span(source: &str) -> SourceSpan {
+    let mut separators = source
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| l.trim() == "---")
+        .map(|(i, l)| (i + 1, l.chars().count()));
+    match (separators.next(), separators.next()) {
+        (Some((start_line, _)), Some((end_line, end_column))) => SourceSpan {
+            start_line,
+            start_column: 1,
+            end_line,
+            end_column,
+        },
+        _ => SourceSpan::point(1, 1),
+    }
+}
+
+/// Every statement of the tree that was rejected or matched no sentence, children
+/// included, where doke's validation stops at the first one of each top-level statement.
+/// `first_line` is the line of the document where `body` starts.
+pub fn statement_errors(
+    path: &str,
+    nodes: &[DokeNode],
+    body: &str,
+    first_line: usize,
+) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    for node in nodes {
+        collect_statement_errors(path, node, body, first_line, &mut errors);
+    }
+    errors
+}
+
+fn collect_statement_errors(
+    path: &str,
+    node: &DokeNode,
+    body: &str,
+    first_line: usize,
+    errors: &mut Vec<ValidationError>,
+) {
+    let message = match &node.state {
+        DokeNodeState::Error(e) => Some((e.to_string(), false)),
+        DokeNodeState::Unresolved => Some(("matches no sentence".to_string(), true)),
+        _ => None,
+    };
+    if let Some((message, unmatched)) = message {
+        let parser = match node.parse_data.get(HANDLED_BY_KEY) {
+            Some(GodotValue::String(parser)) => Some(parser.clone()),
+            _ => None,
+        };
+        errors.push(ValidationError::Statement(Box::new(StatementError {
+            path: path.to_string(),
+            span: SourceSpan::of_range(body, node.span.start, node.span.end, first_line),
+            statement: node.statement.trim().to_string(),
+            message,
+            parser,
+            unmatched,
+            suggestion: None,
+        })));
+    }
+    for child in node.children.iter().chain(node.constituents.values()) {
+        collect_statement_errors(path, child, body, first_line, errors);
+    }
+}
+
+/// Points the statements matching no sentence to the pattern they likely meant.
+pub fn suggest_sentences(errors: &mut [ValidationError], patterns: &[Pattern]) {
+    for error in errors {
+        if let ValidationError::Statement(e) = error
+            && e.unmatched
+            && let Some(pattern) = grammar::closest(patterns, &e.statement)
+        {
+            e.message = format!("{}, did you mean '{}' ?", e.message, pattern.pattern);
+            e.suggestion = Some(pattern.pattern.clone());
+        }
+    }
+}
+
+/// Gives doke's validation errors that have a position the span and statement they are about.
+/// Others are returned as they are.
+pub fn locate(
+    path: &str,
+    error: DokeValidationError,
+    nodes: &[DokeNode],
+    body: &str,
+    first_line: usize,
+) -> Result<ValidationError, DokeValidationError> {
+    match error {
+        DokeValidationError::HypothesisPromotionFailed(e, position) => {
+            let statement = statement_at(nodes, position.start).unwrap_or_default();
+            Ok(ValidationError::Statement(Box::new(StatementError {
+                path: path.to_string(),
+                span: SourceSpan::of_range(body, position.start, position.end, first_line),
+                statement,
+                message: e.to_string(),
+                parser: None,
+                unmatched: false,
+                suggestion: None,
+            })))
+        }
+        e => Err(e),
+    }
+}
+
+/// The innermost statement starting at `offset`.
+fn statement_at<'a>(
+    nodes: impl IntoIterator<Item = &'a DokeNode>,
+    offset: usize,
+) -> Option<String> {
+    nodes.into_iter().find_map(|node| {
+        statement_at(
+            node.children.iter().chain(node.constituents.values()),
+            offset,
+        )
+        .or_else(|| (node.span.start == offset).then(|| node.statement.trim().to_string()))
+    })
+}
+
+/// Checks the markdown of a body against the `limits`, before parsers and checks walk
+/// it recursively, so deeply nested lists or blockquotes fail instead of overflowing the stack.
+pub fn check_markdown_size(
+    path: &str,
+    root: &Node,
+    first_line: usize,
+    limits: &Limits,
+) -> Result<(), ValidationError> {
+    let mut count = 0;
+    let mut stack = vec![(root, 0)];
+    while let Some((node, depth)) = stack.pop() {
+        count += 1;
+        if count > limits.max_markdown_nodes {
+            return Err(ValidationError::TooManyNodes(
+                path.to_string(),
+                limits.max_markdown_nodes,
+            ));
+        }
+        if depth > limits.max_markdown_depth {
+            let span = match node.position() {
+                Some(p) => SourceSpan::point(first_line + p.start.line - 1, p.start.column),
+                None => SourceSpan::point(first_line, 1),
+            };
+            return Err(ValidationError::TooDeep(
+                path.to_string(),
+                span,
+                limits.max_markdown_depth,
+            ));
+        }
+        for child in node.children().into_iter().flatten() {
+            stack.push((child, depth + 1));
+        }
+    }
+    Ok(())
+}
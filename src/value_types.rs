@@ -0,0 +1,462 @@
+// value_types.rs
+// Sentence parameters of structured types (`{damage:dice}`), which doke would parse
+// as nested sentences. The `value_types` stage reads them into values once the
+// sentences are matched, so documents don't need string post-processing.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use doke::GodotValue;
+use doke::semantic::{DokeNode, DokeNodeState, DokeParser};
+use regex::Regex;
+use thiserror::Error;
+use yaml_rust2::Yaml;
+
+use crate::config::ConfigError;
+
+#[derive(Debug, Error)]
+pub enum ValueTypeError {
+    #[error("'{text}' isn't a {type_name} ({expected})")]
+    Invalid {
+        type_name: &'static str,
+        text: String,
+        expected: &'static str,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueType {
+    /// `2d6+3` -> `{count, sides, modifier}`
+    Dice,
+    /// `5s`, `2 turns` -> `{value, unit}`
+    Duration,
+    /// `+15%`, `15 percent` -> `{value, additive}`, additive when it has a sign
+    /// unless the parameter says
+    Percent(Option<bool>),
+    /// `3-7`, `between 3 and 7` -> `{min, max}`
+    Range,
+}
+
+impl ValueType {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "dice" => Self::Dice,
+            "duration" => Self::Duration,
+            "percent" | "percentage" => Self::Percent(None),
+            "percent_additive" => Self::Percent(Some(true)),
+            "percent_multiplicative" => Self::Percent(Some(false)),
+            "range" => Self::Range,
+            _ => return None,
+        })
+    }
+
+    /// The name `value_types:` gives its class under.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Dice => "dice",
+            Self::Duration => "duration",
+            Self::Percent(_) => "percent",
+            Self::Range => "range",
+        }
+    }
+
+    fn parse(self, text: &str) -> Result<HashMap<String, GodotValue>, ValueTypeError> {
+        match self {
+            Self::Dice => dice(text),
+            Self::Duration => duration(text),
+            Self::Percent(additive) => percent(text, additive),
+            Self::Range => range(text),
+        }
+    }
+
+    fn write(self, fields: &HashMap<String, GodotValue>) -> Option<String> {
+        let int = |key: &str| match fields.get(key) {
+            Some(GodotValue::Int(i)) => Some(*i),
+            _ => None,
+        };
+        let float = |key: &str| match fields.get(key) {
+            Some(GodotValue::Float(f)) => Some(*f),
+            Some(GodotValue::Int(i)) => Some(*i as f64),
+            _ => None,
+        };
+        match self {
+            Self::Dice => Some(match int("modifier").unwrap_or(0) {
+                0 => format!("{}d{}", int("count")?, int("sides")?),
+                m => format!("{}d{}{:+}", int("count")?, int("sides")?, m),
+            }),
+            Self::Duration => match int("unit")? {
+                TURNS => Some(match float("value")? {
+                    1.0 => "1 turn".to_string(),
+                    turns => format!("{} turns", turns),
+                }),
+                _ => Some(format!("{}s", float("value")?)),
+            },
+            Self::Percent(_) => {
+                let percent = float("value")? * 100.0;
+                // Rounded, as 0.15 * 100 isn't quite 15
+                let percent = (percent * 1e6).round() / 1e6;
+                match fields.get("additive") {
+                    Some(GodotValue::Bool(true)) if percent >= 0.0 => {
+                        Some(format!("+{}%", percent))
+                    }
+                    _ => Some(format!("{}%", percent)),
+                }
+            }
+            Self::Range => {
+                let (min, max) = (fields.get("min")?, fields.get("max")?);
+                match (float("min")? < 0.0, min == max) {
+                    (_, true) => Some(min.to_string()),
+                    (false, false) => Some(format!("{}-{}", min, max)),
+                    (true, false) => Some(format!("{} to {}", min, max)),
+                }
+            }
+        }
+    }
+}
+
+pub fn is_value_type(type_name: &str) -> bool {
+    ValueType::from_name(type_name).is_some()
+}
+
+/// The text of a value of the value type `type_name`, as documents write it.
+pub fn write(type_name: &str, value: &GodotValue) -> Option<String> {
+    let ty = ValueType::from_name(type_name)?;
+    match value {
+        GodotValue::Dict(fields) | GodotValue::Resource { fields, .. } => ty.write(fields),
+        _ => None,
+    }
+}
+
+static DICE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\d*)\s*[dD]\s*(\d+)\s*(?:([-+])\s*(\d+))?$").expect("valid regex")
+});
+
+/// `d6`, `2d6`, `2d6+3` or `1d20 - 1`.
+fn dice(text: &str) -> Result<HashMap<String, GodotValue>, ValueTypeError> {
+    let invalid = || ValueTypeError::Invalid {
+        type_name: "dice",
+        text: text.to_string(),
+        expected: "like 2d6 or 1d20+3",
+    };
+    let caps = DICE_RE.captures(text.trim()).ok_or_else(invalid)?;
+    let count = match &caps[1] {
+        "" => 1,
+        count => count.parse().map_err(|_| invalid())?,
+    };
+    let sides: i64 = caps[2].parse().map_err(|_| invalid())?;
+    let modifier: i64 = match caps.get(4) {
+        Some(m) => m.as_str().parse().map_err(|_| invalid())?,
+        None => 0,
+    };
+    if count == 0 || sides == 0 {
+        return Err(invalid());
+    }
+    let modifier = match caps.get(3).map(|sign| sign.as_str()) {
+        Some("-") => -modifier,
+        _ => modifier,
+    };
+    Ok(HashMap::from([
+        ("count".to_string(), GodotValue::Int(count)),
+        ("sides".to_string(), GodotValue::Int(sides)),
+        ("modifier".to_string(), GodotValue::Int(modifier)),
+    ]))
+}
+
+/// `unit` of durations counted in seconds.
+const SECONDS: i64 = 0;
+/// `unit` of durations counted in turns.
+const TURNS: i64 = 1;
+
+static DURATION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d+(?:\.\d+)?|\.\d+)\s*([a-zA-Z]+)$").expect("valid regex"));
+
+/// `5s`, `1.5 minutes`, `300ms` or `2 turns`. Times are in seconds (`unit` 0), and
+/// turns or rounds are counted as they are (`unit` 1).
+fn duration(text: &str) -> Result<HashMap<String, GodotValue>, ValueTypeError> {
+    let invalid = || ValueTypeError::Invalid {
+        type_name: "duration",
+        text: text.to_string(),
+        expected: "like 5s, 1.5 minutes or 2 turns",
+    };
+    let caps = DURATION_RE.captures(text.trim()).ok_or_else(invalid)?;
+    let amount: f64 = caps[1].parse().map_err(|_| invalid())?;
+    let (scale, unit) = match caps[2].to_lowercase().as_str() {
+        "ms" | "millisecond" | "milliseconds" => (0.001, SECONDS),
+        "s" | "sec" | "secs" | "second" | "seconds" => (1.0, SECONDS),
+        "m" | "min" | "mins" | "minute" | "minutes" => (60.0, SECONDS),
+        "h" | "hr" | "hrs" | "hour" | "hours" => (3600.0, SECONDS),
+        "turn" | "turns" | "round" | "rounds" => (1.0, TURNS),
+        _ => return Err(invalid()),
+    };
+    Ok(HashMap::from([
+        ("value".to_string(), GodotValue::Float(amount * scale)),
+        ("unit".to_string(), GodotValue::Int(unit)),
+    ]))
+}
+
+static PERCENT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^([-+])?\s*(\d+(?:\.\d+)?|\.\d+)\s*(?:%|percent\b)$").expect("valid regex")
+});
+
+/// `15%`, `+15%`, `-2.5 %` or `15 percent`, as a fraction (`0.15`). `additive` is
+/// whether it adds to a value instead of multiplying it, written with a sign unless
+/// the parameter is `percent_additive` or `percent_multiplicative`.
+fn percent(
+    text: &str,
+    additive: Option<bool>,
+) -> Result<HashMap<String, GodotValue>, ValueTypeError> {
+    let invalid = || ValueTypeError::Invalid {
+        type_name: "percent",
+        text: text.to_string(),
+        expected: "like 15%, +15% or 15 percent",
+    };
+    let caps = PERCENT_RE.captures(text.trim()).ok_or_else(invalid)?;
+    let amount: f64 = caps[2].parse().map_err(|_| invalid())?;
+    let sign = caps.get(1).map(|sign| sign.as_str());
+    let value = match sign {
+        Some("-") => -amount / 100.0,
+        _ => amount / 100.0,
+    };
+    Ok(HashMap::from([
+        ("value".to_string(), GodotValue::Float(value)),
+        (
+            "additive".to_string(),
+            GodotValue::Bool(additive.unwrap_or(sign.is_some())),
+        ),
+    ]))
+}
+
+const NUMBER: &str = r"-?(?:\d+(?:\.\d+)?|\.\d+)";
+
+static RANGE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i)^(?:between\s+({n})\s+and\s+({n})|({n})\s*(?:-|–|\.\.|to)\s*({n})|({n}))$",
+        n = NUMBER
+    ))
+    .expect("valid regex")
+});
+
+/// `3-7`, `3 to 7`, `3..7`, `between 3 and 7`, or `5` for `5-5`. Bounds are ints
+/// unless one of them has decimals.
+fn range(text: &str) -> Result<HashMap<String, GodotValue>, ValueTypeError> {
+    let invalid = |expected| ValueTypeError::Invalid {
+        type_name: "range",
+        text: text.to_string(),
+        expected,
+    };
+    let syntax = "like 3-7, 3 to 7 or between 3 and 7";
+    let caps = RANGE_RE
+        .captures(text.trim())
+        .ok_or_else(|| invalid(syntax))?;
+    let bounds: Vec<&str> = caps.iter().skip(1).flatten().map(|m| m.as_str()).collect();
+    let (min, max) = match bounds[..] {
+        [min, max] => (min, max),
+        [single] => (single, single),
+        _ => return Err(invalid(syntax)),
+    };
+    let bound = |b: &str| -> Result<GodotValue, ValueTypeError> {
+        if min.contains('.') || max.contains('.') {
+            b.parse()
+                .map(GodotValue::Float)
+                .map_err(|_| invalid(syntax))
+        } else {
+            b.parse().map(GodotValue::Int).map_err(|_| invalid(syntax))
+        }
+    };
+    let (min_value, max_value) = (bound(min)?, bound(max)?);
+    let as_float = |v: &GodotValue| match v {
+        GodotValue::Int(i) => *i as f64,
+        GodotValue::Float(f) => *f,
+        _ => 0.0,
+    };
+    if as_float(&min_value) > as_float(&max_value) {
+        return Err(invalid("the minimum is above the maximum"));
+    }
+    Ok(HashMap::from([
+        ("min".to_string(), min_value),
+        ("max".to_string(), max_value),
+    ]))
+}
+
+/// `value_types:` value type -> resource class its values are built as, Dictionaries without.
+#[derive(Debug, Clone, Default)]
+pub struct ValueTypes {
+    classes: HashMap<&'static str, String>,
+}
+
+impl ValueTypes {
+    pub fn from_yaml(y: &Yaml) -> Result<Self, ConfigError> {
+        let mut types = Self::default();
+        let Some(hash) = y.as_hash() else {
+            return Ok(types);
+        };
+        for (name, class) in hash {
+            let (Some(name), Some(class)) = (name.as_str(), class.as_str()) else {
+                return Err(ConfigError::Invalid(
+                    "'value_types' entries must be `type: class`".into(),
+                ));
+            };
+            let ty = ValueType::from_name(name).ok_or_else(|| {
+                ConfigError::Invalid(format!("value_types : unknown type '{}'", name))
+            })?;
+            types.classes.insert(ty.name(), class.to_string());
+        }
+        Ok(types)
+    }
+
+    fn value(&self, ty: ValueType, fields: HashMap<String, GodotValue>) -> GodotValue {
+        match self.classes.get(ty.name()) {
+            Some(class) => GodotValue::Resource {
+                type_name: class.clone(),
+                abstract_type_name: class.clone(),
+                fields,
+            },
+            None => GodotValue::Dict(fields),
+        }
+    }
+}
+
+/// The `value_types` stage : reads the parameters of value types, whatever the
+/// sentence parser made of them.
+#[derive(Debug, Clone, Default)]
+pub struct ValueTypeParser(pub ValueTypes);
+
+impl ValueTypeParser {
+    fn process_node(&self, node: &mut DokeNode) {
+        for (_, constituent) in node.constituents.iter_mut() {
+            let ty = match constituent.parse_data.get("sentence_type") {
+                Some(GodotValue::String(name)) => ValueType::from_name(name),
+                _ => None,
+            };
+            if let Some(ty) = ty {
+                constituent.state = match ty.parse(&constituent.statement) {
+                    Ok(fields) => DokeNodeState::Resolved(Box::new(self.0.value(ty, fields))),
+                    Err(e) => DokeNodeState::Error(Box::new(e)),
+                };
+            }
+            self.process_node(constituent);
+        }
+        for child in &mut node.children {
+            self.process_node(child);
+        }
+    }
+}
+
+impl DokeParser for ValueTypeParser {
+    fn process(&self, node: &mut DokeNode, _frontmatter: &HashMap<String, GodotValue>) {
+        self.process_node(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(type_name: &str, text: &str) -> Option<HashMap<String, GodotValue>> {
+        ValueType::from_name(type_name)?.parse(text).ok()
+    }
+
+    fn round_trip(type_name: &str, text: &str) -> Option<String> {
+        write(type_name, &GodotValue::Dict(parse(type_name, text)?))
+    }
+
+    fn ints(fields: &[(&str, i64)]) -> Option<HashMap<String, GodotValue>> {
+        Some(
+            fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), GodotValue::Int(*v)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn dice() {
+        let dice = |count, sides, modifier| {
+            ints(&[("count", count), ("sides", sides), ("modifier", modifier)])
+        };
+        assert_eq!(parse("dice", "2d6+3"), dice(2, 6, 3));
+        assert_eq!(parse("dice", "d20"), dice(1, 20, 0));
+        assert_eq!(parse("dice", "1D20 - 1"), dice(1, 20, -1));
+        assert_eq!(parse("dice", "0d6"), None);
+        assert_eq!(parse("dice", "2d0"), None);
+        assert_eq!(parse("dice", "2d"), None);
+        for text in ["2d6", "2d6+3", "1d20-1"] {
+            assert_eq!(round_trip("dice", text).as_deref(), Some(text));
+        }
+    }
+
+    #[test]
+    fn duration() {
+        let duration = |value, unit| {
+            Some(HashMap::from([
+                ("value".to_string(), GodotValue::Float(value)),
+                ("unit".to_string(), GodotValue::Int(unit)),
+            ]))
+        };
+        assert_eq!(parse("duration", "1.5 minutes"), duration(90.0, SECONDS));
+        assert_eq!(parse("duration", "300ms"), duration(0.3, SECONDS));
+        assert_eq!(parse("duration", "2 Turns"), duration(2.0, TURNS));
+        assert_eq!(parse("duration", "5 parsecs"), None);
+        assert_eq!(parse("duration", "-5s"), None);
+        for text in ["5s", "1.5s", "1 turn", "3 turns"] {
+            assert_eq!(round_trip("duration", text).as_deref(), Some(text));
+        }
+    }
+
+    #[test]
+    fn percent() {
+        let percent = |value, additive| {
+            Some(HashMap::from([
+                ("value".to_string(), GodotValue::Float(value)),
+                ("additive".to_string(), GodotValue::Bool(additive)),
+            ]))
+        };
+        assert_eq!(parse("percent", "15%"), percent(0.15, false));
+        assert_eq!(parse("percent", "+15 percent"), percent(0.15, true));
+        assert_eq!(parse("percent", "-2.5 %"), percent(-0.025, true));
+        assert_eq!(parse("percent_additive", "15%"), percent(0.15, true));
+        assert_eq!(
+            parse("percent_multiplicative", "+15%"),
+            percent(0.15, false)
+        );
+        assert_eq!(parse("percent", "15"), None);
+        for text in ["15%", "+15%", "-2.5%", "0.5%"] {
+            assert_eq!(round_trip("percent", text).as_deref(), Some(text));
+        }
+    }
+
+    #[test]
+    fn percent_errors_name_the_type() {
+        let error = ValueType::from_name("percent")
+            .and_then(|ty| ty.parse("lots").err())
+            .map(|e| e.to_string());
+        assert_eq!(
+            error.as_deref(),
+            Some("'lots' isn't a percent (like 15%, +15% or 15 percent)")
+        );
+    }
+
+    #[test]
+    fn range() {
+        let range = |min, max| ints(&[("min", min), ("max", max)]);
+        assert_eq!(parse("range", "3-7"), range(3, 7));
+        assert_eq!(parse("range", "-3-7"), range(-3, 7));
+        assert_eq!(parse("range", "-7--3"), range(-7, -3));
+        assert_eq!(parse("range", "between 3 and 7"), range(3, 7));
+        assert_eq!(parse("range", "3..7"), range(3, 7));
+        assert_eq!(parse("range", "5"), range(5, 5));
+        assert_eq!(parse("range", "7-3"), None);
+        assert_eq!(
+            parse("range", "1-2.5"),
+            Some(HashMap::from([
+                ("min".to_string(), GodotValue::Float(1.0)),
+                ("max".to_string(), GodotValue::Float(2.5)),
+            ]))
+        );
+        for text in ["3-7", "-3 to 7", "5", "1.5-2.5"] {
+            assert_eq!(round_trip("range", text).as_deref(), Some(text));
+        }
+        // Negative bounds are written with `to`, not `-`
+        assert_eq!(round_trip("range", "-3-7").as_deref(), Some("-3 to 7"));
+    }
+}
@@ -0,0 +1,92 @@
+// variables.rs
+// Project-wide constants substituted into documents as `{{name}}` before
+// anything parses them, so balancing values live in one place.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use doke::GodotValue;
+use thiserror::Error;
+
+use crate::config::{self, ConfigError};
+use crate::frontmatter::{normalize_key, yaml_to_godot};
+use crate::import::content_hash;
+
+#[derive(Debug, Error)]
+pub enum VariableError {
+    #[error("Unknown variable {{{{{0}}}}}")]
+    Unknown(String),
+    #[error("Unclosed {{{{ in : {0}")]
+    Unclosed(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    values: HashMap<String, GodotValue>,
+}
+
+impl Variables {
+    /// Loads a yaml file of variables. Nested maps are reached with `{{a.b}}`.
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let mut vars = Self::default();
+        if let GodotValue::Dict(map) = yaml_to_godot(config::load_yaml(path)?) {
+            vars.values = map;
+        }
+        Ok(vars)
+    }
+
+    /// Changes when any variable does.
+    pub fn hash(&self) -> u64 {
+        content_hash(&GodotValue::Dict(self.values.clone()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Adds `other`'s variables, replacing ours on conflicts.
+    pub fn extend(&mut self, other: &Variables) {
+        self.values
+            .extend(other.values.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&GodotValue> {
+        let mut parts = name.split('.').map(normalize_key);
+        let mut current = self.values.get(&parts.next()?)?;
+        for part in parts {
+            current = match current {
+                GodotValue::Dict(map) => map.get(&part)?,
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+
+    /// Replaces every `{{name}}` in the text. Unknown names are errors,
+    /// so a typo doesn't silently end up in a resource.
+    pub fn interpolate(&self, text: &str) -> Result<String, VariableError> {
+        if !text.contains("{{") {
+            return Ok(text.to_string());
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let end = after.find("}}").ok_or_else(|| {
+                VariableError::Unclosed(after.lines().next().unwrap_or_default().to_string())
+            })?;
+            let name = after[..end].trim();
+            let value = self
+                .get(name)
+                .ok_or_else(|| VariableError::Unknown(name.to_string()))?;
+            match value {
+                GodotValue::String(s) => out.push_str(s),
+                other => out.push_str(&other.to_string()),
+            }
+            rest = &after[end + 2..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
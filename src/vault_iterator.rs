@@ -0,0 +1,99 @@
+// vault_iterator.rs
+// GDScript iterator over the documents of a file type, reading each one only
+// when asked, so scanning metadata doesn't pay for full imports upfront.
+
+use godot::prelude::*;
+
+use crate::DokeImporter;
+
+#[derive(GodotClass)]
+#[class(no_init, base=RefCounted)]
+/// Iterates the documents of a file type (see `documents:` in its config).
+/// Use `has_next()`/`next()`, or `for path in iterator`, then `get_frontmatter()`
+/// or `import()` for the current document.
+pub struct DokeVaultIterator {
+    importer: Gd<DokeImporter>,
+    file_type: String,
+    paths: Vec<String>,
+    /// Index of the next document
+    position: usize,
+    base: Base<RefCounted>,
+}
+
+impl DokeVaultIterator {
+    pub fn create(importer: Gd<DokeImporter>, file_type: String, paths: Vec<String>) -> Gd<Self> {
+        Gd::from_init_fn(|base| Self {
+            importer,
+            file_type,
+            paths,
+            position: 0,
+            base,
+        })
+    }
+
+    fn current(&self) -> Option<&String> {
+        self.position.checked_sub(1).and_then(|i| self.paths.get(i))
+    }
+}
+
+#[godot_api]
+impl DokeVaultIterator {
+    #[func]
+    fn has_next(&self) -> bool {
+        self.position < self.paths.len()
+    }
+
+    #[func]
+    /// Moves to the next document and returns its path, or an empty string past the end.
+    fn next(&mut self) -> GString {
+        if !self.has_next() {
+            return GString::new();
+        }
+        self.position += 1;
+        self.current().map(GString::from).unwrap_or_default()
+    }
+
+    #[func]
+    /// Number of documents, whether iterated or not.
+    fn size(&self) -> i64 {
+        self.paths.len() as i64
+    }
+
+    #[func]
+    /// Frontmatter of the current document, without parsing its statements.
+    fn get_frontmatter(&self) -> Dictionary {
+        match self.current() {
+            Some(path) => self.importer.bind().get_doke_frontmatter(path.clone()),
+            None => Dictionary::new(),
+        }
+    }
+
+    #[func]
+    /// Fully imports the current document.
+    fn import(&self) -> Option<Gd<Resource>> {
+        let path = self.current()?.clone();
+        self.importer
+            .bind()
+            .import_doke(self.file_type.clone(), path)
+    }
+
+    // GDScript `for` loop protocol
+    #[func]
+    fn _iter_init(&mut self, _state: Variant) -> bool {
+        self.position = 0;
+        self.next();
+        !self.paths.is_empty()
+    }
+
+    #[func]
+    fn _iter_next(&mut self, _state: Variant) -> bool {
+        let more = self.has_next();
+        self.next();
+        more
+    }
+
+    #[func]
+    fn _iter_get(&self, _state: Variant) -> GString {
+        self.current().map(GString::from).unwrap_or_default()
+    }
+}
@@ -0,0 +1,134 @@
+// wasm_parser.rs
+// Parsers compiled to WebAssembly, run in a sandbox : no imports (so no file or network
+// access), bounded memory and fuel. Third-party parsers can run inside the editor without
+// trusting native code. Only built with the `wasm` feature.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use doke::GodotValue;
+use doke::semantic::{DokeNode, DokeNodeState, DokeParser};
+use godot::classes::Json;
+use godot::prelude::*;
+use thiserror::Error;
+use wasmtime::{Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::callable_parser::{context, state_from_result};
+use crate::import::{Conversion, frontmatter_to_dictionary};
+
+/// Memory a parser may grow to.
+const MAX_MEMORY: usize = 64 << 20;
+/// Instructions (roughly) a parser may run for one top-level statement and its children.
+const FUEL: u64 = 100_000_000;
+
+#[derive(Debug, Error)]
+pub enum WasmError {
+    #[error("{0}")]
+    Wasmtime(#[from] wasmtime::Error),
+    #[error("the module doesn't follow the parser ABI : {0}")]
+    Abi(String),
+}
+
+/// A WebAssembly parser. The module exports `memory`, `doke_alloc(len: i32) -> i32` and
+/// `doke_parse(ptr: i32, len: i32) -> i64`. `doke_parse` gets the JSON of
+/// `{statement, frontmatter, parse_data, options}` written at `ptr`, and returns
+/// `out_ptr << 32 | out_len` where it wrote its JSON result, shaped like the Dictionary
+/// of a GDScript parser.
+pub struct WasmParser {
+    engine: Engine,
+    module: Module,
+}
+
+impl std::fmt::Debug for WasmParser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("WasmParser")
+    }
+}
+
+struct Sandbox {
+    store: Store<StoreLimits>,
+    instance: Instance,
+}
+
+impl WasmParser {
+    pub fn load(path: &Path) -> Result<Self, WasmError> {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::from_file(&engine, path)?;
+        if module.imports().len() > 0 {
+            return Err(WasmError::Abi("parsers can't import anything".into()));
+        }
+        Ok(Self { engine, module })
+    }
+
+    fn sandbox(&self) -> Result<Sandbox, WasmError> {
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL)?;
+        let instance = Instance::new(&mut store, &self.module, &[])?;
+        Ok(Sandbox { store, instance })
+    }
+
+    fn process_node(&self, sandbox: &mut Sandbox, node: &mut DokeNode, frontmatter: &Dictionary) {
+        if matches!(node.state, DokeNodeState::Unresolved) {
+            let mut input = context(node, frontmatter);
+            input.set("statement", node.statement.clone());
+            match sandbox.parse(&Json::stringify(&input.to_variant()).to_string()) {
+                Ok(output) => {
+                    let returned = Json::parse_string(&output);
+                    if let Some(state) = returned
+                        .try_to::<Dictionary>()
+                        .ok()
+                        .and_then(|d| state_from_result(&d))
+                    {
+                        node.state = state;
+                    }
+                }
+                Err(e) => node.state = DokeNodeState::Error(Box::new(e)),
+            }
+        }
+        for child in &mut node.children {
+            self.process_node(sandbox, child, frontmatter);
+        }
+    }
+}
+
+impl Sandbox {
+    fn parse(&mut self, input: &str) -> Result<String, WasmError> {
+        let memory = self
+            .instance
+            .get_memory(&mut self.store, "memory")
+            .ok_or_else(|| WasmError::Abi("no exported `memory`".into()))?;
+        let alloc = self
+            .instance
+            .get_typed_func::<i32, i32>(&mut self.store, "doke_alloc")?;
+        let parse = self
+            .instance
+            .get_typed_func::<(i32, i32), i64>(&mut self.store, "doke_parse")?;
+        let len = i32::try_from(input.len()).map_err(|_| WasmError::Abi("input too big".into()))?;
+        let ptr = alloc.call(&mut self.store, len)?;
+        memory
+            .write(&mut self.store, ptr as u32 as usize, input.as_bytes())
+            .map_err(|_| WasmError::Abi("`doke_alloc` returned memory out of bounds".into()))?;
+        let out = parse.call(&mut self.store, (ptr, len))? as u64;
+        let (out_ptr, out_len) = ((out >> 32) as usize, (out & 0xffff_ffff) as usize);
+        let bytes = memory
+            .data(&self.store)
+            .get(out_ptr..out_ptr + out_len)
+            .ok_or_else(|| WasmError::Abi("result out of the module's memory".into()))?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| WasmError::Abi("result isn't UTF-8".into()))
+    }
+}
+
+impl DokeParser for WasmParser {
+    fn process(&self, node: &mut DokeNode, frontmatter: &HashMap<String, GodotValue>) {
+        let frontmatter =
+            frontmatter_to_dictionary(frontmatter, &Conversion::default()).unwrap_or_default();
+        match self.sandbox() {
+            Ok(mut sandbox) => self.process_node(&mut sandbox, node, &frontmatter),
+            Err(e) => node.state = DokeNodeState::Error(Box::new(e)),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+// watch.rs
+// Re-imports the documents of watched file types when they change on disk,
+// for authors editing them in an external markdown editor.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use godot::classes::editor_toaster::Severity;
+use godot::classes::{EditorInterface, Engine, Os, ProjectSettings, ResourceLoader, Script};
+
+/// Modification times and content hashes of the documents of each watched file type.
+#[derive(Debug, Default)]
+pub struct Watcher {
+    watched: HashMap<String, HashMap<PathBuf, FileState>>,
+    /// The document and line of the last failed re-import, for `jump_to_watch_error`
+    pub last_error: Option<(String, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileState {
+    modified: Option<SystemTime>,
+    hash: Option<u64>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn content_hash(path: &Path) -> Option<u64> {
+    let bytes = std::fs::read(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+impl FileState {
+    fn of(path: &Path) -> Self {
+        Self {
+            modified: modified(path),
+            hash: content_hash(path),
+        }
+    }
+}
+
+impl Watcher {
+    /// Starts watching a file type, from the current state of its documents.
+    pub fn watch(&mut self, file_type: &str, paths: Vec<PathBuf>) {
+        let files = paths
+            .into_iter()
+            .map(|p| {
+                let state = FileState::of(&p);
+                (p, state)
+            })
+            .collect();
+        self.watched.insert(file_type.to_string(), files);
+    }
+
+    pub fn unwatch(&mut self, file_type: &str) -> bool {
+        self.watched.remove(file_type).is_some()
+    }
+
+    /// Counts every document of the file type as changed at the next poll,
+    /// e.g. when its parsers changed.
+    pub fn invalidate(&mut self, file_type: &str) {
+        for state in self
+            .watched
+            .get_mut(file_type)
+            .into_iter()
+            .flat_map(|f| f.values_mut())
+        {
+            *state = FileState {
+                modified: None,
+                hash: None,
+            };
+        }
+    }
+
+    pub fn file_types(&self) -> Vec<String> {
+        self.watched.keys().cloned().collect()
+    }
+
+    /// The documents that are new or were modified since the last call, given
+    /// the current documents of the type. Deleted documents are forgotten.
+    /// Documents saved without changing their bytes (autosaves, touches) aren't counted.
+    pub fn changed(&mut self, file_type: &str, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        let Some(files) = self.watched.get_mut(file_type) else {
+            return Vec::new();
+        };
+        let mut changed = Vec::new();
+        let mut current = HashMap::with_capacity(paths.len());
+        for path in paths {
+            let previous = files.get(&path).copied();
+            let time = modified(&path);
+            let state = match previous {
+                Some(state) if state.modified == time => state,
+                _ => {
+                    let state = FileState::of(&path);
+                    if previous.map(|p| p.hash) != Some(state.hash) {
+                        changed.push(path.clone());
+                    }
+                    state
+                }
+            };
+            current.insert(path, state);
+        }
+        *files = current;
+        changed
+    }
+}
+
+/// Shows the result of a re-import as a toast when running in the editor.
+/// The tooltip has the whole error, with the document's path and line to jump to.
+pub fn toast(path: &str, error: Option<(&str, usize)>) {
+    if !Engine::singleton().is_editor_hint() {
+        return;
+    }
+    let Some(mut toaster) = EditorInterface::singleton().get_editor_toaster() else {
+        return;
+    };
+    let name = Path::new(path)
+        .file_name()
+        .map_or(path.into(), |n| n.to_string_lossy());
+    match error {
+        None => toaster
+            .push_toast_ex(&format!("Reimported {}", name))
+            .severity(Severity::INFO)
+            .tooltip(path)
+            .done(),
+        Some((e, line)) => toaster
+            .push_toast_ex(&format!("Couldn't reimport {} (line {})", name, line))
+            .severity(Severity::ERROR)
+            .tooltip(&format!(
+                "{}:{} : {}\njump_to_watch_error() opens it",
+                path, line, e
+            ))
+            .done(),
+    }
+}
+
+/// Opens a document at `line` in the editor's script editor when it loads as a script.
+/// Anything else, like a document imported as a resource, opens in the system's editor.
+pub fn open_at(path: &str, line: usize) -> bool {
+    let local = ProjectSettings::singleton().localize_path(path).to_string();
+    if Engine::singleton().is_editor_hint()
+        && local.starts_with("res://")
+        && let Some(script) = ResourceLoader::singleton()
+            .load(&local)
+            .and_then(|res| res.try_cast::<Script>().ok())
+    {
+        EditorInterface::singleton()
+            .edit_script_ex(&script)
+            .line(line as i32)
+            .done();
+        return true;
+    }
+    let global = ProjectSettings::singleton()
+        .globalize_path(path)
+        .to_string();
+    Os::singleton().shell_open(&global) == godot::global::Error::OK
+}
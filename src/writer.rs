@@ -0,0 +1,301 @@
+// writer.rs
+// Values written back as doke markdown, the sentence patterns read the other way, so
+// tools can edit documents in bulk (renames, balance passes) and save them to their files.
+
+use std::collections::HashMap;
+
+use doke::GodotValue;
+use doke::file_builder::{FieldConfig, FieldType};
+use godot::classes::Script;
+use godot::global::PropertyUsageFlags;
+use godot::prelude::*;
+use thiserror::Error;
+use yaml_rust2::Yaml;
+
+use crate::config::{emit_yaml, variant_to_yaml};
+use crate::grammar::{PARAM_RE, Pattern, Returns};
+use crate::middleware;
+use crate::naming::Naming;
+use crate::value_types;
+
+#[derive(Debug, Error)]
+pub enum WriteError {
+    #[error("no sentence of {0} writes this {1}")]
+    NoSentence(String, String),
+    #[error("the root value must be a resource, got {0}")]
+    NotAResource(String),
+    #[error("can't write {0}")]
+    Value(String),
+}
+
+/// Writes values with the patterns of a file type.
+pub struct Writer<'a> {
+    pub patterns: &'a [Pattern],
+    pub naming: &'a Naming,
+}
+
+fn describe(value: &GodotValue) -> String {
+    match value {
+        GodotValue::Resource { type_name, .. } => type_name.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+impl Writer<'_> {
+    /// The statements of `root`, in the order of the `children` fields they fill.
+    pub fn statements(
+        &self,
+        children: &[FieldConfig],
+        root: &GodotValue,
+    ) -> Result<Vec<String>, WriteError> {
+        let GodotValue::Resource { fields, .. } = root else {
+            return Err(WriteError::NotAResource(describe(root)));
+        };
+        let mut statements = Vec::new();
+        for child in children {
+            let (ty, values) = match (&child.ty, self.field(fields, &child.name)) {
+                (_, None | Some(GodotValue::Nil)) => continue,
+                (FieldType::Single(ty), Some(value)) => (ty, vec![value]),
+                (FieldType::Array(ty), Some(GodotValue::Array(items))) => {
+                    (ty, items.iter().collect())
+                }
+                (FieldType::Array(_), Some(other)) => {
+                    return Err(WriteError::Value(format!(
+                        "{} as the list {}",
+                        describe(other),
+                        child.name
+                    )));
+                }
+            };
+            for value in values {
+                statements.push(self.statement(ty, value)?);
+            }
+        }
+        Ok(statements)
+    }
+
+    /// A field of a resource, under the name of its parameter or the property it was renamed to.
+    fn field<'v>(
+        &self,
+        fields: &'v HashMap<String, GodotValue>,
+        name: &str,
+    ) -> Option<&'v GodotValue> {
+        fields
+            .get(name)
+            .or_else(|| fields.get(&self.naming.property_name(name)))
+    }
+
+    /// The sentence of `abstract_type` writing `value`, with its statements below it
+    /// for the resources it holds that no parameter writes.
+    pub fn statement(&self, abstract_type: &str, value: &GodotValue) -> Result<String, WriteError> {
+        let mut text = self
+            .render(|p| p.target == abstract_type, value)
+            .ok_or_else(|| WriteError::NoSentence(abstract_type.to_string(), describe(value)))?;
+        for child in self.nested(value) {
+            let child = self
+                .render(|_| true, child)
+                .ok_or_else(|| WriteError::NoSentence("any type".into(), describe(child)))?;
+            for line in child.lines() {
+                text.push_str("\n  ");
+                text.push_str(line);
+            }
+        }
+        Ok(text)
+    }
+
+    /// The resources of `value`'s fields that none of its sentence's parameters write.
+    fn nested<'v>(&self, value: &'v GodotValue) -> Vec<&'v GodotValue> {
+        let GodotValue::Resource { fields, .. } = value else {
+            return Vec::new();
+        };
+        let used = self
+            .best(|_| true, value)
+            .map(|(p, _)| p.parameters.clone())
+            .unwrap_or_default();
+        let mut names: Vec<_> = fields
+            .keys()
+            .filter(|k| {
+                !used
+                    .iter()
+                    .any(|(n, _)| self.naming.property_name(n) == **k || n == *k)
+            })
+            .collect();
+        names.sort();
+        let mut nested = Vec::new();
+        for name in names {
+            let items = match &fields[name] {
+                GodotValue::Array(items) => items.iter().collect(),
+                v => vec![v],
+            };
+            for item in items {
+                if let GodotValue::Resource { .. } = item {
+                    nested.push(item);
+                }
+            }
+        }
+        nested
+    }
+
+    /// The text of the pattern among those `accepts` that writes `value` with the most parameters,
+    /// the first one of the dokedefs on a tie.
+    fn render(&self, accepts: impl Fn(&Pattern) -> bool, value: &GodotValue) -> Option<String> {
+        self.best(accepts, value).map(|(_, text)| text)
+    }
+
+    fn best(
+        &self,
+        accepts: impl Fn(&Pattern) -> bool,
+        value: &GodotValue,
+    ) -> Option<(&Pattern, String)> {
+        self.patterns
+            .iter()
+            .filter(|p| accepts(p))
+            .filter_map(|p| Some((p, self.fill(p, value)?)))
+            .min_by_key(|(p, _)| std::cmp::Reverse(p.parameters.len()))
+    }
+
+    /// `pattern` with its parameters replaced by the fields of `value`, when it writes it.
+    fn fill(&self, pattern: &Pattern, value: &GodotValue) -> Option<String> {
+        let fields = match (&pattern.returns, value) {
+            (Returns::Literal(literal), _) if literal == value => {
+                return pattern
+                    .parameters
+                    .is_empty()
+                    .then(|| pattern.pattern.clone());
+            }
+            (
+                Returns::Type(ty),
+                GodotValue::Resource {
+                    type_name, fields, ..
+                },
+            ) if ty == type_name => fields,
+            _ => return None,
+        };
+        let mut out = String::new();
+        let mut last = 0;
+        for (caps, (name, ty)) in PARAM_RE
+            .captures_iter(&pattern.pattern)
+            .zip(&pattern.parameters)
+        {
+            let whole = caps.get(0)?;
+            out.push_str(&pattern.pattern[last..whole.start()]);
+            last = whole.end();
+            match self.field(fields, name) {
+                None | Some(GodotValue::Nil) => return None,
+                Some(v) => out.push_str(&self.parameter(ty, v)?),
+            }
+        }
+        out.push_str(&pattern.pattern[last..]);
+        Some(out)
+    }
+
+    /// A parameter's text : builtin and value types as written in documents, the others
+    /// with the patterns of their section, then any pattern as doke tries them all.
+    fn parameter(&self, ty: &str, value: &GodotValue) -> Option<String> {
+        if let Some(text) = value_types::write(ty, value) {
+            return Some(text);
+        }
+        match (ty.to_lowercase().as_str(), value) {
+            ("int", GodotValue::Int(i)) => Some(i.to_string()),
+            ("int", GodotValue::Float(f)) if f.fract() == 0.0 => Some((*f as i64).to_string()),
+            ("float", GodotValue::Float(f)) => Some(f.to_string()),
+            ("float", GodotValue::Int(i)) => Some(i.to_string()),
+            ("bool", GodotValue::Bool(b)) => Some(b.to_string()),
+            ("string", GodotValue::String(s)) => Some(s.clone()),
+            _ => self
+                .render(|p| p.section == ty, value)
+                .or_else(|| self.render(|_| true, value))
+                .or_else(|| match value {
+                    GodotValue::String(s) => Some(s.clone()),
+                    GodotValue::Int(i) => Some(i.to_string()),
+                    GodotValue::Float(f) => Some(f.to_string()),
+                    _ => None,
+                }),
+        }
+    }
+}
+
+/// A document : its frontmatter, then its statements as a list.
+pub fn document(frontmatter: &Yaml, statements: &[String]) -> String {
+    let mut out = match frontmatter {
+        Yaml::Hash(h) if !h.is_empty() => format!("{}\n", emit_yaml(frontmatter)),
+        _ => "---\n".to_string(),
+    };
+    out.push_str("---\n");
+    for statement in statements {
+        out.push_str("- ");
+        out.push_str(statement);
+        out.push('\n');
+    }
+    out
+}
+
+/// `file_text` with its frontmatter and statements replaced by `document`,
+/// keeping what follows the doke section (from its closing `---`).
+pub fn replace_document(file_text: &str, document: &str) -> String {
+    let mut separators = 0;
+    let mut offset = 0;
+    for line in file_text.split_inclusive('\n') {
+        if line.trim() == "---" {
+            separators += 1;
+            if separators == 3 {
+                return format!("{}\n{}", document, &file_text[offset..]);
+            }
+        }
+        offset += line.len();
+    }
+    document.to_string()
+}
+
+/// The frontmatter of a document from a Dictionary.
+pub fn frontmatter(dict: &Dictionary) -> Result<Yaml, WriteError> {
+    variant_to_yaml(&dict.to_variant()).map_err(|e| WriteError::Value(e.to_string()))
+}
+
+/// A value to write : a built resource, read through its script variables,
+/// or its plain form as `get_doke`/`export_doke_json` give it (`{type, fields}`).
+pub fn value_of(v: &Variant) -> Result<GodotValue, WriteError> {
+    match v.get_type() {
+        VariantType::OBJECT => {
+            let res = v
+                .try_to::<Gd<Resource>>()
+                .map_err(|_| WriteError::Value(v.to_string()))?;
+            let type_name = res
+                .get_script()
+                .try_to::<Gd<Script>>()
+                .ok()
+                .map(|s| s.get_global_name().to_string())
+                .filter(|n| !n.is_empty())
+                .unwrap_or_else(|| res.get_class().to_string());
+            let mut fields = HashMap::new();
+            for property in res.get_property_list().iter_shared() {
+                let usage = property.get("usage").and_then(|u| u.try_to::<u64>().ok());
+                let Some(name) = property.get("name").map(|n| n.to_string()) else {
+                    continue;
+                };
+                if usage.is_some_and(|u| u & PropertyUsageFlags::SCRIPT_VARIABLE.ord() != 0) {
+                    fields.insert(name.clone(), value_of(&res.get(&name))?);
+                }
+            }
+            Ok(GodotValue::Resource {
+                abstract_type_name: type_name.clone(),
+                type_name,
+                fields,
+            })
+        }
+        VariantType::ARRAY => Ok(GodotValue::Array(
+            v.to::<VariantArray>()
+                .iter_shared()
+                .map(|item| value_of(&item))
+                .collect::<Result<_, _>>()?,
+        )),
+        VariantType::DICTIONARY => {
+            let mut map = HashMap::new();
+            for (k, item) in v.to::<Dictionary>().iter_shared() {
+                map.insert(k.to_string(), value_of(&item)?);
+            }
+            Ok(middleware::resource_or_dict(map))
+        }
+        _ => middleware::from_variant(v).map_err(|e| WriteError::Value(e.to_string())),
+    }
+}